@@ -0,0 +1,163 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::BASIS_POINTS;
+use crate::error::ErrorCode;
+use crate::state::Bank;
+
+// Share accounting for the deposit and borrow sides of a bank.
+//
+// Deposits and debts are tracked as shares of the bank's totals so that interest
+// accrued on the totals is spread over every holder. Every conversion rounds in the
+// protocol's favor: depositors get the floor of their shares and pay the ceiling
+// when burning them, borrowers take on the ceiling of their debt shares and only
+// burn the floor when repaying.
+
+fn mul_div_floor(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    if denominator == 0 {
+        return Err(ErrorCode::MathOverflow.into());
+    }
+    let result: u128 = value as u128 * numerator as u128 / denominator as u128;
+    u64::try_from(result).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+fn mul_div_ceil(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    if denominator == 0 {
+        return Err(ErrorCode::MathOverflow.into());
+    }
+    let result: u128 = (value as u128 * numerator as u128).div_ceil(denominator as u128);
+    u64::try_from(result).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Converts an amount into shares of a pool, rounding down.
+pub fn amount_to_shares_floor(amount: u64, total: u64, total_shares: u64) -> Result<u64> {
+    if total_shares == 0 || total == 0 {
+        return Ok(amount);
+    }
+    mul_div_floor(amount, total_shares, total)
+}
+
+/// Converts an amount into shares of a pool, rounding up.
+pub fn amount_to_shares_ceil(amount: u64, total: u64, total_shares: u64) -> Result<u64> {
+    if total_shares == 0 || total == 0 {
+        return Ok(amount);
+    }
+    mul_div_ceil(amount, total_shares, total)
+}
+
+/// Converts shares of a pool back into an amount, rounding down.
+pub fn shares_to_amount_floor(shares: u64, total: u64, total_shares: u64) -> Result<u64> {
+    if total_shares == 0 {
+        return Ok(0);
+    }
+    mul_div_floor(shares, total, total_shares)
+}
+
+/// Converts shares of a pool back into an amount, rounding up.
+pub fn shares_to_amount_ceil(shares: u64, total: u64, total_shares: u64) -> Result<u64> {
+    if total_shares == 0 {
+        return Ok(0);
+    }
+    mul_div_ceil(shares, total, total_shares)
+}
+
+/// Amount of the bank's deposits a holder of `shares` can withdraw.
+pub fn deposit_value(bank: &Bank, shares: u64) -> Result<u64> {
+    shares_to_amount_floor(shares, bank.total_deposits, bank.total_deposit_shares)
+}
+
+/// Amount of the bank's debt a holder of `shares` owes.
+pub fn borrowed_value(bank: &Bank, shares: u64) -> Result<u64> {
+    shares_to_amount_ceil(shares, bank.total_borrowed, bank.total_borrowed_shares)
+}
+
+/// Records a deposit of `amount` and returns the shares minted for it. Fails once written-off debt has left the
+/// bank's shares worth nothing, since new shares would go to the holders of those.
+pub fn deposit(bank: &mut Bank, amount: u64) -> Result<u64> {
+    if bank.total_deposits == 0 && bank.total_deposit_shares != 0 {
+        return Err(ErrorCode::SharesWithoutAssets.into());
+    }
+
+    let shares: u64 = amount_to_shares_floor(amount, bank.total_deposits, bank.total_deposit_shares)?;
+
+    bank.total_deposits = bank.total_deposits.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_shares = bank.total_deposit_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(shares)
+}
+
+/// Records a withdrawal of `amount` by a holder of `user_shares` and returns the shares burned.
+pub fn withdraw(bank: &mut Bank, amount: u64, user_shares: u64) -> Result<u64> {
+    if amount > deposit_value(bank, user_shares)? {
+        return Err(ErrorCode::InsufficientFunds.into());
+    }
+
+    let shares: u64 = amount_to_shares_ceil(amount, bank.total_deposits, bank.total_deposit_shares)?.min(user_shares);
+
+    bank.total_deposits = bank.total_deposits.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_shares = bank.total_deposit_shares.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(shares)
+}
+
+/// Records a withdrawal of everything `user_shares` are worth, burning all of them, and returns the amount.
+pub fn withdraw_all(bank: &mut Bank, user_shares: u64) -> Result<u64> {
+    let amount: u64 = deposit_value(bank, user_shares)?;
+
+    bank.total_deposits = bank.total_deposits.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_shares = bank.total_deposit_shares.checked_sub(user_shares).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(amount)
+}
+
+/// Records a borrow of `amount` and returns the debt shares minted for it.
+pub fn borrow(bank: &mut Bank, amount: u64) -> Result<u64> {
+    let shares: u64 = amount_to_shares_ceil(amount, bank.total_borrowed, bank.total_borrowed_shares)?;
+
+    bank.total_borrowed = bank.total_borrowed.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_borrowed_shares = bank.total_borrowed_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(shares)
+}
+
+/// Records a repayment of `amount` by a holder of `user_shares` debt shares and returns the shares burned.
+pub fn repay(bank: &mut Bank, amount: u64, user_shares: u64) -> Result<u64> {
+    let owed: u64 = borrowed_value(bank, user_shares)?;
+    if amount > owed {
+        return Err(ErrorCode::OverRepay.into());
+    }
+
+    // Paying off the whole balance clears every share; otherwise burn the floor.
+    let shares: u64 = if amount == owed {
+        user_shares
+    } else {
+        amount_to_shares_floor(amount, bank.total_borrowed, bank.total_borrowed_shares)?.min(user_shares)
+    };
+
+    bank.total_borrowed = bank.total_borrowed.saturating_sub(amount);
+    bank.total_borrowed_shares = bank.total_borrowed_shares.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(shares)
+}
+
+/// Books `interest` paid into the bank. `reserve_factor_bps` of it is set aside for the insurance fund and the
+/// rest goes to depositors.
+pub fn credit_interest(bank: &mut Bank, interest: u64) -> Result<()> {
+    let reserve: u64 = mul_div_floor(interest, bank.reserve_factor_bps, BASIS_POINTS)?;
+
+    bank.insurance_fees_pending = bank.insurance_fees_pending.checked_add(reserve).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposits = bank.total_deposits.checked_add(interest - reserve).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+/// Writes off the debt behind `user_shares`, burning them and taking what they owed out of the bank's deposits so
+/// every depositor shares the loss. Returns the amount written off.
+pub fn write_off(bank: &mut Bank, user_shares: u64) -> Result<u64> {
+    let amount: u64 = borrowed_value(bank, user_shares)?;
+
+    bank.total_borrowed = bank.total_borrowed.saturating_sub(amount);
+    bank.total_borrowed_shares = bank.total_borrowed_shares.checked_sub(user_shares).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposits = bank.total_deposits.saturating_sub(amount);
+
+    Ok(amount)
+}
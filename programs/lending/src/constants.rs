@@ -1,9 +1,15 @@
 use anchor_lang::prelude::*;
 
-#[constants]
+#[constant]
 pub const SOL_USB_FEED_ID: &str = "0x7d9e2258cec229cf52873a8e58d035a276873c485d753860e56d248fb33ce68a";
 pub const USDC_USD_FEED_ID: &str = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
 pub const MAX_AGE: u64 = 100;
+// Reward streams each bank can run at once.
+pub const MAX_REWARD_STREAMS: usize = 2;
+pub const BASIS_POINTS: u64 = 10_000;
+pub const SECONDS_PER_YEAR: u64 = 31_536_000;
+// Decimals of USD amounts, such as outflow limits set in USD.
+pub const USD_DECIMALS: u8 = 6;
 
 
 // 0xeaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a
@@ -11,7 +11,94 @@ pub enum ErrorCode {
     OverRepay,
     #[msg("User is not under collaterized, can't be liquidated")]
     NotUnderCollaterized,
+    #[msg("Math operation overflowed")]
+    MathOverflow,
+    #[msg("Collateral and borrowed mints must be different")]
+    InvalidLiquidationMints,
+    #[msg("Signer is not allowed to manage this market")]
+    Unauthorized,
+    #[msg("Market is paused")]
+    MarketPaused,
+    #[msg("Mint uses a Token-2022 extension the program doesn't support")]
+    UnsupportedMintExtension,
+    #[msg("Collateral mint doesn't back the borrowed asset")]
+    InvalidCollateralMint,
+    #[msg("User still has an open deposit or borrow position")]
+    OpenPosition,
+    #[msg("Requested amount is more than the remaining borrow allowance")]
+    AllowanceExceeded,
+    #[msg("Borrow allowance has expired")]
+    AllowanceExpired,
+    #[msg("Isolated collateral can't be mixed with other collateral")]
+    IsolatedCollateralMixed,
+    #[msg("Asset can't be borrowed against isolated collateral")]
+    NotBorrowableInIsolation,
+    #[msg("Borrow would exceed the isolated collateral's debt ceiling")]
+    DebtCeilingExceeded,
+    #[msg("Isolated collateral's bank is required to settle its debt")]
+    IsolatedBankRequired,
+    #[msg("E-mode category doesn't exist")]
+    InvalidEmodeCategory,
+    #[msg("Position is outside the user's e-mode category")]
+    EmodeCategoryMismatch,
+    #[msg("Reward stream doesn't exist")]
+    InvalidRewardStream,
+    #[msg("Reward stream slot is already in use")]
+    RewardStreamInUse,
+    #[msg("User has shares or unclaimed rewards in another bank on this side")]
+    RewardBankMismatch,
+    #[msg("Term loan duration must be positive")]
+    InvalidDuration,
+    #[msg("Stable rate doesn't meet the conditions for a rebalance")]
+    RebalanceNotAllowed,
+    #[msg("Swap program must be an executable program other than this one")]
+    InvalidSwapProgram,
+    #[msg("Health factor is below the requested minimum")]
+    HealthFactorTooLow,
+    #[msg("Swap output is worth less than the slippage limit allows")]
+    SlippageExceeded,
+    #[msg("Liquidation close factor or bonus parameters are invalid")]
+    InvalidLiquidationConfig,
+    #[msg("Auction duration must be positive and its end discount at most 100%")]
+    InvalidAuctionConfig,
+    #[msg("Collateral from this bank is liquidated by auction")]
+    LiquidatedByAuction,
+    #[msg("Collateral from this bank isn't liquidated by auction")]
+    AuctionLiquidationDisabled,
+    #[msg("Auction is for a different user or banks")]
+    AuctionMismatch,
+    #[msg("Reserve factor must be at most 100%, the unstake cooldown non-negative and the unstake window positive")]
+    InvalidInsuranceConfig,
+    #[msg("Unstake cooldown hasn't elapsed or no unstake was requested")]
+    UnstakeCooldown,
+    #[msg("User has collateral left or no debt to cover")]
+    NoBadDebt,
+    #[msg("Outflow window must be non-negative")]
+    InvalidOutflowConfig,
+    #[msg("Net outflows from the bank exceed its limit for the window")]
+    OutflowRateLimited,
+    #[msg("A price update is required to value outflows in USD")]
+    PriceUpdateRequired,
+    #[msg("Account isn't a bank or user from before markets existed")]
+    InvalidLegacyAccount,
+    #[msg("Deposits for another user can't change their e-mode category or isolation")]
+    DepositForChangesMode,
+    #[msg("Bank doesn't hold the user's position")]
+    PositionBankMismatch,
+    #[msg("User has rewards left to claim")]
+    UnclaimedRewards,
+    #[msg("Position the auction was started on is still liquidatable")]
+    AuctionStillLiquidatable,
+    #[msg("Pool has shares outstanding but nothing left backing them")]
+    SharesWithoutAssets,
+    #[msg("User's term loan from the bank is required to settle its debt")]
+    TermLoanRequired,
+    #[msg("Oracle price must be positive")]
+    InvalidPrice,
+    #[msg("Loan-to-value and liquidation threshold must be in basis points, with the loan-to-value no higher")]
+    InvalidRiskParameters,
+    #[msg("E-mode category parameters are below the bank's own")]
+    EmodeBelowBankParameters,
+    #[msg("Unstake window has passed, request the unstake again")]
+    UnstakeWindowExpired,
 }
-
-
-
@@ -0,0 +1,198 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, Price, PriceUpdateV2};
+
+use crate::accounting;
+use crate::constants::{BASIS_POINTS, SOL_USB_FEED_ID, USDC_USD_FEED_ID};
+use crate::error::ErrorCode;
+use crate::instructions::{accrue_stable_debt, emode_liquidation_threshold, emode_max_ltv};
+use crate::mint_extensions::{amount_with_interest, mint_decimals};
+use crate::state::{Bank, EmodeCategory, StableDebt, User};
+
+// Health of a whole position, for instructions that move both of a user's sides at once and only check the
+// result at the end.
+
+/// Price of `mint` read from `price_update`, from the USDC feed for the user's USDC side and the SOL feed otherwise.
+pub fn price_for(user: &User, mint: Pubkey, price_update: &PriceUpdateV2, max_price_age: u64) -> Result<Price> {
+    let feed: &str = match mint {
+        key if key == user.usdc_address => USDC_USD_FEED_ID,
+        _=> SOL_USB_FEED_ID,
+    };
+    let feed_id: [u8; 32] = get_feed_id_from_hex(feed)?;
+    Ok(price_update.get_price_no_older_than(&Clock::get()?, max_price_age, &feed_id)?)
+}
+
+/// Value of `amount` raw tokens of a mint with `decimals` decimals at `price`, as a mantissa and the power of ten it
+/// is in. Fails unless the price is positive.
+pub fn token_value(amount: u64, price: &Price, decimals: u8) -> Result<(u128, i32)> {
+    if price.price <= 0 {
+        return Err(ErrorCode::InvalidPrice.into());
+    }
+    let value: u128 = (price.price as u128).checked_mul(amount as u128).ok_or(ErrorCode::MathOverflow)?;
+    Ok((value, price.exponent - decimals as i32))
+}
+
+/// `value` in powers of ten of `exponent`, brought down to the power `target`, which is no larger.
+fn rescale(value: u128, exponent: i32, target: i32) -> Result<u128> {
+    let factor: u128 = 10u128.checked_pow(exponent.abs_diff(target)).ok_or(ErrorCode::MathOverflow)?;
+    value.checked_mul(factor).ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Raw tokens of the `to` mint one raw token of the `from` mint is worth at their prices, as a numerator and a
+/// denominator.
+pub fn exchange_rate(from_price: &Price, from_decimals: u8, to_price: &Price, to_decimals: u8) -> Result<(u128, u128)> {
+    let (from_value, from_exponent): (u128, i32) = token_value(1, from_price, from_decimals)?;
+    let (to_value, to_exponent): (u128, i32) = token_value(1, to_price, to_decimals)?;
+    let exponent: i32 = from_exponent.min(to_exponent);
+    Ok((rescale(from_value, from_exponent, exponent)?, rescale(to_value, to_exponent, exponent)?))
+}
+
+/// Health factor of `user` across both of its sides, in basis points: the deposits' values times their liquidation
+/// thresholds over the value of the debt. `banks`, `mints` and `prices` hold the SOL side and the
+/// USDC side in the same order. A position without debt reports `u64::MAX`.
+pub fn health_factor_bps(
+    user: &User,
+    banks: [&Bank; 2],
+    mints: [&AccountInfo; 2],
+    prices: [&Price; 2],
+    emode_category: Option<&EmodeCategory>,
+) -> Result<u64> {
+    let emode_threshold: Option<u64> = match emode_liquidation_threshold(user, emode_category, banks) {
+        None if user.emode_category != 0 => return Err(ErrorCode::EmodeCategoryMismatch.into()),
+        emode_threshold => emode_threshold,
+    };
+    let weights: [u64; 2] = banks.map(|bank| emode_threshold.unwrap_or(bank.liquidation_threshold));
+
+    weighted_health_bps(user, banks, mints, prices, weights, [0, 0])
+}
+
+/// Like `health_factor_bps`, with the deposits weighted by their loan-to-value instead of their liquidation threshold
+/// and `new_debt` added to what each side owes. The position can take on `new_debt` while this is at least 1.
+pub fn borrow_capacity_bps(
+    user: &User,
+    banks: [&Bank; 2],
+    mints: [&AccountInfo; 2],
+    prices: [&Price; 2],
+    emode_category: Option<&EmodeCategory>,
+    new_debt: [u64; 2],
+) -> Result<u64> {
+    let emode_ltv: Option<u64> = match emode_max_ltv(user, emode_category, banks) {
+        None if user.emode_category != 0 => return Err(ErrorCode::EmodeCategoryMismatch.into()),
+        emode_ltv => emode_ltv,
+    };
+    let weights: [u64; 2] = banks.map(|bank| emode_ltv.unwrap_or(bank.max_ltv));
+
+    weighted_health_bps(user, banks, mints, prices, weights, new_debt)
+}
+
+fn weighted_health_bps(
+    user: &User,
+    banks: [&Bank; 2],
+    mints: [&AccountInfo; 2],
+    prices: [&Price; 2],
+    weights: [u64; 2],
+    new_debt: [u64; 2],
+) -> Result<u64> {
+    for bank in banks {
+        check_position_bank(user, bank)?;
+    }
+    if (banks[0].mint_address == user.usdc_address) == (banks[1].mint_address == user.usdc_address) {
+        return Err(ErrorCode::InvalidCollateralMint.into());
+    }
+
+    let now: i64 = Clock::get()?.unix_timestamp;
+    // Weighted collateral and debt of each side, valued in the power of ten its price and decimals put them in.
+    let mut values: Vec<(u128, u128, i32)> = Vec::with_capacity(2);
+
+    for (((bank, mint), price), (weight, new_debt)) in banks.into_iter().zip(mints).zip(prices).zip(weights.into_iter().zip(new_debt)) {
+        let deposited_shares: u64 = match bank.mint_address {
+            key if key == user.usdc_address => user.deposited_usdc_shares,
+            _=> user.deposited_sol_shares,
+        };
+        let deposited: u64 = amount_with_interest(mint, accounting::deposit_value(bank, deposited_shares)?, now)?;
+        let owed: u64 = debt_owed(user, bank)?.checked_add(new_debt).ok_or(ErrorCode::MathOverflow)?;
+        let borrowed: u64 = amount_with_interest(mint, owed, now)?;
+
+        let decimals: u8 = mint_decimals(mint)?;
+        let (collateral_value, exponent): (u128, i32) = token_value(deposited, price, decimals)?;
+        let (debt_value, _): (u128, i32) = token_value(borrowed, price, decimals)?;
+        let weighted_value: u128 = collateral_value.checked_mul(weight as u128).ok_or(ErrorCode::MathOverflow)?;
+        values.push((weighted_value, debt_value, exponent));
+    }
+
+    let exponent: i32 = values.iter().map(|(_, _, exponent)| *exponent).min().unwrap_or(0);
+    let mut weighted_collateral: u128 = 0;
+    let mut debt: u128 = 0;
+    for (weighted_value, debt_value, value_exponent) in values {
+        weighted_collateral = weighted_collateral.checked_add(rescale(weighted_value, value_exponent, exponent)?).ok_or(ErrorCode::MathOverflow)?;
+        debt = debt.checked_add(rescale(debt_value, value_exponent, exponent)?).ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    if debt == 0 {
+        return Ok(u64::MAX);
+    }
+    // The weights are in basis points, so the ratio already is.
+    Ok((weighted_collateral / debt).min(u64::MAX as u128) as u64)
+}
+
+/// Fails if `user` has debt and its health factor is under 1. Prices are only read when there is debt, so the price
+/// updates can be left out otherwise.
+pub fn check_health(
+    user: &User,
+    banks: [&Bank; 2],
+    mints: [&AccountInfo; 2],
+    price_updates: [Option<&PriceUpdateV2>; 2],
+    emode_category: Option<&EmodeCategory>,
+    max_price_age: u64,
+) -> Result<()> {
+    if !has_debt(user) {
+        return Ok(());
+    }
+
+    let mut prices: Vec<Price> = Vec::with_capacity(2);
+    for (bank, price_update) in banks.into_iter().zip(price_updates) {
+        let price_update: &PriceUpdateV2 = price_update.ok_or(ErrorCode::PriceUpdateRequired)?;
+        prices.push(price_for(user, bank.mint_address, price_update, max_price_age)?);
+    }
+
+    if health_factor_bps(user, banks, mints, [&prices[0], &prices[1]], emode_category)? < BASIS_POINTS {
+        return Err(ErrorCode::HealthFactorTooLow.into());
+    }
+
+    Ok(())
+}
+
+/// Whether `user` owes anything on either side.
+pub fn has_debt(user: &User) -> bool {
+    user.borrowed_sol_shares != 0 || user.borrowed_usdc_shares != 0
+        || user.sol_term_owed != 0 || user.usdc_term_owed != 0
+        || user.sol_stable_debt.owed != 0 || user.usdc_stable_debt.owed != 0
+}
+
+/// Everything `user` owes `bank`, in tokens: the variable-rate debt behind its shares, its term loan and its
+/// stable-rate debt with interest up to now.
+pub fn debt_owed(user: &User, bank: &Bank) -> Result<u64> {
+    let (borrowed_shares, term_owed, mut stable_debt): (u64, u64, StableDebt) = match bank.mint_address {
+        key if key == user.usdc_address => (user.borrowed_usdc_shares, user.usdc_term_owed, user.usdc_stable_debt),
+        _=> (user.borrowed_sol_shares, user.sol_term_owed, user.sol_stable_debt),
+    };
+    accrue_stable_debt(&mut stable_debt, Clock::get()?.unix_timestamp)?;
+
+    accounting::borrowed_value(bank, borrowed_shares)?
+        .checked_add(term_owed)
+        .and_then(|debt| debt.checked_add(stable_debt.owed))
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Fails unless `bank` is the one holding `user`'s position on its side. The SOL side stays with the bank its rewards
+/// accrue against until it is empty, so another SOL-side bank can't be passed off as it.
+pub fn check_position_bank(user: &User, bank: &Bank) -> Result<()> {
+    let sol_side_open: bool = user.deposited_sol_shares != 0
+        || user.borrowed_sol_shares != 0
+        || user.sol_term_owed != 0
+        || user.sol_stable_debt.owed != 0;
+    if bank.mint_address != user.usdc_address && sol_side_open && bank.mint_address != user.sol_rewards.bank_mint {
+        return Err(ErrorCode::PositionBankMismatch.into());
+    }
+
+    Ok(())
+}
@@ -2,20 +2,55 @@ use anchor_lang::prelude::*;
 use crate::state::User;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use crate::state::Bank;
+use crate::constants::{BASIS_POINTS, MAX_AGE};
+use crate::mint_extensions::validate_mint_extensions;
+use crate::rates::accrue_interest;
+use crate::state::{Bank, LendingMarket};
+
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct InitMarket<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + LendingMarket::INIT_SPACE,
+    )]
+    pub market: Account<'info, LendingMarket>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarket<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+}
 
 #[derive(Accounts)]
 pub struct InitBank<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
     pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
-        mut,
+        init,
         payer = signer,
         space = 8 + Bank::INIT_SPACE,
-        seeds = [mint_key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank: Account<'info, Bank>,
@@ -25,7 +60,7 @@ pub struct InitBank<'info> {
         token::mint = mint,
         token::authority = bank_token_account,
         payer = signer,
-        seeds = [b"treasury", mint_key().as_ref()],
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
@@ -33,45 +68,227 @@ pub struct InitBank<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateBankIsolation<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBankOutflowLimit<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBankRates<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
 
 #[derive(Accounts)]
 pub struct InitUser<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    pub market: Account<'info, LendingMarket>,
+
     #[account(
         init,
         payer = signer,
         space = 8 + User::INIT_SPACE,
-        seeds = [signer.key().as_ref()],
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
         bump,
     )]
     pub user_account: Account<'info, User>,
     pub system_program: Program<'info, System>, // BECAUSE WE ARE INITIALIZING OUR ACCOUNT.
 }
 
+#[derive(Accounts)]
+pub struct CloseUser<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub market: Account<'info, LendingMarket>,
+
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+
+
+pub fn process_init_market(ctx: Context<InitMarket>, quote_currency: [u8; 32]) -> Result<()> {
+    let market: &mut LendingMarket = &mut ctx.accounts.market;
+    market.owner = ctx.accounts.signer.key();
+    market.quote_currency = quote_currency;
+    market.paused = false;
+    market.max_price_age = MAX_AGE;
+
+    Ok(())
+}
+
+pub fn process_update_market(ctx: Context<UpdateMarket>, paused: bool, max_price_age: u64) -> Result<()> {
+    let market: &mut LendingMarket = &mut ctx.accounts.market;
+    market.paused = paused;
+    market.max_price_age = max_price_age;
+
+    Ok(())
+}
 
+/// Creates the bank for `mint` with a `liquidation_threshold` and `max_ltv` in basis points of the collateral's value.
+pub fn process_init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64) -> Result<()> {
+    if max_ltv > liquidation_threshold || liquidation_threshold > BASIS_POINTS {
+        return Err(ErrorCode::InvalidRiskParameters.into());
+    }
+    validate_mint_extensions(&ctx.accounts.mint.to_account_info())?;
 
-pub fn process_init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64 ) -> Result<()> {
     let bank = &mut ctx.accounts.bank;
-    let bank_mint_address = ctx.accounts.mint.key();
+    bank.mint_address = ctx.accounts.mint.key();
     bank.authority = ctx.accounts.signer.key();
+    bank.market = ctx.accounts.market.key();
     bank.liquidation_threshold = liquidation_threshold;
     bank.max_ltv = max_ltv;
-    bank.interest_rate = 0.05 as u64;
+    bank.last_updated = Clock::get()?.unix_timestamp as u64;
+
+    Ok(())
+}
+
+pub fn process_update_bank_isolation(ctx: Context<UpdateBankIsolation>, isolated: bool, debt_ceiling: u64, borrowable_in_isolation: bool) -> Result<()> {
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    bank.isolated = isolated;
+    bank.debt_ceiling = debt_ceiling;
+    bank.borrowable_in_isolation = borrowable_in_isolation;
+
+    Ok(())
+}
+
+/// Limits net outflows from the bank to `limit` over a rolling window of `window` seconds, 0 for no limit. The limit
+/// is in tokens, or with `limit_in_usd` in USD with `USD_DECIMALS` decimals at the oracle price. Flows recorded so
+/// far are dropped.
+pub fn process_update_bank_outflow_limit(ctx: Context<UpdateBankOutflowLimit>, window: i64, limit: u64, limit_in_usd: bool) -> Result<()> {
+    if window < 0 {
+        return Err(ErrorCode::InvalidOutflowConfig.into());
+    }
+
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    bank.outflow_window = window;
+    bank.outflow_limit = limit;
+    bank.outflow_limit_in_usd = limit_in_usd;
+    bank.outflow_window_start = Clock::get()?.unix_timestamp;
+    bank.outflow_previous = 0;
+    bank.outflow_current = 0;
+
+    Ok(())
+}
+
+/// Sets the utilization curve variable-rate debt pays interest on, see `rates::curve_rate_bps`.
+pub fn process_update_bank_rates(ctx: Context<UpdateBankRates>, base_rate_bps: u64, rate_slope_bps: u64) -> Result<()> {
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    // Variable debt owes the old curve rate up to now.
+    accrue_interest(bank, Clock::get()?.unix_timestamp)?;
+    bank.base_rate_bps = base_rate_bps;
+    bank.rate_slope_bps = rate_slope_bps;
 
     Ok(())
 }
 
 pub fn process_init_user(ctx: Context<InitUser>, usdc_address: Pubkey) -> Result<()> {
-    let user_account: User = &mut ctx.accounts.user_account;
-    user.account.owner = ctx.accounts.signer.key();
+    let user_account: &mut User = &mut ctx.accounts.user_account;
+    user_account.owner = ctx.accounts.signer.key();
+    user_account.market = ctx.accounts.market.key();
     user_account.usdc_address = usdc_address;
     Ok(()) 
 }
 
+pub fn process_close_user(ctx: Context<CloseUser>) -> Result<()> {
+    let user: &User = &ctx.accounts.user_account;
 
+    // Shares are what interest accrues on, so a position is only closed once its shares are gone.
+    let positions: [(&str, u64); 4] = [
+        ("SOL deposit", user.deposited_sol_shares),
+        ("SOL borrow", user.borrowed_sol_shares),
+        ("USDC deposit", user.deposited_usdc_shares),
+        ("USDC borrow", user.borrowed_usdc_shares),
+    ];
 
+    // Term and stable-rate debt aren't in shares, so they are checked on what is owed.
+    let debts: [(&str, u64); 4] = [
+        ("SOL term loan", user.sol_term_owed),
+        ("USDC term loan", user.usdc_term_owed),
+        ("SOL stable borrow", user.sol_stable_debt.owed),
+        ("USDC stable borrow", user.usdc_stable_debt.owed),
+    ];
 
+    let mut has_open_position: bool = false;
+    for (position, shares) in positions {
+        if shares != 0 {
+            msg!("{} is still open with {} shares", position, shares);
+            has_open_position = true;
+        }
+    }
+    for (position, owed) in debts {
+        if owed != 0 {
+            msg!("{} is still open with {} owed", position, owed);
+            has_open_position = true;
+        }
+    }
 
+    if has_open_position {
+        return Err(ErrorCode::OpenPosition.into());
+    }
 
+    // Closing the account would forfeit rewards that haven't been claimed yet.
+    let unclaimed: bool = user.sol_rewards.positions.iter()
+        .chain(user.usdc_rewards.positions.iter())
+        .any(|position| position.earned != 0);
+    if unclaimed {
+        return Err(ErrorCode::UnclaimedRewards.into());
+    }
+
+    Ok(())
+}
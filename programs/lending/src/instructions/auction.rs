@@ -0,0 +1,447 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::accounting;
+use crate::constants::BASIS_POINTS;
+use crate::health::{exchange_rate, health_factor_bps, price_for};
+use crate::outflow;
+use crate::rates::accrue_interest;
+use crate::state::{Auction, Bank, EmodeCategory, LendingMarket, User};
+
+use crate::error::ErrorCode;
+
+use super::{credit_repayment, debit_withdrawal};
+
+#[derive(Accounts)]
+pub struct UpdateBankAuction<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct StartAuction<'info> {
+    #[account(mut)]
+    pub starter: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+    #[account(constraint = borrowed_mint.key() != collateral_mint.key() @ ErrorCode::InvalidLiquidationMints)]
+    pub borrowed_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+        constraint = collateral_bank.auction_liquidation @ ErrorCode::AuctionLiquidationDisabled,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), borrowed_mint.key().as_ref()],
+        bump,
+    )]
+    pub borrowed_bank: Account<'info, Bank>,
+
+    // The account of the user whose collateral is auctioned.
+    #[account(
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    #[account(
+        init,
+        payer = starter,
+        space = 8 + Auction::INIT_SPACE,
+        seeds = [b"auction", collateral_bank.key().as_ref(), user_account.key().as_ref()],
+        bump,
+    )]
+    pub auction: Account<'info, Auction>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Bid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+    pub borrowed_mint: InterfaceAccount<'info, Mint>,
+
+    // Bids are priced off, and only accepted at, the current oracle prices.
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), borrowed_mint.key().as_ref()],
+        bump,
+    )]
+    pub borrowed_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), borrowed_mint.key().as_ref()],
+        bump,
+    )]
+    pub borrowed_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    #[account(
+        mut,
+        seeds = [b"auction", collateral_bank.key().as_ref(), user_account.key().as_ref()],
+        bump,
+        constraint = auction.borrowed_bank == borrowed_bank.key() @ ErrorCode::AuctionMismatch,
+        constraint = auction.starter == starter.key() @ ErrorCode::AuctionMismatch,
+    )]
+    pub auction: Account<'info, Auction>,
+
+    /// CHECK: Only receives the auction's rent once it closes, and must be the account that started it.
+    #[account(mut)]
+    pub starter: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_collateral_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = borrowed_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_borrowed_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+// Permissionless: anyone can cancel an auction once the position it was started on is healthy again.
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    pub signer: Signer<'info>,
+    pub market: Account<'info, LendingMarket>,
+
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+    pub borrowed_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), borrowed_mint.key().as_ref()],
+        bump,
+    )]
+    pub borrowed_bank: Account<'info, Bank>,
+
+    #[account(
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    #[account(
+        mut,
+        close = starter,
+        seeds = [b"auction", collateral_bank.key().as_ref(), user_account.key().as_ref()],
+        bump,
+        constraint = auction.borrowed_bank == borrowed_bank.key() @ ErrorCode::AuctionMismatch,
+        constraint = auction.starter == starter.key() @ ErrorCode::AuctionMismatch,
+    )]
+    pub auction: Account<'info, Auction>,
+
+    /// CHECK: Only receives the auction's rent, and must be the account that started it.
+    #[account(mut)]
+    pub starter: UncheckedAccount<'info>,
+}
+
+pub fn process_update_bank_auction(
+    ctx: Context<UpdateBankAuction>,
+    enabled: bool,
+    duration: i64,
+    start_premium_bps: u64,
+    end_discount_bps: u64,
+) -> Result<()> {
+    if duration <= 0 || end_discount_bps > BASIS_POINTS {
+        return Err(ErrorCode::InvalidAuctionConfig.into());
+    }
+
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    bank.auction_liquidation = enabled;
+    bank.auction_duration = duration;
+    bank.auction_start_premium_bps = start_premium_bps;
+    bank.auction_end_discount_bps = end_discount_bps;
+
+    Ok(())
+}
+
+/// Puts a liquidatable user's collateral up for auction against their debt in `borrowed_mint`. Anyone can start one,
+/// paying the auction account's rent until it closes.
+pub fn process_start_auction(ctx: Context<StartAuction>) -> Result<()> {
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+    let user: &User = &ctx.accounts.user_account;
+    let collateral_price: Price = price_for(user, ctx.accounts.collateral_mint.key(), &ctx.accounts.collateral_price_update, max_price_age)?;
+    let borrowed_price: Price = price_for(user, ctx.accounts.borrowed_mint.key(), &ctx.accounts.borrowed_price_update, max_price_age)?;
+
+    let health_factor_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.borrowed_bank],
+        [&ctx.accounts.collateral_mint.to_account_info(), &ctx.accounts.borrowed_mint.to_account_info()],
+        [&collateral_price, &borrowed_price],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+
+    let borrowed_shares: u64 = match ctx.accounts.borrowed_mint.key() {
+        key if key == user.usdc_address => user.borrowed_usdc_shares,
+        _=> user.borrowed_sol_shares,
+    };
+    let debt: u64 = accounting::borrowed_value(&ctx.accounts.borrowed_bank, borrowed_shares)?;
+
+    if health_factor_bps >= BASIS_POINTS || debt == 0 {
+        return Err(ErrorCode::NotUnderCollaterized.into());
+    }
+
+    let collateral_bank: &Bank = &ctx.accounts.collateral_bank;
+    ctx.accounts.auction.set_inner(Auction {
+        user: ctx.accounts.user_account.key(),
+        starter: ctx.accounts.starter.key(),
+        collateral_bank: ctx.accounts.collateral_bank.key(),
+        borrowed_bank: ctx.accounts.borrowed_bank.key(),
+        debt,
+        start_price_bps: BASIS_POINTS.checked_add(collateral_bank.auction_start_premium_bps).ok_or(ErrorCode::MathOverflow)?,
+        end_price_bps: BASIS_POINTS - collateral_bank.auction_end_discount_bps,
+        start_time: Clock::get()?.unix_timestamp,
+        duration: collateral_bank.auction_duration,
+    });
+
+    Ok(())
+}
+
+/// Share of the collateral's oracle value, in basis points, the auction asks at `now`.
+pub fn auction_price_bps(auction: &Auction, now: i64) -> u64 {
+    let elapsed: i64 = (now - auction.start_time).clamp(0, auction.duration);
+    let drop_bps: u128 = auction.start_price_bps.saturating_sub(auction.end_price_bps) as u128 * elapsed as u128 / auction.duration as u128;
+    auction.start_price_bps - drop_bps as u64
+}
+
+/// Buys up to `amount` of the auctioned collateral at the auction's current share of the oracle price, paid in the
+/// debt asset. Bids are only taken while the position is liquidatable; a bid that finds it healthy closes the auction
+/// without buying anything. The purchase is capped at what the user still has and at what covers the debt left to
+/// the auction, and the payment repays that debt. The auction closes once the position is healthy again or the debt
+/// or the collateral runs out.
+pub fn process_bid(ctx: Context<Bid>, amount: u64) -> Result<()> {
+    let collateral_mint_key: Pubkey = ctx.accounts.collateral_mint.key();
+    let borrowed_mint_key: Pubkey = ctx.accounts.borrowed_mint.key();
+    let collateral_mint: AccountInfo = ctx.accounts.collateral_mint.to_account_info();
+    let borrowed_mint: AccountInfo = ctx.accounts.borrowed_mint.to_account_info();
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+    let now: i64 = Clock::get()?.unix_timestamp;
+    accrue_interest(&mut ctx.accounts.collateral_bank, now)?;
+    accrue_interest(&mut ctx.accounts.borrowed_bank, now)?;
+    let auction: &Auction = &ctx.accounts.auction;
+    let user: &User = &ctx.accounts.user_account;
+
+    let collateral_price: Price = price_for(user, collateral_mint_key, &ctx.accounts.collateral_price_update, max_price_age)?;
+    let borrowed_price: Price = price_for(user, borrowed_mint_key, &ctx.accounts.borrowed_price_update, max_price_age)?;
+
+    let health_factor_before_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.borrowed_bank],
+        [&collateral_mint, &borrowed_mint],
+        [&collateral_price, &borrowed_price],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+    // An auction left open while the position recovered would still ask its old, discounted price once the
+    // position is liquidatable again. It is closed instead, so a new one starts over at the premium.
+    if health_factor_before_bps >= BASIS_POINTS {
+        return ctx.accounts.auction.close(ctx.accounts.starter.to_account_info());
+    }
+
+    let (collateral_shares, borrowed_shares): (u64, u64) = match collateral_mint_key {
+        key if key == user.usdc_address => (user.deposited_usdc_shares, user.borrowed_sol_shares),
+        _=> (user.deposited_sol_shares, user.borrowed_usdc_shares),
+    };
+    let collateral: u64 = accounting::deposit_value(&ctx.accounts.collateral_bank, collateral_shares)?;
+    let debt: u64 = accounting::borrowed_value(&ctx.accounts.borrowed_bank, borrowed_shares)?.min(auction.debt);
+
+    // One collateral token costs `price_numerator / price_denominator` borrowed tokens, rounded up in the bank's favor.
+    let (rate_numerator, rate_denominator): (u128, u128) = exchange_rate(
+        &collateral_price,
+        ctx.accounts.collateral_mint.decimals,
+        &borrowed_price,
+        ctx.accounts.borrowed_mint.decimals,
+    )?;
+    let price_numerator: u128 = rate_numerator.checked_mul(auction_price_bps(auction, now) as u128).ok_or(ErrorCode::MathOverflow)?;
+    let price_denominator: u128 = rate_denominator.checked_mul(BASIS_POINTS as u128).ok_or(ErrorCode::MathOverflow)?;
+    let covering_amount: u128 = (debt as u128 * price_denominator).div_ceil(price_numerator.max(1));
+    let amount: u64 = (amount as u128).min(covering_amount).min(collateral as u128) as u64;
+    let payment: u64 = (amount as u128 * price_numerator).div_ceil(price_denominator).min(debt as u128) as u64;
+
+    let transfer_to_bank: TransferChecked = TransferChecked {
+        from: ctx.accounts.bidder_borrowed_token_account.to_account_info(),
+        to: ctx.accounts.borrowed_bank_token_account.to_account_info(),
+        authority: ctx.accounts.bidder.to_account_info(),
+        mint: ctx.accounts.borrowed_mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program.clone(), transfer_to_bank);
+
+    let balance_before: u64 = ctx.accounts.borrowed_bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, payment, ctx.accounts.borrowed_mint.decimals)?;
+    ctx.accounts.borrowed_bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.borrowed_bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    credit_repayment(
+        &mut ctx.accounts.borrowed_bank,
+        Some(&mut ctx.accounts.collateral_bank),
+        &mut ctx.accounts.user_account,
+        borrowed_mint_key,
+        received,
+    )?;
+    let amount: u64 = debit_withdrawal(&mut ctx.accounts.collateral_bank, &mut ctx.accounts.user_account, collateral_mint_key, amount)?;
+
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.collateral_bank, &ctx.accounts.user_account, Some(&*ctx.accounts.collateral_price_update), max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.collateral_bank, amount, price.as_ref(), ctx.accounts.collateral_mint.decimals, Clock::get()?.unix_timestamp)?;
+
+    let transfer_to_bidder: TransferChecked = TransferChecked {
+        from: ctx.accounts.collateral_bank_token_account.to_account_info(),
+        to: ctx.accounts.bidder_collateral_token_account.to_account_info(),
+        authority: ctx.accounts.collateral_bank_token_account.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+    };
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            collateral_mint_key.as_ref(),
+            &[ctx.bumps.collateral_bank_token_account],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_to_bidder).with_signer(signer_seeds);
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.collateral_mint.decimals)?;
+
+    let auction: &mut Auction = &mut ctx.accounts.auction;
+    auction.debt = auction.debt.saturating_sub(received);
+
+    let user: &User = &ctx.accounts.user_account;
+    let (collateral_shares, borrowed_shares): (u64, u64) = match collateral_mint_key {
+        key if key == user.usdc_address => (user.deposited_usdc_shares, user.borrowed_sol_shares),
+        _=> (user.deposited_sol_shares, user.borrowed_usdc_shares),
+    };
+    let health_factor_after_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.borrowed_bank],
+        [&collateral_mint, &borrowed_mint],
+        [&collateral_price, &borrowed_price],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+    if auction.debt == 0 || collateral_shares == 0 || borrowed_shares == 0 || health_factor_after_bps >= BASIS_POINTS {
+        ctx.accounts.auction.close(ctx.accounts.starter.to_account_info())?;
+    }
+
+    Ok(())
+}
+
+/// Closes an auction whose position is healthy again at the current oracle prices, returning its rent to the
+/// starter.
+pub fn process_cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+    let user: &User = &ctx.accounts.user_account;
+    let collateral_price: Price = price_for(user, ctx.accounts.collateral_mint.key(), &ctx.accounts.collateral_price_update, max_price_age)?;
+    let borrowed_price: Price = price_for(user, ctx.accounts.borrowed_mint.key(), &ctx.accounts.borrowed_price_update, max_price_age)?;
+
+    let health_factor_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.borrowed_bank],
+        [&ctx.accounts.collateral_mint.to_account_info(), &ctx.accounts.borrowed_mint.to_account_info()],
+        [&collateral_price, &borrowed_price],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+
+    if health_factor_bps < BASIS_POINTS {
+        return Err(ErrorCode::AuctionStillLiquidatable.into());
+    }
+
+    Ok(())
+}
@@ -1,9 +1,14 @@
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::Mint, TokenAccount, TokenInterface, TransferChecked};      
-use pyth_solana_receiver_sdk::price_update::{PriceUpdate, get_feed_id_from_hex};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{PriceUpdateV2, Price};
 
 
-use crate::{constant::SOL_USB_FEED_ID, USDC_USD_FEED_ID, state::{Bank, User}};
+use crate::accounting;
+use crate::health::{borrow_capacity_bps, price_for};
+use crate::outflow;
+use crate::rates::accrue_interest;
+use crate::rewards::accrue_rewards;
+use crate::{constants::BASIS_POINTS, state::{Bank, EmodeCategory, LendingMarket, User}};
 
 use crate::error::ErrorCode;
 
@@ -13,29 +18,52 @@ pub struct Borrow<'info> {
     pub signer: Signer<'info>,
 
     // The mint account of token that the user want's to borrow.
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
     pub mint: InterfaceAccount<'info, Mint>,
 
+    // The mint of the asset backing the loan, read to value interest-bearing collateral.
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
     #[account(
         mut,
-        seeds = [mint.key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank: Account<'info, Bank>,
 
+    // Bank of the collateral, which tracks the debt isolated collateral backs.
     #[account(
         mut,
-        seeds = [b"treasury", mint.key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [signer.key().as_ref()],
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
         bump,
     )]
     pub user_account: Account<'info, User>,
 
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
     #[account(
         init_if_needed,
         payer = signer,
@@ -44,101 +72,143 @@ pub struct Borrow<'info> {
         associated_token::token_program = token_program,
     )]
     pub user_token_account: InterfaceAccount<'info, TokenAccount>,
-    pub price_update: Account<'info, PriceUpdate>,
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    // Values the debt, and the borrow against a bank outflow limit set in USD.
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
 pub fn process_borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
-
-    let bank: Bank = &mut ctx.accounts.bank;
-    let user: User = &mut ctx.accounts.user_account;
-
-    let price_update: PriceUpdate = &mut ctx.accounts.price_update;
-
-    let total_collateral: u64;
-
-    match ctx.accounts.mint.bank_token_account_into().key() {
-        key::Pubkey if key == user.usdc_address => {
-            let sol_feed_id = get_feed_id_from_hex(SOL_USB_FEED_ID)?;
-            let sol_price: Price = price_update.get_price_no_older_than(clock: &Clock::get()?, MAX_AGE, &sol_feed_id)?;
-            let new_value: u64 = calculate_account_interest(user.deposited_sol, bank.interest_rate, user.last_updated)?;
-            total_collateral = usdc_price.price as u64 * new_value;            
-        }
-        _=> {
-            let usdc_feed_id: [ul: 32] = get_feed_id_from_hex(input: USDC_USD_FEED_ID)?;
-            let usdc_price: Price = price_update.get_price_no_older_than(clock: &Clock::get()?, maximum_age: MAX_AGE, &usdc_feed_id)?;
-            let new_value: u64 = calculate_account_interest(deposited: user.deposited_usdc, bank.interest_rate, usdc.last_updated)?;
-            total_collateral = usdc_price.price as u64 * new_value;            
-        }
-    }
-
-    let borrowable_amount: u64 = total_collateral.check_nul(bank.liquidation_threshold).unwrap();
-
-    if borrowable_amount < amount {
-        return Err(ErrorCode::OverBorrowableAmount.into());
-    }
-
-    let transfer_spl_accounts: TransferChecked = TransferChecked {
-        from: ctx.accounts.bank_token_account.bank_token_account_into(),
-        to: ctx.accounts.user_token_account.bank_token_account_into(),
-        authority: ctx.accounts.bank.to_account_into(),
-        mint: ctx.accounts.mint.to_account_into(),
+    check_borrowable(
+        &ctx.accounts.bank,
+        &ctx.accounts.collateral_bank,
+        &ctx.accounts.user_account,
+        &ctx.accounts.market,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.collateral_mint.to_account_info(),
+        ctx.accounts.emode_category.as_deref(),
+        &ctx.accounts.price_update,
+        &ctx.accounts.borrowed_price_update,
+        amount,
+    )?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
     };
 
-    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_into();
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
 
     
+    let market_key: Pubkey = ctx.accounts.market.key();
     let mint_key: Pubkey = ctx.accounts.mint.key();
     let signer_seeds: &[&[&[u8]]] = &[
         &[
             b"treasury",
+            market_key.as_ref(),
             mint_key.as_ref(),
             &[ctx.bumps.bank_token_account],
         ]
     ];
 
-    let cpi_ctx: CpiContext = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer[signer_seeds];
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+
+    let decimals: u8 = ctx.accounts.mint.decimals;
 
-    let decimals: ul = ctx.accounts.mint.decimals;
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
-    token_interface::transferchecked(cpi_ctx, amount, decimals)?;
+    record_borrow(&mut ctx.accounts.bank, &mut ctx.accounts.collateral_bank, &mut ctx.accounts.user_account, amount)?;
 
-    if bank.total_borrowed == 0 {
-        bank.total_borrowed = amount;
-        bank.total_borrowed_shares = amount;
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.bank, &ctx.accounts.user_account, Some(&*ctx.accounts.borrowed_price_update), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.bank, amount, price.as_ref(), decimals, Clock::get()?.unix_timestamp)
+}
+
+/// Fails unless `user`'s position, valued at `price_update` for the collateral and `borrowed_price_update` for
+/// `bank`'s mint, stays within its loan-to-value with `amount` more borrowed from `bank`, and the borrow respects
+/// the isolation rules of `collateral_bank` and the user's e-mode category.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn check_borrowable<'info>(
+    bank: &Bank,
+    collateral_bank: &Bank,
+    user: &User,
+    market: &LendingMarket,
+    mint: &AccountInfo<'info>,
+    collateral_mint: &AccountInfo<'info>,
+    emode_category: Option<&EmodeCategory>,
+    price_update: &PriceUpdateV2,
+    borrowed_price_update: &PriceUpdateV2,
+    amount: u64,
+) -> Result<()> {
+    let max_price_age: u64 = market.max_price_age;
+
+    if collateral_mint.key() == bank.mint_address || (collateral_mint.key() != user.usdc_address && bank.mint_address != user.usdc_address) {
+        return Err(ErrorCode::InvalidCollateralMint.into());
     }
 
-    let borrow_ratio: u64 = amount.check_div(bank.total_borrowed).unwrap();
-    let user_shares: u64 = bank.total_borrowed_shares.check_nul(borrow_ratio).unwrap();
+    // Both sides' deposits back the borrow at their loan-to-value, against everything the position owes.
+    let borrow_capacity_bps: u64 = borrow_capacity_bps(
+        user,
+        [collateral_bank, bank],
+        [collateral_mint, mint],
+        [
+            &price_for(user, collateral_mint.key(), price_update, max_price_age)?,
+            &price_for(user, bank.mint_address, borrowed_price_update, max_price_age)?,
+        ],
+        emode_category,
+        [0, amount],
+    )?;
+    if borrow_capacity_bps < BASIS_POINTS {
+        return Err(ErrorCode::OverBorrowableAmount.into());
+    }
 
-    match ctx.accounts.mint.to_account_into().key() {
-        key::Pubkey if key == user.usdc_address => {
-            user.borrowed_usdc == amount;
-            user.borrowed_usdc_shares += user_shares;
-        },
-        _=> {
-            user.borrowed_sol += amount;
-            user.borrowed_sol_shares == user_shares;
+    // Isolated collateral only backs allowlisted banks, up to its debt ceiling.
+    if user.isolated_mint != Pubkey::default() {
+        if !bank.borrowable_in_isolation {
+            return Err(ErrorCode::NotBorrowableInIsolation.into());
+        }
+        if collateral_mint.key() != user.isolated_mint {
+            return Err(ErrorCode::IsolatedCollateralMixed.into());
+        }
+        let isolated_debt: u64 = collateral_bank.isolated_debt.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        if isolated_debt > collateral_bank.debt_ceiling {
+            return Err(ErrorCode::DebtCeilingExceeded.into());
         }
     }
-    
-    user.last_updated_borrow = Clock::get()?.unix_timestamp;
-
 
     Ok(())
 }
 
-pub fn calculate_account_interest(deposited: u64, interest_rate: u64, last_updated: u64) -> Result<u64> {
-    let current_time: u64 = Clock::get()?.unix_timestamp;
-    let time_diff: u64 = current_time = last_updated;
-    let new_value: u64 = (deposited as f64 * E.powf(interest_rate as f32 * time_diff as f32) as f64) as u64;
-    Ok(new_value)
-}
-
+/// Books a borrow of `amount` from `bank` onto `user` once the tokens have left the treasury.
+pub(crate) fn record_borrow(bank: &mut Bank, collateral_bank: &mut Bank, user: &mut User, amount: u64) -> Result<()> {
+    let now: i64 = Clock::get()?.unix_timestamp;
+    accrue_rewards(bank, user, now)?;
+    // Interest up to now is charged on the debt before the borrow adds to it.
+    accrue_interest(bank, now)?;
 
+    let user_shares: u64 = accounting::borrow(bank, amount)?;
 
+    if user.isolated_mint != Pubkey::default() {
+        collateral_bank.isolated_debt = collateral_bank.isolated_debt.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    }
 
+    match bank.mint_address {
+        key if key == user.usdc_address => {
+            user.borrowed_usdc = user.borrowed_usdc.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            user.borrowed_usdc_shares = user.borrowed_usdc_shares.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
+        },
+        _=> {
+            user.borrowed_sol = user.borrowed_sol.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            user.borrowed_sol_shares = user.borrowed_sol_shares.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
+        }
+    }
 
+    user.last_updated_borrow = Clock::get()?.unix_timestamp;
 
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::outflow;
+use crate::state::{Bank, BorrowAllowance, EmodeCategory, LendingMarket, User};
+
+use crate::error::ErrorCode;
+
+use super::{check_borrowable, record_borrow};
+
+#[derive(Accounts)]
+pub struct ApproveBorrowDelegation<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    /// CHECK: Only used as a key; the delegate signs when it borrows.
+    pub delegate: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = 8 + BorrowAllowance::INIT_SPACE,
+        seeds = [b"allowance", signer.key().as_ref(), delegate.key().as_ref(), bank.key().as_ref()],
+        bump,
+    )]
+    pub borrow_allowance: Account<'info, BorrowAllowance>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BorrowDelegated<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    // Bank of the collateral, which tracks the debt isolated collateral backs.
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The owner whose collateral backs the loan and who carries the debt.
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    #[account(
+        mut,
+        seeds = [b"allowance", user_account.owner.as_ref(), signer.key().as_ref(), bank.key().as_ref()],
+        bump,
+    )]
+    pub borrow_allowance: Account<'info, BorrowAllowance>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    // Values the debt, and the borrow against a bank outflow limit set in USD.
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Sets how much `delegate` may borrow against the signer's collateral and until when. Approving again replaces
+/// the previous allowance, and an amount of zero revokes it.
+pub fn process_approve_borrow_delegation(ctx: Context<ApproveBorrowDelegation>, amount: u64, expires_at: i64) -> Result<()> {
+    let allowance: &mut BorrowAllowance = &mut ctx.accounts.borrow_allowance;
+    allowance.owner = ctx.accounts.signer.key();
+    allowance.delegate = ctx.accounts.delegate.key();
+    allowance.bank = ctx.accounts.bank.key();
+    allowance.amount = amount;
+    allowance.expires_at = expires_at;
+
+    Ok(())
+}
+
+pub fn process_borrow_delegated(ctx: Context<BorrowDelegated>, amount: u64) -> Result<()> {
+    let allowance: &mut BorrowAllowance = &mut ctx.accounts.borrow_allowance;
+
+    if Clock::get()?.unix_timestamp > allowance.expires_at {
+        return Err(ErrorCode::AllowanceExpired.into());
+    }
+
+    allowance.amount = allowance.amount.checked_sub(amount).ok_or(ErrorCode::AllowanceExceeded)?;
+
+    check_borrowable(
+        &ctx.accounts.bank,
+        &ctx.accounts.collateral_bank,
+        &ctx.accounts.user_account,
+        &ctx.accounts.market,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.collateral_mint.to_account_info(),
+        ctx.accounts.emode_category.as_deref(),
+        &ctx.accounts.price_update,
+        &ctx.accounts.borrowed_price_update,
+        amount,
+    )?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        to: ctx.accounts.signer_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+
+    let decimals: u8 = ctx.accounts.mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+    record_borrow(&mut ctx.accounts.bank, &mut ctx.accounts.collateral_bank, &mut ctx.accounts.user_account, amount)?;
+
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.bank, &ctx.accounts.user_account, Some(&*ctx.accounts.borrowed_price_update), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.bank, amount, price.as_ref(), decimals, Clock::get()?.unix_timestamp)
+}
@@ -0,0 +1,196 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::health::{health_factor_bps, price_for};
+use crate::outflow;
+use crate::state::{Bank, EmodeCategory, LendingMarket, User};
+use crate::swap_adapter::invoke_swap;
+
+use crate::error::ErrorCode;
+
+use super::{credit_repayment, debit_withdrawal, prepare_repayment};
+
+#[derive(Accounts)]
+pub struct Deleverage<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    // The mint of the debt that is repaid.
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    // The collateral that is withdrawn and sold for the debt asset.
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    // Also the isolated bank whose debt ceiling the repayment frees, when the collateral is isolated.
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // The swap delivers the debt asset here, and the debt is repaid from here.
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The withdrawn collateral lands here for the swap to spend.
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_collateral_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub price_update: Account<'info, PriceUpdateV2>,
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+
+    /// CHECK: Any swap program the user picks; `invoke_swap` only requires it to be executable and not this program.
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Withdraws `amount` of the owner's collateral, `u64::MAX` for all of it, sells it through `swap_program` and
+/// repays the `mint` debt with what the swap delivered, up to all of it. The swap's accounts come in
+/// `remaining_accounts` and `swap_data` is its instruction data. Unlike a liquidation, no bonus is taken. The
+/// position may fail its checks while collateral is out, but must end at least as healthy as it started.
+pub fn process_deleverage<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Deleverage<'info>>,
+    amount: u64,
+    swap_data: Vec<u8>,
+) -> Result<()> {
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let collateral_mint_key: Pubkey = ctx.accounts.collateral_mint.key();
+    let user: &User = &ctx.accounts.user_account;
+
+    if collateral_mint_key == mint_key || (collateral_mint_key != user.usdc_address && mint_key != user.usdc_address) {
+        return Err(ErrorCode::InvalidCollateralMint.into());
+    }
+
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+    let price: Price = price_for(user, mint_key, &ctx.accounts.price_update, max_price_age)?;
+    let collateral_price: Price = price_for(user, collateral_mint_key, &ctx.accounts.collateral_price_update, max_price_age)?;
+    let health_before_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.bank],
+        [&ctx.accounts.collateral_mint.to_account_info(), &ctx.accounts.mint.to_account_info()],
+        [&collateral_price, &price],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+
+    let amount: u64 = debit_withdrawal(&mut ctx.accounts.collateral_bank, &mut ctx.accounts.user_account, collateral_mint_key, amount)?;
+
+    let outflow_price: Option<Price> = outflow::outflow_price(&ctx.accounts.collateral_bank, &ctx.accounts.user_account, Some(&*ctx.accounts.collateral_price_update), max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.collateral_bank, amount, outflow_price.as_ref(), ctx.accounts.collateral_mint.decimals, Clock::get()?.unix_timestamp)?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.collateral_bank_token_account.to_account_info(),
+        to: ctx.accounts.user_collateral_token_account.to_account_info(),
+        authority: ctx.accounts.collateral_bank_token_account.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            collateral_mint_key.as_ref(),
+            &[ctx.bumps.collateral_bank_token_account],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program.clone(), transfer_cpi_accounts).with_signer(signer_seeds);
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.collateral_mint.decimals)?;
+
+    let debt_before: u64 = ctx.accounts.user_token_account.amount;
+    invoke_swap(&ctx.accounts.swap_program.to_account_info(), ctx.remaining_accounts, swap_data)?;
+    ctx.accounts.user_token_account.reload()?;
+    let swapped: u64 = ctx.accounts.user_token_account.amount.checked_sub(debt_before).ok_or(ErrorCode::MathOverflow)?;
+
+    // Anything the swap delivered beyond the debt stays with the owner.
+    let owed: u64 = prepare_repayment(&mut ctx.accounts.bank, &ctx.accounts.user_account, &ctx.accounts.mint.to_account_info(), u64::MAX)?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.user_token_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, swapped.min(owed), ctx.accounts.mint.decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    credit_repayment(&mut ctx.accounts.bank, Some(&mut ctx.accounts.collateral_bank), &mut ctx.accounts.user_account, mint_key, received)?;
+
+    let health_after_bps: u64 = health_factor_bps(
+        &ctx.accounts.user_account,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.bank],
+        [&ctx.accounts.collateral_mint.to_account_info(), &ctx.accounts.mint.to_account_info()],
+        [&collateral_price, &price],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+
+    if health_after_bps < health_before_bps {
+        return Err(ErrorCode::HealthFactorTooLow.into());
+    }
+
+    Ok(())
+}
@@ -1,8 +1,13 @@
 use anchor_lang::prelude::*;
 // use anchor_spl::{associated_token::AssociatedToken, token_interface::Mint, TokenAccount, TokenInterface, TransferChecked};
-use anchor_spl::token_interface::{associated_token::AssociatedToken, token_interface::Mint, TokenAccount, TokenInterface, TransferChecked};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked}};
 use anchor_spl::token_interface;
-use crate::state::{Bank, User};
+use crate::accounting;
+use crate::outflow;
+use crate::rates::accrue_interest;
+use crate::rewards::accrue_rewards;
+use crate::state::{Bank, LendingMarket, User};
+use crate::error::ErrorCode;
 
 
 #[derive(Accounts)]
@@ -10,32 +15,37 @@ pub struct Deposit<'info>{
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
     pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [mint_key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank: Account<'info, Bank>,
 
     #[account(
         mut,
-        seeds = [b"treasury", mint_key().as_ref()],
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [signer.key().as_ref()],
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
         bump,
     )]
     pub user_account: Account<'info, User>,
 
     #[account(
         mut,
-        associated_token::mint = Mint,
+        associated_token::mint = mint,
         associated_token::authority = signer,
         associated_token::token_program = token_program,
     )]
@@ -46,6 +56,51 @@ pub struct Deposit<'info>{
 }
 
 
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey)]
+pub struct DepositFor<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The beneficiary's account is credited with the deposit, while the tokens come from the signer.
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), beneficiary.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
 pub fn process_deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
     let transfer_cpi_accounts: TransferChecked = TransferChecked {
         from: ctx.accounts.user_token_account.to_account_info(),
@@ -55,46 +110,84 @@ pub fn process_deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
     };
 
     let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx: CpiContext = CpiContext::info(cpi_program, transfer_cpi_accounts);
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
 
     let decimals: u8 = ctx.accounts.mint.decimals;
 
+    // Transfer-fee mints deliver less than `amount`, so credit what the treasury actually received.
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
 
-    let bank = &mut ctx.accounts.bank;
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    credit_deposit(&mut ctx.accounts.bank, &mut ctx.accounts.user_account, mint_key, received)
+}
 
-    if bank.total_deposits == 0 {
-        bank.total_deposits = amount;
-        bank.total_deposit_shares = amount;
+pub fn process_deposit_for(ctx: Context<DepositFor>, _beneficiary: Pubkey, amount: u64) -> Result<()> {
+    // Only the owner can take their account out of its e-mode category or into (or out of) isolation.
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let user: &User = &ctx.accounts.user_account;
+    let bank: &Bank = &ctx.accounts.bank;
+    let leaves_emode: bool = user.emode_category != 0 && bank.emode_category != user.emode_category;
+    let isolated_mint: Pubkey = if bank.isolated { mint_key } else { Pubkey::default() };
+    if leaves_emode || (mint_key != user.usdc_address && isolated_mint != user.isolated_mint) {
+        return Err(ErrorCode::DepositForChangesMode.into());
     }
 
-    let deposit_ratio: u64 = amount.checked_div(bank.total_deposits).unwrap();
-    let user_shares: u64 = bank.total_deposit_shares.check_mul(deposit_ratio).unwrap();
-
-    let user: &mut Account = &mut ctx.accounts.user_account;
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.signer_token_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
 
-    match ctx.accounts.mint.to_account_info().key() {
-        key::Pubkey if key == user.usdc_address => {
-            user.deposited_usdc += amount;
-            user.deposited_usdc_shares += user_shares;
-        },
-        _=> {
-            user.deposited_sol += amount;
-            user.deposited_sol_shares += user_shares;
-        }
-    }
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
 
-    bank.total_deposits += amount;
-    bank.total_deposit_shares += user_shares;
+    let decimals: u8 = ctx.accounts.mint.decimals;
 
-    user.last_updated = Clock::get()?.unix_timestamp;
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
 
-    Ok(())
+    credit_deposit(&mut ctx.accounts.bank, &mut ctx.accounts.user_account, mint_key, received)
 }
 
+/// Books `amount` the treasury has already received as a deposit of `mint` by `user`.
+pub(crate) fn credit_deposit(bank: &mut Bank, user: &mut User, mint: Pubkey, amount: u64) -> Result<()> {
+    // The SOL-side slot holds a single collateral, so isolated collateral can't share it with anything else.
+    if mint != user.usdc_address {
+        let isolated_mint: Pubkey = if bank.isolated { mint } else { Pubkey::default() };
+        if user.deposited_sol_shares != 0 && user.isolated_mint != isolated_mint {
+            return Err(ErrorCode::IsolatedCollateralMixed.into());
+        }
+        user.isolated_mint = isolated_mint;
+    }
 
+    let now: i64 = Clock::get()?.unix_timestamp;
+    accrue_rewards(bank, user, now)?;
+    // Shares are priced on the interest owed so far, so the deposit can't claim any of it.
+    accrue_interest(bank, now)?;
 
+    let user_shares: u64 = accounting::deposit(bank, amount)?;
+    outflow::record_inflow(bank, amount, now);
 
+    match mint {
+        key if key == user.usdc_address => {
+            user.deposited_usdc = user.deposited_usdc.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            user.deposited_usdc_shares = user.deposited_usdc_shares.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
+        },
+        _=> {
+            user.deposited_sol = user.deposited_sol.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            user.deposited_sol_shares = user.deposited_sol_shares.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
+        }
+    }
 
+    user.last_updated = Clock::get()?.unix_timestamp;
+    // The position may be healthy again, so the liquidation bonus clock waits for a fresh `flag_liquidatable`.
+    user.liquidatable_since = 0;
 
-
+    Ok(())
+}
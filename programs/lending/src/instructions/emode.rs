@@ -0,0 +1,208 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+
+use crate::constants::BASIS_POINTS;
+use crate::health::check_health;
+use crate::state::{Bank, EmodeCategory, LendingMarket, User};
+
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+#[instruction(id: u8)]
+pub struct InitEmodeCategory<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + EmodeCategory::INIT_SPACE,
+        seeds = [b"emode", market.key().as_ref(), &[id]],
+        bump,
+    )]
+    pub emode_category: Account<'info, EmodeCategory>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(category: u8)]
+pub struct UpdateBankEmode<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    // Left out when taking the bank out of e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+}
+
+// The banks of the user's open positions are passed as remaining accounts, so they can be checked against the category.
+#[derive(Accounts)]
+#[instruction(category: u8)]
+pub struct SetEmode<'info> {
+    pub signer: Signer<'info>,
+
+    pub market: Account<'info, LendingMarket>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // Left out when leaving e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // Both sides of the position, whose health is checked under the new category.
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), sol_mint.key().as_ref()],
+        bump,
+    )]
+    pub sol_bank: Account<'info, Bank>,
+
+    #[account(
+        constraint = usdc_mint.key() == user_account.usdc_address @ ErrorCode::InvalidCollateralMint,
+    )]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), usdc_mint.key().as_ref()],
+        bump,
+    )]
+    pub usdc_bank: Account<'info, Bank>,
+
+    // Only needed when the user has debt.
+    pub sol_price_update: Option<Account<'info, PriceUpdateV2>>,
+    pub usdc_price_update: Option<Account<'info, PriceUpdateV2>>,
+}
+
+pub fn process_init_emode_category(ctx: Context<InitEmodeCategory>, id: u8, max_ltv: u64, liquidation_threshold: u64) -> Result<()> {
+    // Category 0 means "no category" on banks and users.
+    if id == 0 {
+        return Err(ErrorCode::InvalidEmodeCategory.into());
+    }
+    // A category's positions must stay overcollateralized, so neither value can reach their full collateral value.
+    if max_ltv > liquidation_threshold || liquidation_threshold >= BASIS_POINTS {
+        return Err(ErrorCode::InvalidRiskParameters.into());
+    }
+
+    let emode_category: &mut EmodeCategory = &mut ctx.accounts.emode_category;
+    emode_category.market = ctx.accounts.market.key();
+    emode_category.id = id;
+    emode_category.max_ltv = max_ltv;
+    emode_category.liquidation_threshold = liquidation_threshold;
+
+    Ok(())
+}
+
+pub fn process_update_bank_emode(ctx: Context<UpdateBankEmode>, category: u8) -> Result<()> {
+    if category != 0 {
+        let emode_category: &EmodeCategory = ctx.accounts.emode_category.as_deref().ok_or(ErrorCode::InvalidEmodeCategory)?;
+        // Joining the category can only raise what the bank's positions are allowed.
+        let bank: &Bank = &ctx.accounts.bank;
+        if emode_category.max_ltv < bank.max_ltv || emode_category.liquidation_threshold < bank.liquidation_threshold {
+            return Err(ErrorCode::EmodeBelowBankParameters.into());
+        }
+    }
+
+    ctx.accounts.bank.emode_category = category;
+
+    Ok(())
+}
+
+pub fn process_set_emode(ctx: Context<SetEmode>, category: u8) -> Result<()> {
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let user: &mut User = &mut ctx.accounts.user_account;
+
+    if category != 0 {
+        if ctx.accounts.emode_category.is_none() {
+            return Err(ErrorCode::InvalidEmodeCategory.into());
+        }
+
+        let mut usdc_covered: bool = user.deposited_usdc_shares == 0 && user.borrowed_usdc_shares == 0;
+        let mut sol_covered: bool = user.deposited_sol_shares == 0 && user.borrowed_sol_shares == 0;
+
+        for account in ctx.remaining_accounts {
+            if *account.owner != crate::ID {
+                return Err(ErrorCode::EmodeCategoryMismatch.into());
+            }
+            let bank: Bank = Bank::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+            if bank.market != market_key || bank.emode_category != category {
+                return Err(ErrorCode::EmodeCategoryMismatch.into());
+            }
+
+            // The SOL side is only vouched for by the bank it is actually in, the one its rewards accrue against.
+            if bank.mint_address == user.usdc_address {
+                usdc_covered = true;
+            } else if bank.mint_address == user.sol_rewards.bank_mint {
+                sol_covered = true;
+            }
+        }
+
+        if !usdc_covered || !sol_covered {
+            return Err(ErrorCode::EmodeCategoryMismatch.into());
+        }
+    }
+
+    user.emode_category = category;
+
+    // Leaving a category, or switching to one with lower parameters, can leave the position liquidatable.
+    check_health(
+        user,
+        [&ctx.accounts.sol_bank, &ctx.accounts.usdc_bank],
+        [&ctx.accounts.sol_mint.to_account_info(), &ctx.accounts.usdc_mint.to_account_info()],
+        [ctx.accounts.sol_price_update.as_deref(), ctx.accounts.usdc_price_update.as_deref()],
+        ctx.accounts.emode_category.as_deref(),
+        ctx.accounts.market.max_price_age,
+    )?;
+
+    Ok(())
+}
+
+/// `user`'s e-mode category when both banks of a position belong to it.
+fn category_for<'a>(user: &User, emode_category: Option<&'a EmodeCategory>, banks: [&Bank; 2]) -> Option<&'a EmodeCategory> {
+    let emode_category: &EmodeCategory = emode_category?;
+    let in_category: bool = user.emode_category != 0
+        && emode_category.id == user.emode_category
+        && banks.iter().all(|bank| bank.emode_category == user.emode_category);
+
+    in_category.then_some(emode_category)
+}
+
+/// Liquidation threshold of `user`'s e-mode category when both banks of a position belong to it.
+pub(crate) fn emode_liquidation_threshold(user: &User, emode_category: Option<&EmodeCategory>, banks: [&Bank; 2]) -> Option<u64> {
+    category_for(user, emode_category, banks).map(|emode_category| emode_category.liquidation_threshold)
+}
+
+/// Loan-to-value of `user`'s e-mode category when both banks of a position belong to it.
+pub(crate) fn emode_max_ltv(user: &User, emode_category: Option<&EmodeCategory>, banks: [&Bank; 2]) -> Option<u64> {
+    category_for(user, emode_category, banks).map(|emode_category| emode_category.max_ltv)
+}
@@ -0,0 +1,502 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::accounting;
+use crate::constants::BASIS_POINTS;
+use crate::rates::accrue_interest;
+use crate::rewards::accrue_rewards;
+use crate::state::{Bank, InsuranceStake, LendingMarket, StableDebt, TermLoan, User};
+
+use crate::error::ErrorCode;
+
+use super::{accrue_stable_debt, accrue_term_loan, apply_stable_payment, apply_term_payment, credit_repayment, set_term_owed, stable_debt, term_owed, write_off_stable, write_off_term};
+
+// Each bank's insurance fund is a vault at `[b"insurance", bank]` holding the reserve kept back from interest
+// payments and what stakers put in. It covers bad debt before depositors take the loss.
+
+#[derive(Accounts)]
+pub struct UpdateBankInsurance<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    // Created the first time the bank's insurance is configured.
+    #[account(
+        init_if_needed,
+        token::mint = mint,
+        token::authority = insurance_vault,
+        payer = signer,
+        seeds = [b"insurance", bank.key().as_ref()],
+        bump,
+    )]
+    pub insurance_vault: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeInsurance<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    // Holds the reserve that is swept into the vault first.
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance", bank.key().as_ref()],
+        bump,
+    )]
+    pub insurance_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + InsuranceStake::INIT_SPACE,
+        seeds = [b"insurance_stake", bank.key().as_ref(), staker.key().as_ref()],
+        bump,
+    )]
+    pub stake: Account<'info, InsuranceStake>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = staker,
+        associated_token::token_program = token_program,
+    )]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RequestUnstake<'info> {
+    pub staker: Signer<'info>,
+
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance_stake", bank.key().as_ref(), staker.key().as_ref()],
+        bump,
+    )]
+    pub stake: Account<'info, InsuranceStake>,
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance", bank.key().as_ref()],
+        bump,
+    )]
+    pub insurance_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance_stake", bank.key().as_ref(), staker.key().as_ref()],
+        bump,
+    )]
+    pub stake: Account<'info, InsuranceStake>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = mint,
+        associated_token::authority = staker,
+        associated_token::token_program = token_program,
+    )]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct CoverBadDebt<'info> {
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    // The mint of the bad debt.
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance", bank.key().as_ref()],
+        bump,
+    )]
+    pub insurance_vault: InterfaceAccount<'info, TokenAccount>,
+
+    // The account of the user whose debt is covered.
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // Bank of the borrower's isolated collateral, if any, so the covered debt frees up its debt ceiling.
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), user_account.isolated_mint.as_ref()],
+        bump,
+    )]
+    pub isolated_bank: Option<Account<'info, Bank>>,
+
+    // The user's term loan from the bank, passed to cover it along with the rest of the debt.
+    #[account(
+        mut,
+        seeds = [b"term_loan", bank.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub term_loan: Option<Account<'info, TermLoan>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn process_update_bank_insurance(ctx: Context<UpdateBankInsurance>, reserve_factor_bps: u64, cooldown: i64, unstake_window: i64) -> Result<()> {
+    if reserve_factor_bps > BASIS_POINTS || cooldown < 0 || unstake_window <= 0 {
+        return Err(ErrorCode::InvalidInsuranceConfig.into());
+    }
+
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    bank.reserve_factor_bps = reserve_factor_bps;
+    bank.insurance_cooldown = cooldown;
+    bank.insurance_unstake_window = unstake_window;
+
+    Ok(())
+}
+
+/// Moves the reserve kept back from interest payments out of the treasury and into the insurance vault.
+fn sweep_insurance_fees<'info>(
+    bank: &mut Bank,
+    bank_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    insurance_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    treasury_bump: u8,
+) -> Result<()> {
+    if bank.insurance_fees_pending == 0 {
+        return Ok(());
+    }
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: bank_token_account.to_account_info(),
+        to: insurance_vault.to_account_info(),
+        authority: bank_token_account.to_account_info(),
+        mint: mint.to_account_info(),
+    };
+
+    let mint_key: Pubkey = mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            bank.market.as_ref(),
+            mint_key.as_ref(),
+            &[treasury_bump],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(token_program.to_account_info(), transfer_cpi_accounts).with_signer(signer_seeds);
+    token_interface::transfer_checked(cpi_ctx, bank.insurance_fees_pending, mint.decimals)?;
+
+    bank.insurance_fees_pending = 0;
+    bank_token_account.reload()?;
+    insurance_vault.reload()?;
+
+    Ok(())
+}
+
+/// Stakes `amount` in the bank's insurance fund for shares of it, which grow with the reserve swept in and shrink
+/// with the bad debt the fund covers.
+pub fn process_stake_insurance(ctx: Context<StakeInsurance>, amount: u64) -> Result<()> {
+    sweep_insurance_fees(
+        &mut ctx.accounts.bank,
+        &mut ctx.accounts.bank_token_account,
+        &mut ctx.accounts.insurance_vault,
+        &ctx.accounts.mint,
+        &ctx.accounts.token_program,
+        ctx.bumps.bank_token_account,
+    )?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.staker_token_account.to_account_info(),
+        to: ctx.accounts.insurance_vault.to_account_info(),
+        authority: ctx.accounts.staker.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_cpi_accounts);
+
+    let balance_before: u64 = ctx.accounts.insurance_vault.amount;
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+    ctx.accounts.insurance_vault.reload()?;
+    let received: u64 = ctx.accounts.insurance_vault.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    let bank_key: Pubkey = ctx.accounts.bank.key();
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    // Reserve swept in before anyone staked is backed by shares no staker owns, so it stays in the fund instead of
+    // going to the first staker. A fund drained by bad debt can't take new stakes while old shares remain.
+    if bank.insurance_shares == 0 {
+        bank.insurance_shares = balance_before;
+    } else if balance_before == 0 {
+        return Err(ErrorCode::SharesWithoutAssets.into());
+    }
+    let shares: u64 = accounting::amount_to_shares_floor(received, balance_before, bank.insurance_shares)?;
+    bank.insurance_shares = bank.insurance_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+
+    let stake: &mut InsuranceStake = &mut ctx.accounts.stake;
+    stake.owner = ctx.accounts.staker.key();
+    stake.bank = bank_key;
+    stake.shares = stake.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+/// Starts the cooldown on `shares` of the staker's stake, replacing any earlier request.
+pub fn process_request_unstake(ctx: Context<RequestUnstake>, shares: u64) -> Result<()> {
+    let stake: &mut InsuranceStake = &mut ctx.accounts.stake;
+    if shares > stake.shares {
+        return Err(ErrorCode::InsufficientFunds.into());
+    }
+
+    stake.unstake_shares = shares;
+    stake.unstake_at = Clock::get()?.unix_timestamp.checked_add(ctx.accounts.bank.insurance_cooldown).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+/// Pays out the shares of a request whose cooldown has elapsed, at what they are worth now. A request left past the
+/// bank's unstake window has expired, so stakers can't sit on one to leave the moment bad debt shows up.
+pub fn process_unstake(ctx: Context<Unstake>) -> Result<()> {
+    let now: i64 = Clock::get()?.unix_timestamp;
+    let stake: &InsuranceStake = &ctx.accounts.stake;
+    if stake.unstake_shares == 0 || now < stake.unstake_at {
+        return Err(ErrorCode::UnstakeCooldown.into());
+    }
+    if now > stake.unstake_at.checked_add(ctx.accounts.bank.insurance_unstake_window).ok_or(ErrorCode::MathOverflow)? {
+        return Err(ErrorCode::UnstakeWindowExpired.into());
+    }
+
+    sweep_insurance_fees(
+        &mut ctx.accounts.bank,
+        &mut ctx.accounts.bank_token_account,
+        &mut ctx.accounts.insurance_vault,
+        &ctx.accounts.mint,
+        &ctx.accounts.token_program,
+        ctx.bumps.bank_token_account,
+    )?;
+
+    let bank_key: Pubkey = ctx.accounts.bank.key();
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    let stake: &mut InsuranceStake = &mut ctx.accounts.stake;
+    let shares: u64 = stake.unstake_shares;
+    let amount: u64 = accounting::shares_to_amount_floor(shares, ctx.accounts.insurance_vault.amount, bank.insurance_shares)?;
+
+    bank.insurance_shares = bank.insurance_shares.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+    stake.shares = stake.shares.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+    stake.unstake_shares = 0;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.insurance_vault.to_account_info(),
+        to: ctx.accounts.staker_token_account.to_account_info(),
+        authority: ctx.accounts.insurance_vault.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"insurance",
+            bank_key.as_ref(),
+            &[ctx.bumps.insurance_vault],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_cpi_accounts).with_signer(signer_seeds);
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+
+    Ok(())
+}
+
+/// Settles the `mint` debt of a user who has no collateral left, typically after a liquidation, including their
+/// term loan from the bank, which has to be passed when there is one. The insurance fund repays as much of it as it can, and whatever
+/// remains is written off against the bank's depositors. Anyone can call it.
+pub fn process_cover_bad_debt(ctx: Context<CoverBadDebt>) -> Result<()> {
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let now: i64 = Clock::get()?.unix_timestamp;
+    // The debt earns its borrow-side rewards up to the moment it is written off.
+    accrue_rewards(&mut ctx.accounts.bank, &mut ctx.accounts.user_account, now)?;
+    accrue_interest(&mut ctx.accounts.bank, now)?;
+    let user: &mut User = &mut ctx.accounts.user_account;
+    let borrowed_shares: u64 = match mint_key {
+        key if key == user.usdc_address => user.borrowed_usdc_shares,
+        _=> user.borrowed_sol_shares,
+    };
+    let stable: &mut StableDebt = stable_debt(user, mint_key);
+    accrue_stable_debt(stable, now)?;
+    let stable_owed: u64 = stable.owed;
+    let term_owed: u64 = match ctx.accounts.term_loan.as_deref_mut() {
+        Some(term_loan) => {
+            accrue_term_loan(term_loan, now)?;
+            set_term_owed(user, mint_key, term_loan.owed);
+            term_loan.owed
+        }
+        // A term loan left out would stay on the user with nothing left to cover it.
+        None if term_owed(user, mint_key) != 0 => return Err(ErrorCode::TermLoanRequired.into()),
+        None => 0,
+    };
+
+    if user.deposited_sol_shares != 0 || user.deposited_usdc_shares != 0 || (borrowed_shares == 0 && stable_owed == 0 && term_owed == 0) {
+        return Err(ErrorCode::NoBadDebt.into());
+    }
+
+    sweep_insurance_fees(
+        &mut ctx.accounts.bank,
+        &mut ctx.accounts.bank_token_account,
+        &mut ctx.accounts.insurance_vault,
+        &ctx.accounts.mint,
+        &ctx.accounts.token_program,
+        ctx.bumps.bank_token_account,
+    )?;
+
+    let variable_debt: u64 = accounting::borrowed_value(&ctx.accounts.bank, borrowed_shares)?;
+    let debt: u64 = variable_debt.checked_add(stable_owed).and_then(|debt| debt.checked_add(term_owed)).ok_or(ErrorCode::MathOverflow)?;
+    let covered: u64 = debt.min(ctx.accounts.insurance_vault.amount);
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.insurance_vault.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.insurance_vault.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let bank_key: Pubkey = ctx.accounts.bank.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"insurance",
+            bank_key.as_ref(),
+            &[ctx.bumps.insurance_vault],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_cpi_accounts).with_signer(signer_seeds);
+
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, covered, ctx.accounts.mint.decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    // The fund pays the variable-rate debt first, then the stable-rate debt and the term loan.
+    let variable_covered: u64 = received.min(variable_debt);
+    let stable_covered: u64 = (received - variable_covered).min(stable_owed);
+    credit_repayment(&mut ctx.accounts.bank, ctx.accounts.isolated_bank.as_deref_mut(), &mut ctx.accounts.user_account, mint_key, variable_covered)?;
+    let stable: &mut StableDebt = stable_debt(&mut ctx.accounts.user_account, mint_key);
+    apply_stable_payment(&mut ctx.accounts.bank, stable, stable_covered)?;
+    if let Some(term_loan) = ctx.accounts.term_loan.as_deref_mut() {
+        apply_term_payment(&mut ctx.accounts.bank, term_loan, &mut ctx.accounts.user_account, received - variable_covered - stable_covered)?;
+    }
+
+    // Whatever the fund couldn't cover is socialized.
+    let stable: &mut StableDebt = stable_debt(&mut ctx.accounts.user_account, mint_key);
+    write_off_stable(&mut ctx.accounts.bank, stable)?;
+    if let Some(term_loan) = ctx.accounts.term_loan.as_deref_mut() {
+        write_off_term(&mut ctx.accounts.bank, term_loan, &mut ctx.accounts.user_account)?;
+    }
+    let user: &mut User = &mut ctx.accounts.user_account;
+    let written_off: u64 = match mint_key {
+        key if key == user.usdc_address => {
+            let written_off: u64 = accounting::write_off(&mut ctx.accounts.bank, user.borrowed_usdc_shares)?;
+            user.borrowed_usdc_shares = 0;
+            user.borrowed_usdc = 0;
+            written_off
+        }
+        _=> {
+            let written_off: u64 = accounting::write_off(&mut ctx.accounts.bank, user.borrowed_sol_shares)?;
+            user.borrowed_sol_shares = 0;
+            user.borrowed_sol = 0;
+            written_off
+        }
+    };
+
+    if user.isolated_mint != Pubkey::default() && mint_key == user.usdc_address {
+        let isolated_bank: &mut Bank = ctx.accounts.isolated_bank.as_deref_mut().ok_or(ErrorCode::IsolatedBankRequired)?;
+        isolated_bank.isolated_debt = isolated_bank.isolated_debt.saturating_sub(written_off);
+        user.isolated_mint = Pubkey::default();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::constants::BASIS_POINTS;
+use crate::health::{health_factor_bps, price_for};
+use crate::outflow;
+use crate::state::{Bank, EmodeCategory, LendingMarket, User};
+use crate::swap_adapter::invoke_swap;
+
+use crate::error::ErrorCode;
+
+use super::{check_borrowable, credit_deposit, record_borrow};
+
+#[derive(Accounts)]
+pub struct Leverage<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    // The mint that is borrowed and sold for more collateral.
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // The borrowed tokens land here for the swap to spend.
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The swap delivers collateral here, and it is deposited from here.
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_collateral_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub price_update: Account<'info, PriceUpdateV2>,
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+
+    /// CHECK: Any swap program the user picks; `invoke_swap` only requires it to be executable and not this program.
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Borrows `amount` of `mint`, sells it for collateral through `swap_program` and deposits what the swap
+/// delivered, in one go. The swap's accounts come in `remaining_accounts` and `swap_data` is its instruction data.
+/// The borrow isn't checked on its own; the finished position has to stay within the loan-to-value and its health
+/// factor has to reach `min_health_factor_bps`, and at least 1.
+pub fn process_leverage<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Leverage<'info>>,
+    amount: u64,
+    min_health_factor_bps: u64,
+    swap_data: Vec<u8>,
+) -> Result<()> {
+    let user: &User = &ctx.accounts.user_account;
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let collateral_mint_key: Pubkey = ctx.accounts.collateral_mint.key();
+
+    if collateral_mint_key == mint_key || (collateral_mint_key != user.usdc_address && mint_key != user.usdc_address) {
+        return Err(ErrorCode::InvalidCollateralMint.into());
+    }
+
+    // Isolated collateral is bound by a debt ceiling and an allowlist that are checked before a borrow, which
+    // the loop doesn't have.
+    if user.isolated_mint != Pubkey::default() || ctx.accounts.collateral_bank.isolated {
+        return Err(ErrorCode::NotBorrowableInIsolation.into());
+    }
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program.clone(), transfer_cpi_accounts).with_signer(signer_seeds);
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+
+    record_borrow(&mut ctx.accounts.bank, &mut ctx.accounts.collateral_bank, &mut ctx.accounts.user_account, amount)?;
+
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.bank, &ctx.accounts.user_account, Some(&*ctx.accounts.price_update), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.bank, amount, price.as_ref(), ctx.accounts.mint.decimals, Clock::get()?.unix_timestamp)?;
+
+    let collateral_before: u64 = ctx.accounts.user_collateral_token_account.amount;
+    invoke_swap(&ctx.accounts.swap_program.to_account_info(), ctx.remaining_accounts, swap_data)?;
+    ctx.accounts.user_collateral_token_account.reload()?;
+    let swapped: u64 = ctx.accounts.user_collateral_token_account.amount.checked_sub(collateral_before).ok_or(ErrorCode::MathOverflow)?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.user_collateral_token_account.to_account_info(),
+        to: ctx.accounts.collateral_bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+    };
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+    let balance_before: u64 = ctx.accounts.collateral_bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, swapped, ctx.accounts.collateral_mint.decimals)?;
+    ctx.accounts.collateral_bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.collateral_bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    credit_deposit(&mut ctx.accounts.collateral_bank, &mut ctx.accounts.user_account, collateral_mint_key, received)?;
+
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+    let user: &User = &ctx.accounts.user_account;
+    let health_factor_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.bank],
+        [&ctx.accounts.collateral_mint.to_account_info(), &ctx.accounts.mint.to_account_info()],
+        [
+            &price_for(user, collateral_mint_key, &ctx.accounts.collateral_price_update, max_price_age)?,
+            &price_for(user, mint_key, &ctx.accounts.price_update, max_price_age)?,
+        ],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+
+    // The caller can ask for more headroom, never for less than a healthy position.
+    if health_factor_bps < min_health_factor_bps.max(BASIS_POINTS) {
+        return Err(ErrorCode::HealthFactorTooLow.into());
+    }
+
+    // The finished position still has to fit within the collateral's loan-to-value.
+    check_borrowable(
+        &ctx.accounts.bank,
+        &ctx.accounts.collateral_bank,
+        user,
+        &ctx.accounts.market,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.collateral_mint.to_account_info(),
+        ctx.accounts.emode_category.as_deref(),
+        &ctx.accounts.collateral_price_update,
+        &ctx.accounts.price_update,
+        0,
+    )?;
+
+    Ok(())
+}
@@ -2,49 +2,148 @@
 
 use anchor_lang::prelude::*;
 use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
-use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdate};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
 
-use crate::{constants::{SOL_USB_FEED_ID, USDC_USD_FEED_ID, MAX_AGE}, state::{Bank, User}};
+use crate::accounting;
+use crate::health::{exchange_rate, health_factor_bps, price_for};
+use crate::outflow;
+use crate::rates::accrue_interest;
+use crate::rewards::accrue_rewards;
+use crate::mint_extensions::{interest_scale, with_interest, without_interest};
+use crate::{constants::BASIS_POINTS, state::{Bank, EmodeCategory, LendingMarket, StableDebt, TermLoan, User}};
 
 use crate::error::ErrorCode;
 
+use super::{accrue_stable_debt, accrue_term_loan, apply_stable_payment, apply_term_payment, set_term_owed, stable_debt};
+
+#[derive(Accounts)]
+pub struct UpdateBankLiquidation<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct FlagLiquidatable<'info> {
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+    #[account(constraint = borrowed_mint.key() != collateral_mint.key() @ ErrorCode::InvalidLiquidationMints)]
+    pub borrowed_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), borrowed_mint.key().as_ref()],
+        bump,
+    )]
+    pub borrowed_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+}
+
 #[derive(Accounts)]
 pub struct Liquidate<'info> {
     #[account(mut)]
-    pub liquidate: Signer<'info>,
+    pub liquidator: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
 
-    pub price_update: Account<'info, PriceUpdate>,
+    // Each PriceUpdateV2 account only carries a single feed, so the collateral and borrowed prices come from separate accounts.
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
     pub collateral_mint: InterfaceAccount<'info, Mint>,
-    pub borrow_mint: InterfaceAccount<'info, Mint>,
+    #[account(constraint = borrowed_mint.key() != collateral_mint.key() @ ErrorCode::InvalidLiquidationMints)]
+    pub borrowed_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [collateral_mint.key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
         bump,
+        constraint = !collateral_bank.auction_liquidation @ ErrorCode::LiquidatedByAuction,
     )]
-    pub collateral_mint: Account<'info, Bank>,
+    pub collateral_bank: Account<'info, Bank>,
 
     #[account(
         mut,
-        seeds = [borrowed_mint.key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), borrowed_mint.key().as_ref()],
         bump,
-    )] 
-    pub borrow_bank: Account<'info, Bank>,
+    )]
+    pub borrowed_bank: Account<'info, Bank>,
 
     #[account(
         mut,
-        seeds = [b"treasury", collateral_mint.key().as_ref()],
+        seeds = [b"treasury", market.key().as_ref(), collateral_mint.key().as_ref()],
         bump,
     )]
     pub collateral_bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [liquidator.key().as_ref()],
+        seeds = [b"treasury", market.key().as_ref(), borrowed_mint.key().as_ref()],
+        bump,
+    )]
+    pub borrowed_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The account of the user being liquidated, not the liquidator's.
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
         bump,
     )]
     pub user_account: Account<'info, User>,
 
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // The user's term loan from the borrowed bank, passed to liquidate it instead of the variable-rate debt.
+    #[account(
+        mut,
+        seeds = [b"term_loan", borrowed_bank.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub term_loan: Option<Account<'info, TermLoan>>,
+
     #[account(
         init_if_needed,
         payer = liquidator,
@@ -68,79 +167,265 @@ pub struct Liquidate<'info> {
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
+pub fn process_update_bank_liquidation(
+    ctx: Context<UpdateBankLiquidation>,
+    close_factor_bps: u64,
+    bonus_min_bps: u64,
+    bonus_max_bps: u64,
+    bonus_max_health_bps: u64,
+    bonus_ramp_seconds: i64,
+) -> Result<()> {
+    if close_factor_bps > BASIS_POINTS
+        || bonus_min_bps > bonus_max_bps
+        || bonus_max_bps >= BASIS_POINTS
+        || bonus_max_health_bps >= BASIS_POINTS
+        || bonus_ramp_seconds < 0
+    {
+        return Err(ErrorCode::InvalidLiquidationConfig.into());
+    }
+
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    bank.liquidation_close_factor_bps = close_factor_bps;
+    bank.liquidation_bonus_min_bps = bonus_min_bps;
+    bank.liquidation_bonus_max_bps = bonus_max_bps;
+    bank.liquidation_bonus_max_health_bps = bonus_max_health_bps;
+    bank.liquidation_bonus_ramp_seconds = bonus_ramp_seconds;
+
+    Ok(())
+}
+
+/// Starts the clock on a position that has become liquidatable, so its liquidation bonus ramps up from then on,
+/// and stops it again once the position is healthy. Anyone can call it.
+pub fn process_flag_liquidatable(ctx: Context<FlagLiquidatable>) -> Result<()> {
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+    let user: &User = &ctx.accounts.user_account;
+    let health_factor_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.collateral_bank, &ctx.accounts.borrowed_bank],
+        [&ctx.accounts.collateral_mint.to_account_info(), &ctx.accounts.borrowed_mint.to_account_info()],
+        [
+            &price_for(user, ctx.accounts.collateral_mint.key(), &ctx.accounts.collateral_price_update, max_price_age)?,
+            &price_for(user, ctx.accounts.borrowed_mint.key(), &ctx.accounts.borrowed_price_update, max_price_age)?,
+        ],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+
+    let user: &mut User = &mut ctx.accounts.user_account;
+    if health_factor_bps >= BASIS_POINTS {
+        user.liquidatable_since = 0;
+    } else if user.liquidatable_since == 0 {
+        user.liquidatable_since = Clock::get()?.unix_timestamp;
+    }
+
+    Ok(())
+}
+
+/// Bonus, in basis points of the seized collateral, for liquidating a position at `health_factor_bps` that has
+/// been liquidatable for `liquidatable_for` seconds. It rises linearly from the bank's minimum at a health factor
+/// of 1 to its maximum at `liquidation_bonus_max_health_bps`, and time adds to the rise, reaching the maximum on
+/// its own after `liquidation_bonus_ramp_seconds`.
+pub fn liquidation_bonus_bps(bank: &Bank, health_factor_bps: u64, liquidatable_for: i64) -> u64 {
+    // Migrated banks carry their legacy bonus unchecked, so the math is done in u128 to stay clear of overflow.
+    let min_bps: u128 = bank.liquidation_bonus_min_bps as u128;
+    let max_bps: u128 = (bank.liquidation_bonus_max_bps as u128).max(min_bps);
+    let basis_points: u128 = BASIS_POINTS as u128;
+
+    let health_range_bps: u128 = basis_points.saturating_sub(bank.liquidation_bonus_max_health_bps as u128).max(1);
+    let health_progress_bps: u128 = basis_points.saturating_sub(health_factor_bps as u128) * basis_points / health_range_bps;
+    let time_progress_bps: u128 = match bank.liquidation_bonus_ramp_seconds {
+        ramp_seconds if ramp_seconds > 0 => liquidatable_for.clamp(0, ramp_seconds) as u128 * basis_points / ramp_seconds as u128,
+        _=> 0,
+    };
+    let progress_bps: u128 = (health_progress_bps + time_progress_bps).min(basis_points);
+
+    // Lies between the two u64 bounds, so it fits back.
+    (min_bps + (max_bps - min_bps) * progress_bps / basis_points) as u64
+}
+
 pub fn process_liquidate(ctx: Context<Liquidate>) -> Result<()> {
     let collateral_bank: &mut Bank = &mut ctx.accounts.collateral_bank;
-    let borrowed_bank = &mut ctx.accounts.borrowed_bank;
+    let borrowed_bank: &mut Bank = &mut ctx.accounts.borrowed_bank;
     let user: &mut User = &mut ctx.accounts.user_account;
 
-    let price_update: &mut PriceUpdate = &mut ctx.accounts.price_update;
+    let collateral_price_update: &Account<PriceUpdateV2> = &ctx.accounts.collateral_price_update;
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+
+    let collateral_mint: AccountInfo = ctx.accounts.collateral_mint.to_account_info();
+    let borrowed_mint: AccountInfo = ctx.accounts.borrowed_mint.to_account_info();
+    let collateral_price: Price = price_for(user, collateral_mint.key(), collateral_price_update, max_price_age)?;
+    let borrowed_price: Price = price_for(user, borrowed_mint.key(), &ctx.accounts.borrowed_price_update, max_price_age)?;
+
+    let (collateral_shares, borrowed_shares): (u64, u64) = match collateral_mint.key() {
+        key if key == user.usdc_address => (user.deposited_usdc_shares, user.borrowed_sol_shares),
+        _=> (user.deposited_sol_shares, user.borrowed_usdc_shares),
+    };
+
+    let now: i64 = Clock::get()?.unix_timestamp;
+    accrue_interest(collateral_bank, now)?;
+    accrue_interest(borrowed_bank, now)?;
+
+    // A term loan is liquidated on its own debt, and becomes liquidatable once it is overdue whatever its health.
+    // Otherwise the stable-rate debt is liquidated along with the variable-rate debt.
+    let mut overdue: bool = false;
+    let borrowed_amount: u64;
+    if let Some(term_loan) = ctx.accounts.term_loan.as_deref_mut() {
+        accrue_term_loan(term_loan, now)?;
+        set_term_owed(user, borrowed_bank.mint_address, term_loan.owed);
+        borrowed_amount = term_loan.owed;
+        overdue = now > term_loan.maturity;
+    } else {
+        let debt: &mut StableDebt = stable_debt(user, borrowed_bank.mint_address);
+        accrue_stable_debt(debt, now)?;
+        let stable_owed: u64 = debt.owed;
+        borrowed_amount = accounting::borrowed_value(borrowed_bank, borrowed_shares)?
+            .checked_add(stable_owed)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
 
-    let sol_feed_id: [u8, 32] = get_feed_id_from_hex(input: SOL_USB_FEED_ID)?;
-    let usdc_feed_id: [u8, 32] = get_feed_id_from_hex(input: USDC_USD_FEED_ID)?;
+    // Health is taken the same way as everywhere else, so whatever `flag_liquidatable` flags can be liquidated.
+    // A user without debt reports `u64::MAX` and is never liquidatable.
+    let emode_category: Option<&EmodeCategory> = ctx.accounts.emode_category.as_deref();
+    let health_before_bps: u64 = health_factor_bps(
+        user,
+        [collateral_bank, borrowed_bank],
+        [&collateral_mint, &borrowed_mint],
+        [&collateral_price, &borrowed_price],
+        emode_category,
+    )?;
+
+    if health_before_bps >= BASIS_POINTS && !overdue {
+        return Err(ErrorCode::NotUnderCollaterized.into());
+    }
 
-    let sol_price: Price = price_update.get_price_no_older_than(clock: &Clock::get()?, maximum_age: MAX_AGE, &sol_feed_id)?;
-    let usdc_price: Price = price_update.get_price_no_older_than(clock: &Clock::get()?, maximum_age: MAX_AGE, &usdc_feed_id)?;
+    // The bonus clock starts at the first liquidation unless `flag_liquidatable` started it earlier.
+    if user.liquidatable_since == 0 {
+        user.liquidatable_since = now;
+    }
+    let bonus_bps: u64 = liquidation_bonus_bps(collateral_bank, health_before_bps, now - user.liquidatable_since);
+
+    // Interest-bearing mints are worth more than their raw amounts, so values are taken on the scaled amounts.
+    let collateral_scale: u128 = interest_scale(&collateral_mint, now)?;
+    let borrowed_scale: u128 = interest_scale(&borrowed_mint, now)?;
+
+    // Amounts below are in tokens: the debt repaid by the liquidator in the borrowed mint, and the collateral
+    // it is worth plus the bonus in the collateral mint. Neither can exceed what the user actually has.
+    let liquidation_amount: u64 = u64::try_from(borrowed_amount as u128 * borrowed_bank.liquidation_close_factor_bps as u128 / BASIS_POINTS as u128)
+        .map_err(|_| ErrorCode::MathOverflow)?;
+
+    let (rate_numerator, rate_denominator): (u128, u128) = exchange_rate(
+        &borrowed_price,
+        ctx.accounts.borrowed_mint.decimals,
+        &collateral_price,
+        ctx.accounts.collateral_mint.decimals,
+    )?;
+    let liquidation_value: u128 = with_interest(liquidation_amount as u128, borrowed_scale)?
+        .checked_mul(rate_numerator)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let seized_collateral: u64 = u64::try_from(without_interest(liquidation_value / rate_denominator, collateral_scale)?)
+        .map_err(|_| ErrorCode::MathOverflow)?;
+    let bonus: u64 = u64::try_from(seized_collateral as u128 * bonus_bps as u128 / BASIS_POINTS as u128)
+        .map_err(|_| ErrorCode::MathOverflow)?;
+    let owed_collateral: u64 = seized_collateral.checked_add(bonus).ok_or(ErrorCode::MathOverflow)?;
+    let liquidator_amount: u64 = owed_collateral.min(accounting::deposit_value(collateral_bank, collateral_shares)?);
+
+    // When the collateral runs short, the liquidator only repays what the collateral it gets covers at the bonus.
+    let liquidation_amount: u64 = if liquidator_amount < owed_collateral {
+        (liquidation_amount as u128 * liquidator_amount as u128 / owed_collateral as u128) as u64
+    } else {
+        liquidation_amount
+    };
 
-    let total_collateral: u64;
-    let total_borrowed: u64;
+    accrue_rewards(collateral_bank, user, now)?;
+    accrue_rewards(borrowed_bank, user, now)?;
 
-    match ctx.accounts.collateral_mint.to_account_into.key() {
-        key: Pubkey if key == user.usdc_address => {
-            let new_usdc: u64 = calculate_account_interest(deposited: user.deposited_usdc, collateral_bank.interest_rate, user.last_updated)?;
-            total_collateral * usdc_price.price as u64 * new_usdc;
-            let new_sol = calculate_account_interest(deposited: user.borrowed_sol, borrowed_bank.interest_rate, last_updated: user.last_updated)?;
-            total_borrowed = sol_price.price as u64 = new_sol;
+    // What the liquidator repays goes to the term loan when one is liquidated, and otherwise to the variable-rate
+    // debt first and the stable-rate debt after it.
+    let (borrowed_shares_removed, variable_repaid): (u64, u64) = match ctx.accounts.term_loan.as_deref_mut() {
+        Some(term_loan) => {
+            apply_term_payment(borrowed_bank, term_loan, user, liquidation_amount)?;
+            (0, 0)
+        }
+        None => {
+            let variable_repaid: u64 = liquidation_amount.min(accounting::borrowed_value(borrowed_bank, borrowed_shares)?);
+            let borrowed_shares_removed: u64 = accounting::repay(borrowed_bank, variable_repaid, borrowed_shares)?;
+            let debt: &mut StableDebt = stable_debt(user, borrowed_bank.mint_address);
+            apply_stable_payment(borrowed_bank, debt, liquidation_amount - variable_repaid)?;
+            (borrowed_shares_removed, variable_repaid)
+        }
+    };
+    let collateral_shares_removed: u64 = accounting::withdraw(collateral_bank, liquidator_amount, collateral_shares)?;
+    outflow::record_inflow(borrowed_bank, variable_repaid, now);
+    let outflow_price: Option<Price> = outflow::outflow_price(collateral_bank, user, Some(collateral_price_update), max_price_age)?;
+    outflow::record_outflow(collateral_bank, liquidator_amount, outflow_price.as_ref(), ctx.accounts.collateral_mint.decimals, now)?;
+
+    match ctx.accounts.collateral_mint.to_account_info().key() {
+        key if key == user.usdc_address => {
+            user.deposited_usdc = user.deposited_usdc.saturating_sub(liquidator_amount);
+            user.deposited_usdc_shares = user.deposited_usdc_shares.checked_sub(collateral_shares_removed).ok_or(ErrorCode::MathOverflow)?;
+            user.borrowed_sol = user.borrowed_sol.saturating_sub(variable_repaid);
+            user.borrowed_sol_shares = user.borrowed_sol_shares.checked_sub(borrowed_shares_removed).ok_or(ErrorCode::MathOverflow)?;
         }
         _=> {
-            let new_sol: u64 = calculate_account_interest(user.deposited_sol, collateral_bank.interest_rate, user.last_updated)?;
-            total_collateral = sol_price.price as u64 * new_sol;
-            let new_usdc: u64 = calculate_account_interest(deposited: user.borrowed_usdc, borrowed_bank.interest_rate, user.last_updated_borrow)?;
-            total_borrowed = usdc_price.price as u64 * new_usdc;
+            user.deposited_sol = user.deposited_sol.saturating_sub(liquidator_amount);
+            user.deposited_sol_shares = user.deposited_sol_shares.checked_sub(collateral_shares_removed).ok_or(ErrorCode::MathOverflow)?;
+            user.borrowed_usdc = user.borrowed_usdc.saturating_sub(variable_repaid);
+            user.borrowed_usdc_shares = user.borrowed_usdc_shares.checked_sub(borrowed_shares_removed).ok_or(ErrorCode::MathOverflow)?;
         }
     }
 
-    let health_factor: f64 * ((total_collateral as f64 * collateral_bank.liquidation_threshold as f64) / total_borrowed as f64) as f64;
+    if user.isolated_mint == collateral_bank.mint_address {
+        collateral_bank.isolated_debt = collateral_bank.isolated_debt.saturating_sub(variable_repaid);
+        if user.deposited_sol_shares == 0 && user.borrowed_usdc_shares == 0 {
+            user.isolated_mint = Pubkey::default();
+        }
+    }
 
-    if health_factor >= 1.0 {
-        return Err(ErrorCode::NotUnderCollaterized.into());
+    // A position the liquidation brought back to health needs a fresh flag to start the bonus clock again.
+    let health_after_bps: u64 = health_factor_bps(
+        user,
+        [collateral_bank, borrowed_bank],
+        [&collateral_mint, &borrowed_mint],
+        [&collateral_price, &borrowed_price],
+        emode_category,
+    )?;
+    if health_after_bps >= BASIS_POINTS {
+        user.liquidatable_since = 0;
     }
 
     let transfer_to_bank: TransferChecked = TransferChecked {
-        from: ctx.accounts.liquidator_borrowed_token_account.to_account_into(),
-        to: ctx.accounts.borrowed_bank_token_account.to_account_into(),
-        authority: ctx.accounts.liquidator.to_account_into(),
-        mint: ctx.accounts.borrowed_mint.to_account_into(),
+        from: ctx.accounts.liquidator_borrowed_token_account.to_account_info(),
+        to: ctx.accounts.borrowed_bank_token_account.to_account_info(),
+        authority: ctx.accounts.liquidator.to_account_info(),
+        mint: ctx.accounts.borrowed_mint.to_account_info(),
     };
 
-    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_into();
-    let cpi_ctx: CpiContext = CpiContext::new(cpi_program.clone(), accounts: transfer_to_bank);
-    let decimals: u8 = ctx.accounts.borrow_mint.decimals;
-
-    let liquidation_amount: u64 = total_borrowed.check_nul(borrowed_bank.liquidation_close_factor).unwrap();
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program.clone(), transfer_to_bank);
+    let decimals: u8 = ctx.accounts.borrowed_mint.decimals;
 
     token_interface::transfer_checked(cpi_ctx, liquidation_amount, decimals)?;
 
-    let liquidator_amount: u64 = {liquidation_amount = collateral_bank.liquidation_bonus} + liquidation_amount;
-
     let transfer_to_liquidator: TransferChecked = TransferChecked {
-        from: ctx.accounts.collateral_bank_token_account.to_account_into(),
-        to: ctx.accounts.liquidator_collateral_token_account.to_account_into(),
-        authority: ctx.accounts.collateral_bank_token_account.to_account_into(),
-        mint: ctx.accounts.collateral_mint.to_account_into(),
-    };  
-    
-    
+        from: ctx.accounts.collateral_bank_token_account.to_account_info(),
+        to: ctx.accounts.liquidator_collateral_token_account.to_account_info(),
+        authority: ctx.accounts.collateral_bank_token_account.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+    };
+
+
+    let market_key: Pubkey = ctx.accounts.market.key();
     let mint_key: Pubkey = ctx.accounts.collateral_mint.key();
     let signer_seeds: &[&[&[u8]]] = &[
         &[
             b"treasury",
+            market_key.as_ref(),
             mint_key.as_ref(),
             &[ctx.bumps.collateral_bank_token_account],
         ]
     ];
 
-    let cpi_ctx_to_liquidator = CpiContext::new(cpi_program.clone(), accounts: transfer_to_liquidator)
-        .with_signer[signer_seeds];
+    let cpi_ctx_to_liquidator: CpiContext<TransferChecked> = CpiContext::new(cpi_program.clone(), transfer_to_liquidator)
+        .with_signer(signer_seeds);
 
     let collateral_decimals: u8 = ctx.accounts.collateral_mint.decimals;
 
@@ -149,12 +434,5 @@ pub fn process_liquidate(ctx: Context<Liquidate>) -> Result<()> {
     Ok(())
 }
 
-pub fn calculate_account_interest(deposited: u64, interest_rate: u64, last_updated: i64) -> Result<()> {
-    let current_time = Clock::get()?.unix_timestamp;
-    let time_diff: u64 = current_time - last_updated;
-    let new_value: u64 =(deposited as f64 * E.powf(interest_rate as f32 * time_diff as f32) as f64) as u64;
-    Ok(new_value)
-}
-
 
 
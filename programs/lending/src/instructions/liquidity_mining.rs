@@ -0,0 +1,221 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::constants::MAX_REWARD_STREAMS;
+use crate::rewards::accrue_rewards;
+use crate::state::{Bank, LendingMarket, RewardPosition, RewardStream, User};
+
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct InitRewardStream<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    // Holds the rewards the bank pays out in `reward_mint`, shared by every stream of that mint.
+    #[account(
+        init_if_needed,
+        token::mint = reward_mint,
+        token::authority = reward_vault,
+        token::token_program = token_program,
+        payer = signer,
+        seeds = [b"reward_vault", bank.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault", bank.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault", bank.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = reward_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_reward_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Starts a stream in an unused slot of the bank. Slots are never reused, so positions can't carry an index from
+/// an older stream into a new one.
+pub fn process_init_reward_stream(
+    ctx: Context<InitRewardStream>,
+    stream: u8,
+    borrow_side: bool,
+    emission_rate: u64,
+    start: i64,
+    end: i64,
+) -> Result<()> {
+    let slot: &mut RewardStream = ctx.accounts.bank.reward_streams.get_mut(stream as usize).ok_or(ErrorCode::InvalidRewardStream)?;
+
+    if slot.mint != Pubkey::default() {
+        return Err(ErrorCode::RewardStreamInUse.into());
+    }
+    if end <= start {
+        return Err(ErrorCode::InvalidRewardStream.into());
+    }
+
+    slot.mint = ctx.accounts.reward_mint.key();
+    slot.borrow_side = borrow_side;
+    slot.emission_rate = emission_rate;
+    slot.start = start;
+    slot.end = end;
+    slot.index = 0;
+    slot.last_updated = Clock::get()?.unix_timestamp;
+
+    Ok(())
+}
+
+pub fn process_fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.signer_token_account.to_account_info(),
+        to: ctx.accounts.reward_vault.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.reward_mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+    let decimals: u8 = ctx.accounts.reward_mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)
+}
+
+pub fn process_claim_rewards(ctx: Context<ClaimRewards>, stream: u8) -> Result<()> {
+    let stream: usize = stream as usize;
+    if stream >= MAX_REWARD_STREAMS || ctx.accounts.bank.reward_streams[stream].mint != ctx.accounts.reward_mint.key() {
+        return Err(ErrorCode::InvalidRewardStream.into());
+    }
+
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    let user: &mut User = &mut ctx.accounts.user_account;
+
+    accrue_rewards(bank, user, Clock::get()?.unix_timestamp)?;
+
+    let position: &mut RewardPosition = match bank.mint_address {
+        key if key == user.usdc_address => &mut user.usdc_rewards.positions[stream],
+        _=> &mut user.sol_rewards.positions[stream],
+    };
+    let amount: u64 = position.earned;
+
+    if amount > ctx.accounts.reward_vault.amount {
+        return Err(ErrorCode::InsufficientFunds.into());
+    }
+    position.earned = 0;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.reward_vault.to_account_info(),
+        to: ctx.accounts.user_reward_token_account.to_account_info(),
+        authority: ctx.accounts.reward_vault.to_account_info(),
+        mint: ctx.accounts.reward_mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let bank_key: Pubkey = ctx.accounts.bank.key();
+    let reward_mint_key: Pubkey = ctx.accounts.reward_mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"reward_vault",
+            bank_key.as_ref(),
+            reward_mint_key.as_ref(),
+            &[ctx.bumps.reward_vault],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+
+    let decimals: u8 = ctx.accounts.reward_mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)
+}
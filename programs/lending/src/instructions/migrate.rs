@@ -0,0 +1,305 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{self, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::constants::BASIS_POINTS;
+use crate::state::{Bank, LendingMarket, User};
+
+use crate::error::ErrorCode;
+
+// Banks and users created before markets existed live at bare `[mint]` / `[owner]` addresses, with each bank's tokens
+// in a `[b"treasury", mint]` treasury. `migrate_bank` and `migrate_user` move them into a market at the prefixed
+// addresses and close the old accounts.
+//
+// Fields are only ever appended to `Bank` and `User`, so an account created before the latest ones can't be
+// deserialized until `realloc_bank` / `realloc_user` grow it. The zeroed space leaves the new fields at their
+// defaults.
+
+// `Bank` as it was laid out before markets existed.
+#[derive(AnchorDeserialize)]
+struct LegacyBank {
+    authority: Pubkey,
+    mint_address: Pubkey,
+    total_deposits: u64,
+    total_deposit_shares: u64,
+    total_borrowed: u64,
+    total_borrowed_shares: u64,
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+    liquidation_close_factor: u64,
+    max_ltv: u64,
+    last_updated: u64,
+    interest_rate: u64,
+}
+
+// `User` as it was laid out before markets existed.
+#[derive(AnchorDeserialize)]
+struct LegacyUser {
+    owner: Pubkey,
+    deposited_sol: u64,
+    deposited_sol_shares: u64,
+    borrowed_sol: u64,
+    borrowed_sol_shares: u64,
+    deposited_usdc: u64,
+    deposited_usdc_shares: u64,
+    borrowed_usdc: u64,
+    borrowed_usdc_shares: u64,
+    usdc_address: Pubkey,
+    last_updated: i64,
+    last_updated_borrow: i64,
+}
+
+#[derive(Accounts)]
+pub struct MigrateBank<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: read as a `LegacyBank` once its discriminator is checked.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub legacy_bank: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub legacy_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Bank::INIT_SPACE,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        init,
+        token::mint = mint,
+        token::authority = bank_token_account,
+        payer = signer,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateUser<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub market: Account<'info, LendingMarket>,
+
+    /// CHECK: read as a `LegacyUser` once its discriminator is checked.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [signer.key().as_ref()],
+        bump,
+    )]
+    pub legacy_user_account: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + User::INIT_SPACE,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReallocBank<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: too short to deserialize as a `Bank` until it is grown.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReallocUser<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub market: Account<'info, LendingMarket>,
+
+    /// CHECK: too short to deserialize as a `User` until it is grown.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Deserializes the legacy account `info` after checking it carries `discriminator`.
+fn read_legacy<T: AnchorDeserialize>(info: &AccountInfo, discriminator: [u8; 8]) -> Result<T> {
+    let data = info.try_borrow_data()?;
+    if data.len() < 8 || data[..8] != discriminator {
+        return Err(ErrorCode::InvalidLegacyAccount.into());
+    }
+    T::deserialize(&mut &data[8..]).map_err(|_| ErrorCode::InvalidLegacyAccount.into())
+}
+
+/// Hands `info`'s lamports to `destination` and gives the emptied account back to the system program.
+fn close_legacy<'info>(info: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    let lamports: u64 = info.lamports();
+    **destination.try_borrow_mut_lamports()? = destination.lamports().checked_add(lamports).ok_or(ErrorCode::MathOverflow)?;
+    **info.try_borrow_mut_lamports()? = 0;
+
+    info.assign(&system_program::ID);
+    info.realloc(0, false)?;
+
+    Ok(())
+}
+
+/// Grows `info` to `space` bytes, zeroing the new bytes, with `payer` topping up its rent.
+fn grow<'info>(info: &AccountInfo<'info>, payer: &AccountInfo<'info>, system_program: &AccountInfo<'info>, space: usize) -> Result<()> {
+    if info.data_len() >= space {
+        return Ok(());
+    }
+
+    let rent: u64 = Rent::get()?.minimum_balance(space).saturating_sub(info.lamports());
+    if rent > 0 {
+        let transfer_accounts: Transfer = Transfer {
+            from: payer.clone(),
+            to: info.clone(),
+        };
+        system_program::transfer(CpiContext::new(system_program.clone(), transfer_accounts), rent)?;
+    }
+
+    info.realloc(space, true)?;
+
+    Ok(())
+}
+
+/// Moves a bank from before markets existed into the market, along with every token its treasury holds.
+pub fn process_migrate_bank(ctx: Context<MigrateBank>) -> Result<()> {
+    let legacy_info: AccountInfo = ctx.accounts.legacy_bank.to_account_info();
+    let legacy: LegacyBank = read_legacy(&legacy_info, Bank::DISCRIMINATOR)?;
+
+    ctx.accounts.bank.set_inner(Bank {
+        authority: legacy.authority,
+        market: ctx.accounts.market.key(),
+        mint_address: legacy.mint_address,
+        total_deposits: legacy.total_deposits,
+        total_deposit_shares: legacy.total_deposit_shares,
+        total_borrowed: legacy.total_borrowed,
+        total_borrowed_shares: legacy.total_borrowed_shares,
+        // Legacy banks weighted collateral by plain multipliers, which are basis points now.
+        liquidation_threshold: legacy.liquidation_threshold.checked_mul(BASIS_POINTS).ok_or(ErrorCode::MathOverflow)?,
+        liquidation_bonus_min_bps: legacy.liquidation_bonus,
+        liquidation_close_factor_bps: legacy.liquidation_close_factor,
+        max_ltv: legacy.max_ltv.checked_mul(BASIS_POINTS).ok_or(ErrorCode::MathOverflow)?,
+        last_updated: legacy.last_updated,
+        interest_rate: legacy.interest_rate,
+        ..Bank::default()
+    });
+
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            mint_key.as_ref(),
+            &[ctx.bumps.legacy_bank_token_account],
+        ]
+    ];
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.legacy_bank_token_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.legacy_bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program.clone(), transfer_cpi_accounts).with_signer(signer_seeds);
+    token_interface::transfer_checked(cpi_ctx, ctx.accounts.legacy_bank_token_account.amount, ctx.accounts.mint.decimals)?;
+
+    let close_accounts: CloseAccount = CloseAccount {
+        account: ctx.accounts.legacy_bank_token_account.to_account_info(),
+        destination: ctx.accounts.signer.to_account_info(),
+        authority: ctx.accounts.legacy_bank_token_account.to_account_info(),
+    };
+    token_interface::close_account(CpiContext::new(cpi_program, close_accounts).with_signer(signer_seeds))?;
+
+    close_legacy(&legacy_info, &ctx.accounts.signer.to_account_info())
+}
+
+/// Moves the signer's user account from before markets existed into the market.
+pub fn process_migrate_user(ctx: Context<MigrateUser>) -> Result<()> {
+    let legacy_info: AccountInfo = ctx.accounts.legacy_user_account.to_account_info();
+    let legacy: LegacyUser = read_legacy(&legacy_info, User::DISCRIMINATOR)?;
+
+    ctx.accounts.user_account.set_inner(User {
+        owner: legacy.owner,
+        market: ctx.accounts.market.key(),
+        deposited_sol: legacy.deposited_sol,
+        deposited_sol_shares: legacy.deposited_sol_shares,
+        borrowed_sol: legacy.borrowed_sol,
+        borrowed_sol_shares: legacy.borrowed_sol_shares,
+        deposited_usdc: legacy.deposited_usdc,
+        deposited_usdc_shares: legacy.deposited_usdc_shares,
+        borrowed_usdc: legacy.borrowed_usdc,
+        borrowed_usdc_shares: legacy.borrowed_usdc_shares,
+        usdc_address: legacy.usdc_address,
+        last_updated: legacy.last_updated,
+        last_updated_borrow: legacy.last_updated_borrow,
+        ..User::default()
+    });
+
+    close_legacy(&legacy_info, &ctx.accounts.signer.to_account_info())
+}
+
+/// Grows a bank created before the latest `Bank` fields to the current size.
+pub fn process_realloc_bank(ctx: Context<ReallocBank>) -> Result<()> {
+    let info: AccountInfo = ctx.accounts.bank.to_account_info();
+    grow(&info, &ctx.accounts.signer.to_account_info(), &ctx.accounts.system_program.to_account_info(), 8 + Bank::INIT_SPACE)?;
+
+    Bank::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    Ok(())
+}
+
+/// Grows the signer's user account, created before the latest `User` fields, to the current size.
+pub fn process_realloc_user(ctx: Context<ReallocUser>) -> Result<()> {
+    let info: AccountInfo = ctx.accounts.user_account.to_account_info();
+    grow(&info, &ctx.accounts.signer.to_account_info(), &ctx.accounts.system_program.to_account_info(), 8 + User::INIT_SPACE)?;
+
+    User::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    Ok(())
+}
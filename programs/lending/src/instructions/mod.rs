@@ -1,9 +1,6 @@
 pub use admin::*;
 pub mod admin;
 // pub mod state;
-pub use instruction;
-pub mod instruction;
-// mod instruction;
 pub use deposit::*;
 mod deposit;
 
@@ -15,8 +12,27 @@ pub use repay::*;
 pub mod repay;
 pub use liquidate::*;
 pub mod liquidate;
-
-
-
-
-
+pub use migrate::*;
+pub mod migrate;
+pub use native::*;
+pub mod native;
+pub use delegation::*;
+pub mod delegation;
+pub use emode::*;
+pub mod emode;
+pub use liquidity_mining::*;
+pub mod liquidity_mining;
+pub use term_loan::*;
+pub mod term_loan;
+pub use stable_rate::*;
+pub mod stable_rate;
+pub use leverage::*;
+pub mod leverage;
+pub use swap_collateral::*;
+pub mod swap_collateral;
+pub use deleverage::*;
+pub mod deleverage;
+pub use auction::*;
+pub mod auction;
+pub use insurance::*;
+pub mod insurance;
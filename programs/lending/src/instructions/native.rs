@@ -0,0 +1,226 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token_interface::{self, CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface, TransferChecked};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::health::check_health;
+use crate::outflow;
+use crate::state::{Bank, EmodeCategory, LendingMarket, User};
+use crate::error::ErrorCode;
+
+use super::{credit_deposit, debit_withdrawal};
+
+// Native variants of deposit and withdraw for the SOL bank. Lamports are wrapped into (or unwrapped from) a
+// temporary wSOL account that is closed before the instruction returns, so wallets only see plain SOL move.
+
+#[derive(Accounts)]
+pub struct DepositNative<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    #[account(address = native_mint::ID)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        init,
+        payer = signer,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+        seeds = [b"wrap", signer.key().as_ref()],
+        bump,
+    )]
+    pub wrapped_sol_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawNative<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    #[account(address = native_mint::ID)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        init,
+        payer = signer,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+        seeds = [b"wrap", signer.key().as_ref()],
+        bump,
+    )]
+    pub wrapped_sol_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The user's USDC side, which counts towards the health of the position left behind.
+    #[account(
+        address = user_account.usdc_address @ ErrorCode::InvalidCollateralMint,
+    )]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), usdc_mint.key().as_ref()],
+        bump,
+    )]
+    pub usdc_bank: Account<'info, Bank>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // Value the withdrawal against a bank outflow limit set in USD and the position left behind when the user has
+    // debt, and can be left out otherwise.
+    pub price_update: Option<Account<'info, PriceUpdateV2>>,
+    pub usdc_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn process_deposit_native(ctx: Context<DepositNative>, amount: u64) -> Result<()> {
+    let wrap_accounts: Transfer = Transfer {
+        from: ctx.accounts.signer.to_account_info(),
+        to: ctx.accounts.wrapped_sol_account.to_account_info(),
+    };
+    system_program::transfer(CpiContext::new(ctx.accounts.system_program.to_account_info(), wrap_accounts), amount)?;
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let sync_accounts: SyncNative = SyncNative {
+        account: ctx.accounts.wrapped_sol_account.to_account_info(),
+    };
+    token_interface::sync_native(CpiContext::new(cpi_program.clone(), sync_accounts))?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.wrapped_sol_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+    let decimals: u8 = ctx.accounts.mint.decimals;
+    token_interface::transfer_checked(CpiContext::new(cpi_program.clone(), transfer_cpi_accounts), amount, decimals)?;
+
+    // The emptied wrapper hands its rent back to the signer.
+    let close_accounts: CloseAccount = CloseAccount {
+        account: ctx.accounts.wrapped_sol_account.to_account_info(),
+        destination: ctx.accounts.signer.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+    };
+    token_interface::close_account(CpiContext::new(cpi_program, close_accounts))?;
+
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    credit_deposit(&mut ctx.accounts.bank, &mut ctx.accounts.user_account, mint_key, amount)
+}
+
+pub fn process_withdraw_native(ctx: Context<WithdrawNative>, amount: u64) -> Result<()> {
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let amount: u64 = debit_withdrawal(&mut ctx.accounts.bank, &mut ctx.accounts.user_account, mint_key, amount)?;
+
+    check_health(
+        &ctx.accounts.user_account,
+        [&ctx.accounts.bank, &ctx.accounts.usdc_bank],
+        [&ctx.accounts.mint.to_account_info(), &ctx.accounts.usdc_mint.to_account_info()],
+        [ctx.accounts.price_update.as_deref(), ctx.accounts.usdc_price_update.as_deref()],
+        ctx.accounts.emode_category.as_deref(),
+        ctx.accounts.market.max_price_age,
+    )?;
+
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.bank, &ctx.accounts.user_account, ctx.accounts.price_update.as_deref(), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.bank, amount, price.as_ref(), ctx.accounts.mint.decimals, Clock::get()?.unix_timestamp)?;
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ]
+    ];
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        to: ctx.accounts.wrapped_sol_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+    let decimals: u8 = ctx.accounts.mint.decimals;
+    token_interface::transfer_checked(
+        CpiContext::new(cpi_program.clone(), transfer_cpi_accounts).with_signer(signer_seeds),
+        amount,
+        decimals,
+    )?;
+
+    // Closing the wrapper unwraps it: the withdrawn lamports and its rent both go to the signer.
+    let close_accounts: CloseAccount = CloseAccount {
+        account: ctx.accounts.wrapped_sol_account.to_account_info(),
+        destination: ctx.accounts.signer.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+    };
+    token_interface::close_account(CpiContext::new(cpi_program, close_accounts))?;
+
+    Ok(())
+}
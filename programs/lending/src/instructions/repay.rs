@@ -3,7 +3,12 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
 
-use crate::state::{Bank, User};
+use crate::accounting;
+use crate::outflow;
+use crate::rates::accrue_interest;
+use crate::rewards::accrue_rewards;
+use crate::mint_extensions::amount_before_transfer_fee;
+use crate::state::{Bank, LendingMarket, User};
 
 use crate::error::ErrorCode;
 
@@ -11,108 +16,203 @@ use crate::error::ErrorCode;
 pub struct Repay<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    pub market: Account<'info, LendingMarket>,
     pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [mint.key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank: Account<'info, Bank>,
 
     #[account(
         mut,
-        seeds = [b"treasury", mint.key.as_ref()],
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [signer.key().as_ref()],
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
         bump,
     )]
     pub user_account: Account<'info, User>,
 
+    // Bank of the borrower's isolated collateral, if any, so the repayment frees up its debt ceiling.
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), user_account.isolated_mint.as_ref()],
+        bump,
+    )]
+    pub isolated_bank: Option<Account<'info, Bank>>,
+
     #[account(
         mut,
         associated_token::mint = mint,
         associated_token::authority = signer,
         associated_token::token_program = token_program,
     )]
-    pub user_token_account: Interface<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
  
 }
 
-pub fn process_repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
-    
-    let user: User = &mut ctx.accounts.user_account;
+#[derive(Accounts)]
+pub struct RepayFor<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub market: Account<'info, LendingMarket>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
-    let borrow_value: u64;
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
 
-    match ctx.accounts.mint.to_account_into.key() {
-        key::Pubkey if key == user.usdc_address => {
-            borrow_value = user.borrowed_usdc;
-        },
-        _=> {
-            borrow_value = user.borrowed_sol;
-        }
-        
-    }
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    let time_diff: i64 = user.last_updated_borrow - Clock::get()f.unix_timestamp;
+    // The borrower whose debt is repaid; it doesn't have to be the signer.
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
 
-    let bank: Bank = &mut ctx.accounts.bank;
+    // Bank of the borrower's isolated collateral, if any, so the repayment frees up its debt ceiling.
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), user_account.isolated_mint.as_ref()],
+        bump,
+    )]
+    pub isolated_bank: Option<Account<'info, Bank>>,
 
-    bank.total_borrowed += (bank.total_borrowed as f64 * E.powf(bank.interest_rate as f32 * time_diff as f32) as f64) as u64;
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
 
-    let value_per_share: f64 = bank.total_borrowed as f64 / bank.total_borrowed_shares as f64;
+pub fn process_repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
+    let amount: u64 = prepare_repayment(&mut ctx.accounts.bank, &ctx.accounts.user_account, &ctx.accounts.mint.to_account_info(), amount)?;
 
-    let user_value: u64 = borrow_value / value_per_share as u64;
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.user_token_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
 
-    if amount > user_value {
-        return Err(ErrorCode::OverRepay.into());
-    }
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+    let decimals: u8 = ctx.accounts.mint.decimals;
+
+    // Transfer-fee mints deliver less than `amount`, so only the received amount pays down the debt.
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    credit_repayment(&mut ctx.accounts.bank, ctx.accounts.isolated_bank.as_deref_mut(), &mut ctx.accounts.user_account, mint_key, received)
+}
+
+pub fn process_repay_for(ctx: Context<RepayFor>, amount: u64) -> Result<()> {
+    let amount: u64 = prepare_repayment(&mut ctx.accounts.bank, &ctx.accounts.user_account, &ctx.accounts.mint.to_account_info(), amount)?;
 
     let transfer_cpi_accounts: TransferChecked = TransferChecked {
-        from: ctx.accounts.user_token_account.to_account_into(),
-        to: ctx.accounts.bank_token_account.to_account_into(),
-        authority: ctx.accounts.signer.to_account_into(),
-        mint: ctx.accounts.mint.to_account_into(),
+        from: ctx.accounts.signer_token_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
     };
 
-    let cpi_program = ctx.accounts.token_program.to_account_into();
+    let cpi_program = ctx.accounts.token_program.to_account_info();
 
-    let cpi_ctx: CpiContext = CpiContext::new(cpi_program, transfer_cpi_accounts);
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
 
-    let decimals: ul = ctx.accounts.mint.decimals;
+    let decimals: u8 = ctx.accounts.mint.decimals;
 
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
 
-    let borrow_ratio: u64 = amount.checked_div(bank.total_borrowed).unwrap();
-    let user_shares: u64 = bank.total_borrowed_shares.check_nul(borrow_ratio).unwrap();
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    credit_repayment(&mut ctx.accounts.bank, ctx.accounts.isolated_bank.as_deref_mut(), &mut ctx.accounts.user_account, mint_key, received)
+}
 
-    match ctx.accounts.mint.to_account_into().key() {
-        key: Pubkey if key == user.usdc_address => {
-            user.borrowed_usdc -= amount;
-            user.borrowed_usdc_shares -= user_shares;
+fn borrowed_shares(user: &User, mint: Pubkey) -> u64 {
+    match mint {
+        key if key == user.usdc_address => {
+            user.borrowed_usdc_shares
         },
         _=> {
-            user.borrowed_sol -= amount;
-            user.borrowed_sol_shares -= user_shares;
+            user.borrowed_sol_shares
         }
     }
+}
 
-    bank.total_borrowed -= amount;
-    bank.total_borrowed_shares -= user_shares;
-
-    user.last_updated = Clock::get()?.unix_timestamp;
+/// Accrues borrow interest and returns how much has to be transferred to repay `amount` of `user`'s debt.
+/// `u64::MAX` repays the whole accrued debt, grossed up so it still arrives in full after any transfer fee.
+pub(crate) fn prepare_repayment(bank: &mut Bank, user: &User, mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let user_shares: u64 = borrowed_shares(user, mint.key());
 
+    accrue_interest(bank, Clock::get()?.unix_timestamp)?;
 
-    Ok(())
+    if amount == u64::MAX {
+        let owed: u64 = accounting::borrowed_value(bank, user_shares)?;
+        amount_before_transfer_fee(mint, owed, Clock::get()?.epoch)
+    } else {
+        Ok(amount)
+    }
 }
 
+/// Books `amount` the treasury has already received against `user`'s `mint` debt.
+pub(crate) fn credit_repayment(bank: &mut Bank, isolated_bank: Option<&mut Bank>, user: &mut User, mint: Pubkey, amount: u64) -> Result<()> {
+    let now: i64 = Clock::get()?.unix_timestamp;
+    accrue_rewards(bank, user, now)?;
+
+    let shares_to_remove: u64 = accounting::repay(bank, amount, borrowed_shares(user, mint))?;
+    outflow::record_inflow(bank, amount, now);
+
+    // Debt backed by isolated collateral is always the USDC-side borrow.
+    if user.isolated_mint != Pubkey::default() && mint == user.usdc_address {
+        let isolated_bank: &mut Bank = isolated_bank.ok_or(ErrorCode::IsolatedBankRequired)?;
+        isolated_bank.isolated_debt = isolated_bank.isolated_debt.saturating_sub(amount);
+    }
+
+    match mint {
+        key if key == user.usdc_address => {
+            user.borrowed_usdc_shares = user.borrowed_usdc_shares.checked_sub(shares_to_remove).ok_or(ErrorCode::MathOverflow)?;
+            user.borrowed_usdc = if user.borrowed_usdc_shares == 0 { 0 } else { user.borrowed_usdc.saturating_sub(amount) };
+        },
+        _=> {
+            user.borrowed_sol_shares = user.borrowed_sol_shares.checked_sub(shares_to_remove).ok_or(ErrorCode::MathOverflow)?;
+            user.borrowed_sol = if user.borrowed_sol_shares == 0 { 0 } else { user.borrowed_sol.saturating_sub(amount) };
+        }
+    }
 
+    user.last_updated = Clock::get()?.unix_timestamp;
+    // As with deposits, only a fresh flag restarts the liquidation bonus clock.
+    user.liquidatable_since = 0;
+
+    Ok(())
+}
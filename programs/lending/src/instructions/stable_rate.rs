@@ -0,0 +1,373 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::accounting;
+use crate::mint_extensions::amount_before_transfer_fee;
+use crate::outflow;
+use crate::rewards::accrue_rewards;
+use crate::rates::{curve_rate_bps, simple_interest, supply_rate_bps, utilization_bps};
+use crate::state::{Bank, EmodeCategory, LendingMarket, StableDebt, User};
+
+use crate::error::ErrorCode;
+
+use super::check_borrowable;
+
+#[derive(Accounts)]
+pub struct UpdateBankStableRates<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct BorrowStable<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    // Values the debt, and the borrow against a bank outflow limit set in USD.
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct RepayStable<'info> {
+    pub signer: Signer<'info>,
+    pub market: Account<'info, LendingMarket>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// Permissionless: anyone can move a user's stable rate once the bank's conditions call for it.
+#[derive(Accounts)]
+pub struct RebalanceStableRate<'info> {
+    pub signer: Signer<'info>,
+    pub market: Account<'info, LendingMarket>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), user_account.owner.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+pub fn process_update_bank_stable_rates(
+    ctx: Context<UpdateBankStableRates>,
+    premium_bps: u64,
+    rebalance_utilization_bps: u64,
+    rebalance_down_delta_bps: u64,
+) -> Result<()> {
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    bank.stable_premium_bps = premium_bps;
+    bank.stable_rebalance_utilization_bps = rebalance_utilization_bps;
+    bank.stable_rebalance_down_delta_bps = rebalance_down_delta_bps;
+
+    Ok(())
+}
+
+pub fn process_borrow_stable(ctx: Context<BorrowStable>, amount: u64) -> Result<()> {
+    // Isolated debt is tracked against the variable-rate borrow only.
+    if ctx.accounts.user_account.isolated_mint != Pubkey::default() {
+        return Err(ErrorCode::NotBorrowableInIsolation.into());
+    }
+
+    check_borrowable(
+        &ctx.accounts.bank,
+        &ctx.accounts.collateral_bank,
+        &ctx.accounts.user_account,
+        &ctx.accounts.market,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.collateral_mint.to_account_info(),
+        ctx.accounts.emode_category.as_deref(),
+        &ctx.accounts.price_update,
+        &ctx.accounts.borrowed_price_update,
+        amount,
+    )?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+
+    let decimals: u8 = ctx.accounts.mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+    let now: i64 = Clock::get()?.unix_timestamp;
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    accrue_rewards(bank, &mut ctx.accounts.user_account, now)?;
+    let debt: &mut StableDebt = stable_debt(&mut ctx.accounts.user_account, mint_key);
+    accrue_stable_debt(debt, now)?;
+
+    // The rate is taken before the borrow moves utilization.
+    let rate_bps: u64 = curve_rate_bps(bank)?.checked_add(bank.stable_premium_bps).ok_or(ErrorCode::MathOverflow)?;
+
+    debt.rate_bps = weighted_rate(debt.rate_bps, debt.principal, rate_bps, amount)?;
+    debt.principal = debt.principal.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    debt.owed = debt.owed.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+
+    bank.average_stable_rate_bps = weighted_rate(bank.average_stable_rate_bps, bank.total_stable_borrowed, rate_bps, amount)?;
+    bank.total_stable_borrowed = bank.total_stable_borrowed.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.bank, &ctx.accounts.user_account, Some(&*ctx.accounts.borrowed_price_update), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.bank, amount, price.as_ref(), decimals, now)
+}
+
+/// Repays `amount` of the signer's stable-rate debt, or all of it for `u64::MAX`.
+pub fn process_repay_stable(ctx: Context<RepayStable>, amount: u64) -> Result<()> {
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let debt: &mut StableDebt = stable_debt(&mut ctx.accounts.user_account, mint_key);
+    accrue_stable_debt(debt, Clock::get()?.unix_timestamp)?;
+
+    let owed: u64 = debt.owed;
+    let amount: u64 = match amount {
+        u64::MAX => amount_before_transfer_fee(&ctx.accounts.mint.to_account_info(), owed, Clock::get()?.epoch)?,
+        amount if amount > owed => return Err(ErrorCode::OverRepay.into()),
+        amount => amount,
+    };
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.user_token_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+    let decimals: u8 = ctx.accounts.mint.decimals;
+
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    let debt: &mut StableDebt = stable_debt(&mut ctx.accounts.user_account, mint_key);
+    apply_stable_payment(&mut ctx.accounts.bank, debt, received.min(owed))?;
+    ctx.accounts.user_account.liquidatable_since = 0;
+
+    Ok(())
+}
+
+/// Resets the user's stable rate to the current one, up when utilization or the supply rate has caught up with
+/// it, or down when it sits too far above the current rate.
+pub fn process_rebalance_stable_rate(ctx: Context<RebalanceStableRate>) -> Result<()> {
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    let debt: &mut StableDebt = stable_debt(&mut ctx.accounts.user_account, mint_key);
+
+    if debt.principal == 0 {
+        return Err(ErrorCode::RebalanceNotAllowed.into());
+    }
+    accrue_stable_debt(debt, Clock::get()?.unix_timestamp)?;
+
+    let current_bps: u64 = curve_rate_bps(bank)?.checked_add(bank.stable_premium_bps).ok_or(ErrorCode::MathOverflow)?;
+    let rebalance_up: bool = current_bps > debt.rate_bps
+        && (utilization_bps(bank) >= bank.stable_rebalance_utilization_bps || supply_rate_bps(bank)? >= debt.rate_bps);
+    let rebalance_down: bool = debt.rate_bps > current_bps.saturating_add(bank.stable_rebalance_down_delta_bps);
+
+    if !rebalance_up && !rebalance_down {
+        return Err(ErrorCode::RebalanceNotAllowed.into());
+    }
+
+    let average_bps: u64 = remove_from_average(bank.average_stable_rate_bps, bank.total_stable_borrowed, debt.rate_bps, debt.principal)?;
+    bank.average_stable_rate_bps = weighted_rate(average_bps, bank.total_stable_borrowed.saturating_sub(debt.principal), current_bps, debt.principal)?;
+    debt.rate_bps = current_bps;
+
+    Ok(())
+}
+
+/// `user`'s stable-rate debt on the side of `mint`.
+pub(crate) fn stable_debt(user: &mut User, mint: Pubkey) -> &mut StableDebt {
+    match mint {
+        key if key == user.usdc_address => &mut user.usdc_stable_debt,
+        _=> &mut user.sol_stable_debt,
+    }
+}
+
+/// Accrues simple interest on what `debt` owes up to `now`.
+pub(crate) fn accrue_stable_debt(debt: &mut StableDebt, now: i64) -> Result<()> {
+    let interest: u64 = simple_interest(debt.owed, debt.rate_bps, now - debt.last_updated)?;
+    debt.owed = debt.owed.checked_add(interest).ok_or(ErrorCode::MathOverflow)?;
+    debt.last_updated = now;
+
+    Ok(())
+}
+
+/// Books `amount` the treasury has received against `debt`, accrued up to now. Interest is paid off first and goes
+/// to depositors, less the insurance fund's reserve; the rest pays down principal.
+pub(crate) fn apply_stable_payment(bank: &mut Bank, debt: &mut StableDebt, amount: u64) -> Result<()> {
+    let interest_paid: u64 = amount.min(debt.owed.saturating_sub(debt.principal));
+    let principal_paid: u64 = amount - interest_paid;
+
+    bank.average_stable_rate_bps = remove_from_average(bank.average_stable_rate_bps, bank.total_stable_borrowed, debt.rate_bps, principal_paid)?;
+    bank.total_stable_borrowed = bank.total_stable_borrowed.saturating_sub(principal_paid);
+    accounting::credit_interest(bank, interest_paid)?;
+    outflow::record_inflow(bank, amount, Clock::get()?.unix_timestamp);
+
+    debt.owed = debt.owed.checked_sub(amount).ok_or(ErrorCode::OverRepay)?;
+    debt.principal = debt.principal.checked_sub(principal_paid).ok_or(ErrorCode::MathOverflow)?;
+    if debt.owed == 0 {
+        *debt = StableDebt::default();
+    }
+
+    Ok(())
+}
+
+/// Writes off what is left of `debt`. Its unpaid interest was never credited to depositors, so they only lose the
+/// principal. Returns the principal written off.
+pub(crate) fn write_off_stable(bank: &mut Bank, debt: &mut StableDebt) -> Result<u64> {
+    let principal: u64 = debt.principal;
+
+    bank.average_stable_rate_bps = remove_from_average(bank.average_stable_rate_bps, bank.total_stable_borrowed, debt.rate_bps, principal)?;
+    bank.total_stable_borrowed = bank.total_stable_borrowed.saturating_sub(principal);
+    bank.total_deposits = bank.total_deposits.saturating_sub(principal);
+    *debt = StableDebt::default();
+
+    Ok(principal)
+}
+
+/// Average of two rates weighted by the principal behind each.
+fn weighted_rate(rate_bps: u64, weight: u64, other_rate_bps: u64, other_weight: u64) -> Result<u64> {
+    let total_weight: u128 = weight as u128 + other_weight as u128;
+    if total_weight == 0 {
+        return Ok(0);
+    }
+    let weighted: u128 = rate_bps as u128 * weight as u128 + other_rate_bps as u128 * other_weight as u128;
+    u64::try_from(weighted / total_weight).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Average rate over `weight` once `removed_weight` borrowed at `removed_rate_bps` leaves it.
+fn remove_from_average(rate_bps: u64, weight: u64, removed_rate_bps: u64, removed_weight: u64) -> Result<u64> {
+    let remaining: u128 = weight.saturating_sub(removed_weight) as u128;
+    if remaining == 0 {
+        return Ok(0);
+    }
+    let weighted: u128 = (rate_bps as u128 * weight as u128).saturating_sub(removed_rate_bps as u128 * removed_weight as u128);
+    u64::try_from(weighted / remaining).map_err(|_| ErrorCode::MathOverflow.into())
+}
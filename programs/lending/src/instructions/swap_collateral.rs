@@ -0,0 +1,190 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::constants::BASIS_POINTS;
+use crate::health::{health_factor_bps, price_for};
+use crate::outflow;
+use crate::state::{Bank, EmodeCategory, LendingMarket, User};
+use crate::swap_adapter::invoke_swap;
+
+use crate::error::ErrorCode;
+
+use super::{credit_deposit, debit_withdrawal};
+
+#[derive(Accounts)]
+pub struct SwapCollateral<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    // The collateral that is withdrawn and sold.
+    pub from_mint: InterfaceAccount<'info, Mint>,
+
+    // The collateral the swap buys and that is deposited in its place.
+    #[account(
+        constraint = to_mint.key() != from_mint.key() @ ErrorCode::InvalidCollateralMint,
+    )]
+    pub to_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), from_mint.key().as_ref()],
+        bump,
+    )]
+    pub from_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), to_mint.key().as_ref()],
+        bump,
+    )]
+    pub to_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), from_mint.key().as_ref()],
+        bump,
+    )]
+    pub from_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), to_mint.key().as_ref()],
+        bump,
+    )]
+    pub to_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // The withdrawn collateral lands here for the swap to spend.
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = from_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_from_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The swap delivers the new collateral here, and it is deposited from here.
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = to_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_to_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub from_price_update: Account<'info, PriceUpdateV2>,
+    pub to_price_update: Account<'info, PriceUpdateV2>,
+
+    /// CHECK: Any swap program the user picks; `invoke_swap` only requires it to be executable and not this program.
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Withdraws `amount` of `from_mint` collateral, `u64::MAX` for all of it, sells it through `swap_program` and
+/// deposits what the swap delivered as `to_mint` collateral. The swap's accounts come in `remaining_accounts` and
+/// `swap_data` is its instruction data. Only the finished position is checked, and it must not be liquidatable.
+pub fn process_swap_collateral<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapCollateral<'info>>,
+    amount: u64,
+    swap_data: Vec<u8>,
+) -> Result<()> {
+    let from_mint_key: Pubkey = ctx.accounts.from_mint.key();
+    let to_mint_key: Pubkey = ctx.accounts.to_mint.key();
+
+    // A user holds one collateral per side, so collateral can only move between the SOL side and the USDC side.
+    let usdc_address: Pubkey = ctx.accounts.user_account.usdc_address;
+    if from_mint_key != usdc_address && to_mint_key != usdc_address {
+        return Err(ErrorCode::InvalidCollateralMint.into());
+    }
+
+    let amount: u64 = debit_withdrawal(&mut ctx.accounts.from_bank, &mut ctx.accounts.user_account, from_mint_key, amount)?;
+
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.from_bank, &ctx.accounts.user_account, Some(&*ctx.accounts.from_price_update), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.from_bank, amount, price.as_ref(), ctx.accounts.from_mint.decimals, Clock::get()?.unix_timestamp)?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.from_bank_token_account.to_account_info(),
+        to: ctx.accounts.user_from_token_account.to_account_info(),
+        authority: ctx.accounts.from_bank_token_account.to_account_info(),
+        mint: ctx.accounts.from_mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            from_mint_key.as_ref(),
+            &[ctx.bumps.from_bank_token_account],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program.clone(), transfer_cpi_accounts).with_signer(signer_seeds);
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.from_mint.decimals)?;
+
+    let to_before: u64 = ctx.accounts.user_to_token_account.amount;
+    invoke_swap(&ctx.accounts.swap_program.to_account_info(), ctx.remaining_accounts, swap_data)?;
+    ctx.accounts.user_to_token_account.reload()?;
+    let swapped: u64 = ctx.accounts.user_to_token_account.amount.checked_sub(to_before).ok_or(ErrorCode::MathOverflow)?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.user_to_token_account.to_account_info(),
+        to: ctx.accounts.to_bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.to_mint.to_account_info(),
+    };
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+    let balance_before: u64 = ctx.accounts.to_bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, swapped, ctx.accounts.to_mint.decimals)?;
+    ctx.accounts.to_bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.to_bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    credit_deposit(&mut ctx.accounts.to_bank, &mut ctx.accounts.user_account, to_mint_key, received)?;
+
+    let max_price_age: u64 = ctx.accounts.market.max_price_age;
+    let user: &User = &ctx.accounts.user_account;
+    let health_factor_bps: u64 = health_factor_bps(
+        user,
+        [&ctx.accounts.from_bank, &ctx.accounts.to_bank],
+        [&ctx.accounts.from_mint.to_account_info(), &ctx.accounts.to_mint.to_account_info()],
+        [
+            &price_for(user, from_mint_key, &ctx.accounts.from_price_update, max_price_age)?,
+            &price_for(user, to_mint_key, &ctx.accounts.to_price_update, max_price_age)?,
+        ],
+        ctx.accounts.emode_category.as_deref(),
+    )?;
+
+    if health_factor_bps < BASIS_POINTS {
+        return Err(ErrorCode::HealthFactorTooLow.into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,343 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::accounting;
+use crate::mint_extensions::amount_before_transfer_fee;
+use crate::outflow;
+use crate::rewards::accrue_rewards;
+use crate::rates::{curve_rate_bps, simple_interest};
+use crate::state::{Bank, EmodeCategory, LendingMarket, TermLoan, User};
+
+use crate::error::ErrorCode;
+
+use super::check_borrowable;
+
+#[derive(Accounts)]
+pub struct UpdateBankTermRates<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = market.owner == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct BorrowFixed<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), collateral_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // A borrower holds at most one term loan per bank.
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + TermLoan::INIT_SPACE,
+        seeds = [b"term_loan", bank.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub term_loan: Account<'info, TermLoan>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    // Values the debt, and the borrow against a bank outflow limit set in USD.
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct RepayFixed<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub market: Account<'info, LendingMarket>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        mut,
+        seeds = [b"term_loan", bank.key().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub term_loan: Account<'info, TermLoan>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn process_update_bank_term_rates(ctx: Context<UpdateBankTermRates>, premium_bps: u64, penalty_rate_bps: u64) -> Result<()> {
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    bank.term_premium_bps = premium_bps;
+    bank.term_penalty_rate_bps = penalty_rate_bps;
+
+    Ok(())
+}
+
+pub fn process_borrow_fixed(ctx: Context<BorrowFixed>, amount: u64, duration: i64) -> Result<()> {
+    if duration <= 0 {
+        return Err(ErrorCode::InvalidDuration.into());
+    }
+
+    // Isolated debt is tracked against the variable-rate borrow only.
+    if ctx.accounts.user_account.isolated_mint != Pubkey::default() {
+        return Err(ErrorCode::NotBorrowableInIsolation.into());
+    }
+
+    check_borrowable(
+        &ctx.accounts.bank,
+        &ctx.accounts.collateral_bank,
+        &ctx.accounts.user_account,
+        &ctx.accounts.market,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.collateral_mint.to_account_info(),
+        ctx.accounts.emode_category.as_deref(),
+        &ctx.accounts.price_update,
+        &ctx.accounts.borrowed_price_update,
+        amount,
+    )?;
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+    let market_key: Pubkey = ctx.accounts.market.key();
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            market_key.as_ref(),
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ]
+    ];
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+
+    let decimals: u8 = ctx.accounts.mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+    let now: i64 = Clock::get()?.unix_timestamp;
+    let bank_key: Pubkey = ctx.accounts.bank.key();
+    let bank: &mut Bank = &mut ctx.accounts.bank;
+    let term_loan: &mut TermLoan = &mut ctx.accounts.term_loan;
+    term_loan.owner = ctx.accounts.signer.key();
+    term_loan.bank = bank_key;
+    term_loan.principal = amount;
+    term_loan.owed = amount;
+    term_loan.rate_bps = fixed_rate_bps(bank)?;
+    term_loan.penalty_rate_bps = bank.term_penalty_rate_bps;
+    term_loan.maturity = now.checked_add(duration).ok_or(ErrorCode::MathOverflow)?;
+    term_loan.last_accrued = now;
+
+    bank.total_term_borrowed = bank.total_term_borrowed.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+
+    let user: &mut User = &mut ctx.accounts.user_account;
+    accrue_rewards(bank, user, now)?;
+    set_term_owed(user, bank.mint_address, amount);
+
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.bank, &ctx.accounts.user_account, Some(&*ctx.accounts.borrowed_price_update), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.bank, amount, price.as_ref(), decimals, now)
+}
+
+/// Repays `amount` of the signer's term loan, `u64::MAX` for all of it, and closes the loan once nothing is owed.
+pub fn process_repay_fixed(ctx: Context<RepayFixed>, amount: u64) -> Result<()> {
+    accrue_term_loan(&mut ctx.accounts.term_loan, Clock::get()?.unix_timestamp)?;
+
+    let owed: u64 = ctx.accounts.term_loan.owed;
+    let amount: u64 = match amount {
+        u64::MAX => amount_before_transfer_fee(&ctx.accounts.mint.to_account_info(), owed, Clock::get()?.epoch)?,
+        amount if amount > owed => return Err(ErrorCode::OverRepay.into()),
+        amount => amount,
+    };
+
+    let transfer_cpi_accounts: TransferChecked = TransferChecked {
+        from: ctx.accounts.user_token_account.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+    let decimals: u8 = ctx.accounts.mint.decimals;
+
+    let balance_before: u64 = ctx.accounts.bank_token_account.amount;
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    ctx.accounts.bank_token_account.reload()?;
+    let received: u64 = ctx.accounts.bank_token_account.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?;
+
+    apply_term_payment(&mut ctx.accounts.bank, &mut ctx.accounts.term_loan, &mut ctx.accounts.user_account, received.min(owed))?;
+    ctx.accounts.user_account.liquidatable_since = 0;
+
+    if ctx.accounts.term_loan.owed == 0 {
+        ctx.accounts.term_loan.close(ctx.accounts.signer.to_account_info())?;
+    }
+
+    Ok(())
+}
+
+/// Fixed APR, in basis points, a term loan from `bank` locks in: the curve rate plus the term premium.
+pub(crate) fn fixed_rate_bps(bank: &Bank) -> Result<u64> {
+    curve_rate_bps(bank)?.checked_add(bank.term_premium_bps).ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Accrues simple interest on what `term_loan` owes up to `now`, at its rate until maturity and with the penalty
+/// rate on top after it.
+pub(crate) fn accrue_term_loan(term_loan: &mut TermLoan, now: i64) -> Result<()> {
+    let regular_end: i64 = now.min(term_loan.maturity);
+    let overdue_start: i64 = term_loan.last_accrued.max(term_loan.maturity);
+    let penalized_rate_bps: u64 = term_loan.rate_bps.checked_add(term_loan.penalty_rate_bps).ok_or(ErrorCode::MathOverflow)?;
+
+    let interest: u64 = simple_interest(term_loan.owed, term_loan.rate_bps, regular_end - term_loan.last_accrued)?
+        .checked_add(simple_interest(term_loan.owed, penalized_rate_bps, now - overdue_start)?)
+        .ok_or(ErrorCode::MathOverflow)?;
+    term_loan.owed = term_loan.owed.checked_add(interest).ok_or(ErrorCode::MathOverflow)?;
+    term_loan.last_accrued = term_loan.last_accrued.max(now);
+
+    Ok(())
+}
+
+/// Books `amount` the treasury has received against `term_loan`, which `user` holds. Interest is paid off first and
+/// goes to the bank's depositors; the rest pays down principal.
+pub(crate) fn apply_term_payment(bank: &mut Bank, term_loan: &mut TermLoan, user: &mut User, amount: u64) -> Result<()> {
+    let interest_owed: u64 = term_loan.owed.saturating_sub(term_loan.principal);
+    let interest_paid: u64 = amount.min(interest_owed);
+    let principal_paid: u64 = amount - interest_paid;
+
+    term_loan.owed = term_loan.owed.checked_sub(amount).ok_or(ErrorCode::OverRepay)?;
+
+    term_loan.principal = term_loan.principal.checked_sub(principal_paid).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_term_borrowed = bank.total_term_borrowed.saturating_sub(principal_paid);
+    accounting::credit_interest(bank, interest_paid)?;
+    outflow::record_inflow(bank, amount, Clock::get()?.unix_timestamp);
+    set_term_owed(user, bank.mint_address, term_loan.owed);
+
+    Ok(())
+}
+
+/// Writes off what is left of `term_loan`. Depositors only lose the principal, since unpaid interest was never
+/// credited to them. The emptied loan stays open until its owner closes it with a repayment of 0. Returns the
+/// principal written off.
+pub(crate) fn write_off_term(bank: &mut Bank, term_loan: &mut TermLoan, user: &mut User) -> Result<u64> {
+    let principal: u64 = term_loan.principal;
+
+    bank.total_term_borrowed = bank.total_term_borrowed.saturating_sub(principal);
+    bank.total_deposits = bank.total_deposits.saturating_sub(principal);
+    term_loan.principal = 0;
+    term_loan.owed = 0;
+    set_term_owed(user, bank.mint_address, 0);
+
+    Ok(principal)
+}
+
+/// What `user`'s term loan from the bank of `mint` owed at its last accrual, as mirrored by `set_term_owed`.
+pub(crate) fn term_owed(user: &User, mint: Pubkey) -> u64 {
+    match mint {
+        key if key == user.usdc_address => user.usdc_term_owed,
+        _=> user.sol_term_owed,
+    }
+}
+
+/// Mirrors what `user`'s term loan from the bank of `mint` owes onto the user account, where health checks read it.
+pub(crate) fn set_term_owed(user: &mut User, mint: Pubkey, owed: u64) {
+    match mint {
+        key if key == user.usdc_address => user.usdc_term_owed = owed,
+        _=> user.sol_term_owed = owed,
+    }
+}
@@ -1,37 +1,47 @@
 use anchor_lang::prelude::*;
-use core::f32::consts::E;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::Mint, TokenAccount, TokenInterface, TransferChecked};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked}};
 use anchor_spl::token_interface;
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
 
 
-use crate::state::{Bank, User};
+use crate::accounting;
+use crate::health::check_health;
+use crate::outflow;
+use crate::rates::accrue_interest;
+use crate::rewards::accrue_rewards;
+use crate::state::{Bank, EmodeCategory, LendingMarket, User};
 
-use crate::ErrorCode;
+use crate::error::ErrorCode;
 
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    #[account(
+        constraint = !market.paused @ ErrorCode::MarketPaused,
+    )]
+    pub market: Account<'info, LendingMarket>,
+
     pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [mint.key().as_ref()],
+        seeds = [b"bank", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank: Account<'info, Bank>,
 
     #[account(
         mut,
-        seeds = [b"treasury", mint.key().as_ref()],
+        seeds = [b"treasury", market.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [signer.key().as_ref()],
+        seeds = [b"user", market.key().as_ref(), signer.key().as_ref()],
         bump,
     )]
     pub user_account: Account<'info, User>,
@@ -43,7 +53,32 @@ pub struct Withdraw<'info> {
         associated_token::authority = signer,
         associated_token::token_program = token_program,
     )]
-    pub user_token_account: InterfaceTokenAccount<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // The user's other side, which counts towards the health of the position left behind.
+    #[account(
+        constraint = other_mint.key() != mint.key() @ ErrorCode::InvalidCollateralMint,
+    )]
+    pub other_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"bank", market.key().as_ref(), other_mint.key().as_ref()],
+        bump,
+    )]
+    pub other_bank: Account<'info, Bank>,
+
+    // The user's e-mode category, left out when the user isn't in e-mode.
+    #[account(
+        seeds = [b"emode", market.key().as_ref(), &[user_account.emode_category]],
+        bump,
+    )]
+    pub emode_category: Option<Account<'info, EmodeCategory>>,
+
+    // Value the withdrawal against a bank outflow limit set in USD and the position left behind when the user has
+    // debt, and can be left out otherwise.
+    pub price_update: Option<Account<'info, PriceUpdateV2>>,
+    pub other_price_update: Option<Account<'info, PriceUpdateV2>>,
+
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -52,76 +87,81 @@ pub struct Withdraw<'info> {
 
 // LOGIC FOR INSTRUCTION
 pub fn process_withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-    // let user: &mut Account<'_. User> = &mut ctx.accounts.user_account;
-    let user: &mut User = &mut ctx.accounts.user_account;
-
-    let deposited_value: u64;
-
-    if ctx.accounts.mint.to_account_into().key() == user.usdc_address {
-        deposited_value = user.deposited_usdc;
-    } else {
-        deposited_value = user.deposited_sol;
-    }
-
-    let time_diff: u64 = user.last_updated - Clock::get()?.unix_timestamp;
-
-    let bank: &mut Bank = &mut ctx.accounts.bank;
-    bank.total_deposits = bank.total_deposits as f64 * E.powf(bank.interest_rate as f32 * time_diff as f32) as f32() as u64;
-
-    let value_per_share: f64 = bank.total_deposits as f64 / bank.total_deposit_shares as f64;
+    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let amount: u64 = debit_withdrawal(&mut ctx.accounts.bank, &mut ctx.accounts.user_account, mint_key, amount)?;
 
-    let user_value: f64 = deposited_value as f64 / value_per_share;
+    check_health(
+        &ctx.accounts.user_account,
+        [&ctx.accounts.bank, &ctx.accounts.other_bank],
+        [&ctx.accounts.mint.to_account_info(), &ctx.accounts.other_mint.to_account_info()],
+        [ctx.accounts.price_update.as_deref(), ctx.accounts.other_price_update.as_deref()],
+        ctx.accounts.emode_category.as_deref(),
+        ctx.accounts.market.max_price_age,
+    )?;
 
-    if user_value < amount as f64 {
-        return Err(ErrorCode::InsufficientFunds.into());
-    }
+    let price: Option<Price> = outflow::outflow_price(&ctx.accounts.bank, &ctx.accounts.user_account, ctx.accounts.price_update.as_deref(), ctx.accounts.market.max_price_age)?;
+    outflow::record_outflow(&mut ctx.accounts.bank, amount, price.as_ref(), ctx.accounts.mint.decimals, Clock::get()?.unix_timestamp)?;
 
-    
     let transfer_cpi_accounts = TransferChecked {
-        from: ctx.accounts.bank_token_account.to_account_into(),
-        to: ctx.accounts.user_token_account.to_account_into(),
-        authority: ctx.accounts.bank_token_account.to_account_into(),
-        mint: ctx.accounts.mint.to_account_into(),
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
     };
 
-    let cpi_program = ctx.accounts.token_program.to_account_into();
+    let cpi_program = ctx.accounts.token_program.to_account_info();
 
-    let mint_key: Pubkey = ctx.accounts.mint.key();
+    let market_key: Pubkey = ctx.accounts.market.key();
     let signer_seeds: &[&[&[u8]]] = &[
         &[
             b"treasury",
+            market_key.as_ref(),
             mint_key.as_ref(),
             &[ctx.bumps.bank_token_account],
         ]
     ];
 
-    let cpi_ctx: CpiContext = CpiContext::now(cpi_program, transfer_cpi_accounts)
+    let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_cpi_accounts)
         .with_signer(signer_seeds);
 
     let decimals: u8 = ctx.accounts.mint.decimals;
 
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
-    let bank: Bank = &mut ctx.accounts.bank;
-    let shares_to_remove: f64 = (amount as f64 / bank.total_deposits as f64) * bank.total_deposit_shares as f64;
-
-    let user: User = &mut ctx.accounts.user_account;
-
-    if ctx.accounts.mint.to_account_into().key() == user.usdc_address {
-        user.deposited_usdc -= amount;
-        user.deposited_usdc_shares -= shares_to_remove as u64;
-    } else {
-        user.deposited_sol -= amount;
-        user.dposited_sol_shares -= shares_to_remove as u64;
-    }
-
-    bank.total_deposits -= amount;
-    bank.total_deposit_shares -= shares_to_remove as u64;
-
     Ok(())
 }
 
+/// Accrues rewards and interest and books a withdrawal of `amount` of `mint` by `user`, before the tokens leave the
+/// treasury. `u64::MAX` withdraws the whole accrued balance. Returns the amount to transfer.
+pub(crate) fn debit_withdrawal(bank: &mut Bank, user: &mut User, mint: Pubkey, amount: u64) -> Result<u64> {
+    let now: i64 = Clock::get()?.unix_timestamp;
+    accrue_rewards(bank, user, now)?;
+    accrue_interest(bank, now)?;
 
+    let user_shares: u64 = if mint == user.usdc_address {
+        user.deposited_usdc_shares
+    } else {
+        user.deposited_sol_shares
+    };
 
+    let (amount, shares_to_remove): (u64, u64) = if amount == u64::MAX {
+        (accounting::withdraw_all(bank, user_shares)?, user_shares)
+    } else {
+        (amount, accounting::withdraw(bank, amount, user_shares)?)
+    };
 
+    // Principal is informational only; withdrawals can exceed it once interest has accrued.
+    if mint == user.usdc_address {
+        user.deposited_usdc_shares = user.deposited_usdc_shares.checked_sub(shares_to_remove).ok_or(ErrorCode::MathOverflow)?;
+        user.deposited_usdc = if user.deposited_usdc_shares == 0 { 0 } else { user.deposited_usdc.saturating_sub(amount) };
+    } else {
+        user.deposited_sol_shares = user.deposited_sol_shares.checked_sub(shares_to_remove).ok_or(ErrorCode::MathOverflow)?;
+        user.deposited_sol = if user.deposited_sol_shares == 0 { 0 } else { user.deposited_sol.saturating_sub(amount) };
+        // Isolation ends with the position, but the debt it backs still counts against the ceiling until repaid.
+        if user.deposited_sol_shares == 0 && user.borrowed_usdc_shares == 0 {
+            user.isolated_mint = Pubkey::default();
+        }
+    }
 
+    Ok(amount)
+}
@@ -1,18 +1,17 @@
 use anchor_lang::prelude::*;
-use instruction::*;
-
-use crate::instructions::process_init_bank;
-use crate::instructions::process_init_user;
-use crate::instructions::process_deposit;
-use crate::instructions::process_withdraw;
-use crate::instructions::process_repay;
-use crate::instructions::process_liquidate;
-use anchor_spl::token::accessor::amount;
-
-mod state;
-mod instructions;
-mod error;
-mod constants;
+use instructions::*;
+
+pub mod state;
+pub mod instructions;
+pub mod error;
+pub mod constants;
+pub mod accounting;
+pub mod mint_extensions;
+pub mod rewards;
+pub mod rates;
+pub mod health;
+pub mod outflow;
+pub mod swap_adapter;
 
 declare_id!("GzjQkAayqs4x2XfhMmbi7FmJc6PetaeG8QyxbDBbiNuy");
 
@@ -21,33 +20,205 @@ pub mod lending {
     // use std::task::Context;
     use super::*;
 
+    pub fn init_market(ctx: Context<InitMarket>, quote_currency: [u8; 32]) -> Result<()> {
+        process_init_market(ctx, quote_currency)
+    }
+
+    pub fn update_market(ctx: Context<UpdateMarket>, paused: bool, max_price_age: u64) -> Result<()> {
+        process_update_market(ctx, paused, max_price_age)
+    }
+
     pub fn init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64) -> Result<()> {
         process_init_bank(ctx, liquidation_threshold, max_ltv)
-    } 
+    }
+
+
+    pub fn update_bank_isolation(ctx: Context<UpdateBankIsolation>, isolated: bool, debt_ceiling: u64, borrowable_in_isolation: bool) -> Result<()> {
+        process_update_bank_isolation(ctx, isolated, debt_ceiling, borrowable_in_isolation)
+    }
+
+    pub fn update_bank_outflow_limit(ctx: Context<UpdateBankOutflowLimit>, window: i64, limit: u64, limit_in_usd: bool) -> Result<()> {
+        process_update_bank_outflow_limit(ctx, window, limit, limit_in_usd)
+    }
+
+    pub fn update_bank_rates(ctx: Context<UpdateBankRates>, base_rate_bps: u64, rate_slope_bps: u64) -> Result<()> {
+        process_update_bank_rates(ctx, base_rate_bps, rate_slope_bps)
+    }
+
+    pub fn init_emode_category(ctx: Context<InitEmodeCategory>, id: u8, max_ltv: u64, liquidation_threshold: u64) -> Result<()> {
+        process_init_emode_category(ctx, id, max_ltv, liquidation_threshold)
+    }
+
+    pub fn update_bank_emode(ctx: Context<UpdateBankEmode>, category: u8) -> Result<()> {
+        process_update_bank_emode(ctx, category)
+    }
 
-    
     pub fn init_user(ctx: Context<InitUser>, usdc_address: Pubkey) -> Result<()> {
         process_init_user(ctx, usdc_address)
     }
 
+    pub fn close_user(ctx: Context<CloseUser>) -> Result<()> {
+        process_close_user(ctx)
+    }
+
+    pub fn set_emode(ctx: Context<SetEmode>, category: u8) -> Result<()> {
+        process_set_emode(ctx, category)
+    }
+
     pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
         process_deposit(ctx, amount)
     }
 
+    pub fn deposit_for(ctx: Context<DepositFor>, beneficiary: Pubkey, amount: u64) -> Result<()> {
+        process_deposit_for(ctx, beneficiary, amount)
+    }
+
     pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
         process_withdraw(ctx, amount)
     }
 
+    pub fn deposit_native(ctx: Context<DepositNative>, amount: u64) -> Result<()> {
+        process_deposit_native(ctx, amount)
+    }
+
+    pub fn withdraw_native(ctx: Context<WithdrawNative>, amount: u64) -> Result<()> {
+        process_withdraw_native(ctx, amount)
+    }
+
     pub fn borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
         process_borrow(ctx, amount)
     }
 
+    pub fn approve_borrow_delegation(ctx: Context<ApproveBorrowDelegation>, amount: u64, expires_at: i64) -> Result<()> {
+        process_approve_borrow_delegation(ctx, amount, expires_at)
+    }
+
+    pub fn borrow_delegated(ctx: Context<BorrowDelegated>, amount: u64) -> Result<()> {
+        process_borrow_delegated(ctx, amount)
+    }
+
     pub fn repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
         process_repay(ctx, amount)
     }
 
+    pub fn repay_for(ctx: Context<RepayFor>, amount: u64) -> Result<()> {
+        process_repay_for(ctx, amount)
+    }
+
+    pub fn update_bank_liquidation(ctx: Context<UpdateBankLiquidation>, close_factor_bps: u64, bonus_min_bps: u64, bonus_max_bps: u64, bonus_max_health_bps: u64, bonus_ramp_seconds: i64) -> Result<()> {
+        process_update_bank_liquidation(ctx, close_factor_bps, bonus_min_bps, bonus_max_bps, bonus_max_health_bps, bonus_ramp_seconds)
+    }
+
+    pub fn flag_liquidatable(ctx: Context<FlagLiquidatable>) -> Result<()> {
+        process_flag_liquidatable(ctx)
+    }
+
     pub fn liquidate(ctx: Context<Liquidate>) -> Result<()> {
-        process_liquidate(ctx, amount)
+        process_liquidate(ctx)
+    }
+
+    pub fn update_bank_auction(ctx: Context<UpdateBankAuction>, enabled: bool, duration: i64, start_premium_bps: u64, end_discount_bps: u64) -> Result<()> {
+        process_update_bank_auction(ctx, enabled, duration, start_premium_bps, end_discount_bps)
+    }
+
+    pub fn start_auction(ctx: Context<StartAuction>) -> Result<()> {
+        process_start_auction(ctx)
+    }
+
+    pub fn bid(ctx: Context<Bid>, amount: u64) -> Result<()> {
+        process_bid(ctx, amount)
+    }
+
+    pub fn cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+        process_cancel_auction(ctx)
+    }
+
+    pub fn update_bank_term_rates(ctx: Context<UpdateBankTermRates>, premium_bps: u64, penalty_rate_bps: u64) -> Result<()> {
+        process_update_bank_term_rates(ctx, premium_bps, penalty_rate_bps)
+    }
+
+    pub fn borrow_fixed(ctx: Context<BorrowFixed>, amount: u64, duration: i64) -> Result<()> {
+        process_borrow_fixed(ctx, amount, duration)
+    }
+
+    pub fn repay_fixed(ctx: Context<RepayFixed>, amount: u64) -> Result<()> {
+        process_repay_fixed(ctx, amount)
+    }
+
+    pub fn update_bank_stable_rates(ctx: Context<UpdateBankStableRates>, premium_bps: u64, rebalance_utilization_bps: u64, rebalance_down_delta_bps: u64) -> Result<()> {
+        process_update_bank_stable_rates(ctx, premium_bps, rebalance_utilization_bps, rebalance_down_delta_bps)
+    }
+
+    pub fn borrow_stable(ctx: Context<BorrowStable>, amount: u64) -> Result<()> {
+        process_borrow_stable(ctx, amount)
+    }
+
+    pub fn repay_stable(ctx: Context<RepayStable>, amount: u64) -> Result<()> {
+        process_repay_stable(ctx, amount)
+    }
+
+    pub fn rebalance_stable_rate(ctx: Context<RebalanceStableRate>) -> Result<()> {
+        process_rebalance_stable_rate(ctx)
+    }
+
+    pub fn init_reward_stream(ctx: Context<InitRewardStream>, stream: u8, borrow_side: bool, emission_rate: u64, start: i64, end: i64) -> Result<()> {
+        process_init_reward_stream(ctx, stream, borrow_side, emission_rate, start, end)
+    }
+
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        process_fund_rewards(ctx, amount)
+    }
+
+    pub fn claim_rewards(ctx: Context<ClaimRewards>, stream: u8) -> Result<()> {
+        process_claim_rewards(ctx, stream)
+    }
+
+    pub fn update_bank_insurance(ctx: Context<UpdateBankInsurance>, reserve_factor_bps: u64, cooldown: i64, unstake_window: i64) -> Result<()> {
+        process_update_bank_insurance(ctx, reserve_factor_bps, cooldown, unstake_window)
+    }
+
+    pub fn stake_insurance(ctx: Context<StakeInsurance>, amount: u64) -> Result<()> {
+        process_stake_insurance(ctx, amount)
+    }
+
+    pub fn request_unstake(ctx: Context<RequestUnstake>, shares: u64) -> Result<()> {
+        process_request_unstake(ctx, shares)
+    }
+
+    pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
+        process_unstake(ctx)
+    }
+
+    pub fn cover_bad_debt(ctx: Context<CoverBadDebt>) -> Result<()> {
+        process_cover_bad_debt(ctx)
+    }
+
+    pub fn leverage<'info>(ctx: Context<'_, '_, 'info, 'info, Leverage<'info>>, amount: u64, min_health_factor_bps: u64, swap_data: Vec<u8>) -> Result<()> {
+        process_leverage(ctx, amount, min_health_factor_bps, swap_data)
+    }
+
+    pub fn swap_collateral<'info>(ctx: Context<'_, '_, 'info, 'info, SwapCollateral<'info>>, amount: u64, swap_data: Vec<u8>) -> Result<()> {
+        process_swap_collateral(ctx, amount, swap_data)
+    }
+
+    pub fn deleverage<'info>(ctx: Context<'_, '_, 'info, 'info, Deleverage<'info>>, amount: u64, swap_data: Vec<u8>) -> Result<()> {
+        process_deleverage(ctx, amount, swap_data)
+    }
+
+    pub fn migrate_bank(ctx: Context<MigrateBank>) -> Result<()> {
+        process_migrate_bank(ctx)
+    }
+
+    pub fn migrate_user(ctx: Context<MigrateUser>) -> Result<()> {
+        process_migrate_user(ctx)
+    }
+
+    pub fn realloc_bank(ctx: Context<ReallocBank>) -> Result<()> {
+        process_realloc_bank(ctx)
+    }
+
+    pub fn realloc_user(ctx: Context<ReallocUser>) -> Result<()> {
+        process_realloc_user(ctx)
     }
 
 }
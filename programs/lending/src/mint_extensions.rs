@@ -0,0 +1,143 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        interest_bearing_mint::InterestBearingConfig, transfer_fee::TransferFeeConfig, BaseStateWithExtensions, ExtensionType,
+        StateWithExtensions,
+    },
+};
+
+use crate::constants::{BASIS_POINTS, SECONDS_PER_YEAR};
+use crate::error::ErrorCode;
+
+// Token-2022 extensions a bank can't hold safely: a permanent delegate can move tokens out of the treasury,
+// non-transferable tokens can't be withdrawn, and confidential balances can't be measured.
+const UNSUPPORTED_EXTENSIONS: [ExtensionType; 3] = [
+    ExtensionType::PermanentDelegate,
+    ExtensionType::NonTransferable,
+    ExtensionType::ConfidentialTransferMint,
+];
+
+// 1.0 in the fixed point `interest_scale` returns, and e in it.
+pub const INTEREST_SCALE: u128 = 1_000_000_000_000_000_000;
+const E: u128 = 2_718_281_828_459_045_235;
+
+/// Fails if `mint` is a Token-2022 mint carrying an extension the program doesn't support.
+pub fn validate_mint_extensions(mint: &AccountInfo) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+
+    for extension in state.get_extension_types()? {
+        if UNSUPPORTED_EXTENSIONS.contains(&extension) {
+            msg!("Unsupported mint extension: {:?}", extension);
+            return Err(ErrorCode::UnsupportedMintExtension.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Decimals of `mint`, which can belong to either token program.
+pub fn mint_decimals(mint: &AccountInfo) -> Result<u8> {
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(state.base.decimals)
+}
+
+/// `value` times `factor` over `INTEREST_SCALE`. Only `value`'s whole part is multiplied in full, so `factor` can be
+/// up to a u64 amount without the fraction overflowing.
+fn mul_scaled(value: u128, factor: u128) -> Result<u128> {
+    let whole: u128 = (value / INTEREST_SCALE).checked_mul(factor).ok_or(ErrorCode::MathOverflow)?;
+    let fraction: u128 = (value % INTEREST_SCALE).checked_mul(factor).ok_or(ErrorCode::MathOverflow)? / INTEREST_SCALE;
+    Ok(whole.checked_add(fraction).ok_or(ErrorCode::MathOverflow)?)
+}
+
+/// e to the power of `exponent`, both in `INTEREST_SCALE` fixed point. The fractional part of the exponent goes
+/// through the Taylor series, which converges quickly below 1, and the whole part multiplies by e once per unit.
+fn exp_scaled(exponent: u128) -> Result<u128> {
+    let fraction: u128 = exponent % INTEREST_SCALE;
+    let mut result: u128 = INTEREST_SCALE;
+    let mut term: u128 = INTEREST_SCALE;
+    let mut n: u128 = 1;
+    while term != 0 {
+        term = term * fraction / INTEREST_SCALE / n;
+        result += term;
+        n += 1;
+    }
+
+    // Overflows within a few dozen rounds, well before a huge exponent could keep the loop going.
+    for _ in 0..exponent / INTEREST_SCALE {
+        result = mul_scaled(result, E)?;
+    }
+
+    Ok(result)
+}
+
+/// Factor an interest-bearing mint's raw amounts grow by to reach their UI amount, in `INTEREST_SCALE` fixed point,
+/// and `INTEREST_SCALE` for every other mint.
+pub fn interest_scale(mint: &AccountInfo, unix_timestamp: i64) -> Result<u128> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(INTEREST_SCALE);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let config: &InterestBearingConfig = match state.get_extension::<InterestBearingConfig>() {
+        Ok(config) => config,
+        Err(_) => return Ok(INTEREST_SCALE),
+    };
+
+    let average_rate: i16 = config.time_weighted_average_rate(unix_timestamp).ok_or(ErrorCode::MathOverflow)?;
+    let timespan: i64 = unix_timestamp.checked_sub(config.initialization_timestamp.into()).ok_or(ErrorCode::MathOverflow)?;
+
+    // Interest compounds continuously, so the scale is e^(rate * years).
+    let rate_seconds: i128 = average_rate as i128 * timespan as i128;
+    let exponent: u128 = rate_seconds
+        .unsigned_abs()
+        .checked_mul(INTEREST_SCALE)
+        .ok_or(ErrorCode::MathOverflow)?
+        / (BASIS_POINTS as u128 * SECONDS_PER_YEAR as u128);
+    let growth: u128 = exp_scaled(exponent)?;
+
+    match rate_seconds {
+        rate_seconds if rate_seconds < 0 => Ok(INTEREST_SCALE * INTEREST_SCALE / growth),
+        _=> Ok(growth),
+    }
+}
+
+/// Raw `amount` grown by `scale`, an `interest_scale`.
+pub fn with_interest(amount: u128, scale: u128) -> Result<u128> {
+    mul_scaled(scale, amount)
+}
+
+/// Raw amount that `amount` including interest at `scale`, an `interest_scale`, was grown from.
+pub fn without_interest(amount: u128, scale: u128) -> Result<u128> {
+    Ok(amount.checked_mul(INTEREST_SCALE).ok_or(ErrorCode::MathOverflow)? / scale)
+}
+
+/// Raw `amount` of `mint` including the interest an interest-bearing mint has accrued on it.
+pub fn amount_with_interest(mint: &AccountInfo, amount: u64, unix_timestamp: i64) -> Result<u64> {
+    let amount: u128 = with_interest(amount as u128, interest_scale(mint, unix_timestamp)?)?;
+    Ok(u64::try_from(amount).map_err(|_| ErrorCode::MathOverflow)?)
+}
+
+/// Amount that has to be sent for `amount` of `mint` to arrive after any transfer fee.
+pub fn amount_before_transfer_fee(mint: &AccountInfo, amount: u64, epoch: u64) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(amount);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let config: &TransferFeeConfig = match state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config,
+        Err(_) => return Ok(amount),
+    };
+
+    let fee: u64 = config.calculate_inverse_epoch_fee(epoch, amount).ok_or(ErrorCode::MathOverflow)?;
+    Ok(amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?)
+}
@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::constants::USD_DECIMALS;
+use crate::error::ErrorCode;
+use crate::health::price_for;
+use crate::state::{Bank, User};
+
+// Net outflows of a bank, withdrawals and borrows less deposits and repayments, over a rolling window of
+// `outflow_window` seconds. Flows land in the current window, and the previous window's total counts for the share
+// of it the rolling window still overlaps.
+
+/// Moves `bank`'s windows forward to `now`, dropping flows that have left the rolling window.
+fn roll(bank: &mut Bank, now: i64) {
+    let elapsed: i64 = now.saturating_sub(bank.outflow_window_start);
+    if elapsed >= bank.outflow_window.saturating_mul(2) {
+        bank.outflow_previous = 0;
+        bank.outflow_current = 0;
+        bank.outflow_window_start = now;
+    } else if elapsed >= bank.outflow_window {
+        bank.outflow_previous = bank.outflow_current;
+        bank.outflow_current = 0;
+        bank.outflow_window_start += bank.outflow_window;
+    }
+}
+
+fn record(bank: &mut Bank, amount: i64, now: i64) {
+    if bank.outflow_window == 0 {
+        return;
+    }
+    roll(bank, now);
+    bank.outflow_current = bank.outflow_current.saturating_add(amount);
+}
+
+/// Records `amount` leaving `bank`'s treasury and fails once the net outflow exceeds the bank's limit. Every
+/// instruction that sends tokens out of a treasury calls it; `price` and `decimals` value the outflow when the
+/// limit is in USD, see `outflow_price`.
+pub fn record_outflow(bank: &mut Bank, amount: u64, price: Option<&Price>, decimals: u8, now: i64) -> Result<()> {
+    record(bank, i64::try_from(amount).unwrap_or(i64::MAX), now);
+    check_outflow(bank, price, decimals, now)
+}
+
+/// Records `amount` entering `bank` through a deposit or a repayment.
+pub fn record_inflow(bank: &mut Bank, amount: u64, now: i64) {
+    record(bank, i64::try_from(amount).map_or(i64::MIN, |amount| -amount), now);
+}
+
+/// Net outflow over the rolling window ending at `now`, in tokens. Negative when more came in than went out.
+pub fn net_outflow(bank: &Bank, now: i64) -> i64 {
+    if bank.outflow_window == 0 {
+        return 0;
+    }
+    let elapsed: i64 = now.saturating_sub(bank.outflow_window_start);
+    if elapsed >= bank.outflow_window.saturating_mul(2) {
+        return 0;
+    }
+    let (previous, current, elapsed): (i64, i64, i64) = if elapsed >= bank.outflow_window {
+        (bank.outflow_current, 0, elapsed - bank.outflow_window)
+    } else {
+        (bank.outflow_previous, bank.outflow_current, elapsed)
+    };
+    let overlap: i128 = previous as i128 * (bank.outflow_window - elapsed) as i128 / bank.outflow_window as i128;
+    (overlap + current as i128).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Price `record_outflow` needs for `bank`, read from `price_update` when the bank's limit is in USD and `None`
+/// otherwise.
+pub fn outflow_price(bank: &Bank, user: &User, price_update: Option<&PriceUpdateV2>, max_price_age: u64) -> Result<Option<Price>> {
+    if !bank.outflow_limit_in_usd {
+        return Ok(None);
+    }
+    let price_update: &PriceUpdateV2 = price_update.ok_or(ErrorCode::PriceUpdateRequired)?;
+    Ok(Some(price_for(user, bank.mint_address, price_update, max_price_age)?))
+}
+
+/// Value of `amount` raw tokens of a mint with `decimals` decimals at `price`, in USD with `USD_DECIMALS` decimals.
+fn usd_value(amount: u64, price: &Price, decimals: u8) -> Result<u128> {
+    let value: u128 = (price.price.max(0) as u128).checked_mul(amount as u128).ok_or(ErrorCode::MathOverflow)?;
+    let scale: i32 = USD_DECIMALS as i32 + price.exponent - decimals as i32;
+    if scale >= 0 {
+        let factor: u128 = 10u128.checked_pow(scale as u32).ok_or(ErrorCode::MathOverflow)?;
+        Ok(value.checked_mul(factor).ok_or(ErrorCode::MathOverflow)?)
+    } else {
+        Ok(10u128.checked_pow(scale.unsigned_abs()).map_or(0, |divisor| value / divisor))
+    }
+}
+
+/// Fails once `bank`'s net outflow exceeds its limit. A limit in USD values the outflow at `price`, which is then
+/// required.
+fn check_outflow(bank: &Bank, price: Option<&Price>, decimals: u8, now: i64) -> Result<()> {
+    let net_outflow: i64 = net_outflow(bank, now);
+    if bank.outflow_window == 0 || net_outflow <= 0 {
+        return Ok(());
+    }
+
+    let value: u128 = if bank.outflow_limit_in_usd {
+        let price: &Price = price.ok_or(ErrorCode::PriceUpdateRequired)?;
+        usd_value(net_outflow as u64, price, decimals)?
+    } else {
+        net_outflow as u128
+    };
+
+    if value > bank.outflow_limit as u128 {
+        return Err(ErrorCode::OutflowRateLimited.into());
+    }
+
+    Ok(())
+}
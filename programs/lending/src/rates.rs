@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+
+use crate::accounting;
+use crate::constants::{BASIS_POINTS, SECONDS_PER_YEAR};
+use crate::error::ErrorCode;
+use crate::state::Bank;
+
+// Annual rates, in basis points, derived from a bank's utilization curve. Term and stable-rate loans lock in a
+// premium over the curve when they are taken out.
+
+/// Share of the bank's deposits that is lent out, across variable, stable and term debt, capped at 100%.
+pub fn utilization_bps(bank: &Bank) -> u64 {
+    if bank.total_deposits == 0 {
+        return 0;
+    }
+    let borrowed: u128 = bank.total_borrowed as u128 + bank.total_stable_borrowed as u128 + bank.total_term_borrowed as u128;
+    (borrowed * BASIS_POINTS as u128 / bank.total_deposits as u128).min(BASIS_POINTS as u128) as u64
+}
+
+/// Borrow APR at the bank's current utilization: `base_rate_bps` plus utilization times `rate_slope_bps`.
+pub fn curve_rate_bps(bank: &Bank) -> Result<u64> {
+    let slope_bps: u64 = (bank.rate_slope_bps as u128 * utilization_bps(bank) as u128 / BASIS_POINTS as u128) as u64;
+    bank.base_rate_bps.checked_add(slope_bps).ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// APR depositors earn: what variable borrowers pay at the curve rate and stable borrowers pay at their average
+/// rate, spread over every deposit.
+pub fn supply_rate_bps(bank: &Bank) -> Result<u64> {
+    if bank.total_deposits == 0 {
+        return Ok(0);
+    }
+    let variable_interest: u128 = bank.total_borrowed as u128 * curve_rate_bps(bank)? as u128;
+    let stable_interest: u128 = bank.total_stable_borrowed as u128 * bank.average_stable_rate_bps as u128;
+    u64::try_from((variable_interest + stable_interest) / bank.total_deposits as u128).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Simple interest on `amount` at `rate_bps` a year over `seconds`, rounded up.
+pub fn simple_interest(amount: u64, rate_bps: u64, seconds: i64) -> Result<u64> {
+    if seconds <= 0 {
+        return Ok(0);
+    }
+    let interest: u128 = (amount as u128 * rate_bps as u128 * seconds as u128).div_ceil(BASIS_POINTS as u128 * SECONDS_PER_YEAR as u128);
+    u64::try_from(interest).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Charges variable-rate borrowers the curve rate on `bank`'s debt since it last accrued, and credits the interest
+/// to depositors. A bank that has never accrued starts its clock at `now`.
+pub fn accrue_interest(bank: &mut Bank, now: i64) -> Result<()> {
+    let last_updated: i64 = i64::try_from(bank.last_updated).map_err(|_| ErrorCode::MathOverflow)?;
+    if last_updated != 0 && now > last_updated {
+        let interest: u64 = simple_interest(bank.total_borrowed, curve_rate_bps(bank)?, now - last_updated)?;
+        bank.total_borrowed = bank.total_borrowed.checked_add(interest).ok_or(ErrorCode::MathOverflow)?;
+        // Interest reaches depositors, less the insurance reserve, as it is charged to borrowers.
+        accounting::credit_interest(bank, interest)?;
+    }
+    bank.last_updated = bank.last_updated.max(now as u64);
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+use crate::state::{Bank, RewardPosition, RewardStream, User, UserRewards};
+
+// Liquidity mining accounting for a bank's reward streams.
+//
+// Each stream keeps a running index of rewards emitted per share of the side it rewards. Positions remember the
+// index they last caught up with, so whatever the index moved since then, times the position's shares, is what
+// the position earned. Positions have to catch up before their shares change, which is why every instruction
+// that moves shares calls `accrue_rewards` first.
+
+pub const INDEX_SCALE: u128 = 1_000_000_000_000;
+
+/// Advances `stream`'s index to `now`, spreading what it emitted since the last update over `total_shares`.
+/// Nothing is emitted while there are no shares to reward.
+pub fn update_stream(stream: &mut RewardStream, total_shares: u64, now: i64) -> Result<()> {
+    let from: i64 = stream.last_updated.max(stream.start);
+    let to: i64 = now.min(stream.end);
+
+    if to > from && total_shares != 0 {
+        let emitted: u128 = (stream.emission_rate as u128)
+            .checked_mul((to - from) as u128)
+            .and_then(|emitted| emitted.checked_mul(INDEX_SCALE))
+            .ok_or(ErrorCode::MathOverflow)?;
+        stream.index = stream.index.checked_add(emitted / total_shares as u128).ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    stream.last_updated = stream.last_updated.max(now);
+
+    Ok(())
+}
+
+/// Books what `user_shares` earned since `position` last caught up with `stream`, rounding down.
+pub fn settle_position(stream: &RewardStream, position: &mut RewardPosition, user_shares: u64) -> Result<()> {
+    let earned: u128 = stream.index
+        .checked_sub(position.index)
+        .and_then(|delta| delta.checked_mul(user_shares as u128))
+        .ok_or(ErrorCode::MathOverflow)?
+        / INDEX_SCALE;
+
+    position.earned = u64::try_from(earned)
+        .ok()
+        .and_then(|earned| position.earned.checked_add(earned))
+        .ok_or(ErrorCode::MathOverflow)?;
+    position.index = stream.index;
+
+    Ok(())
+}
+
+/// Brings every stream of `bank` and `user`'s position in it up to `now`.
+pub fn accrue_rewards(bank: &mut Bank, user: &mut User, now: i64) -> Result<()> {
+    // Term and stable-rate debt earn nothing, but still tie the side to its bank.
+    let (deposit_shares, borrow_shares, other_debt, rewards): (u64, u64, bool, &mut UserRewards) = match bank.mint_address {
+        key if key == user.usdc_address => {
+            let other_debt: bool = user.usdc_term_owed != 0 || user.usdc_stable_debt.owed != 0;
+            (user.deposited_usdc_shares, user.borrowed_usdc_shares, other_debt, &mut user.usdc_rewards)
+        }
+        _=> {
+            let other_debt: bool = user.sol_term_owed != 0 || user.sol_stable_debt.owed != 0;
+            (user.deposited_sol_shares, user.borrowed_sol_shares, other_debt, &mut user.sol_rewards)
+        }
+    };
+
+    for stream in bank.reward_streams.iter_mut().filter(|stream| stream.mint != Pubkey::default()) {
+        let total_shares: u64 = if stream.borrow_side { bank.total_borrowed_shares } else { bank.total_deposit_shares };
+        update_stream(stream, total_shares, now)?;
+    }
+
+    // A side earns from one bank at a time. It can only move to another bank once it holds nothing in the old one;
+    // accounts opened before rewards existed adopt the first bank they accrue against, and earn from then on.
+    if rewards.bank_mint != bank.mint_address {
+        let empty: bool = deposit_shares == 0 && borrow_shares == 0 && !other_debt && rewards.positions.iter().all(|position| position.earned == 0);
+        if rewards.bank_mint != Pubkey::default() && !empty {
+            return Err(ErrorCode::RewardBankMismatch.into());
+        }
+
+        rewards.bank_mint = bank.mint_address;
+        for (position, stream) in rewards.positions.iter_mut().zip(bank.reward_streams.iter()) {
+            position.index = stream.index;
+            position.earned = 0;
+        }
+    }
+
+    for (position, stream) in rewards.positions.iter_mut().zip(bank.reward_streams.iter()) {
+        let user_shares: u64 = if stream.borrow_side { borrow_shares } else { deposit_shares };
+        settle_position(stream, position, user_shares)?;
+    }
+
+    Ok(())
+}
@@ -1,9 +1,24 @@
 use anchor_lang::prelude::*;
 
+use crate::constants::MAX_REWARD_STREAMS;
+
 #[account]
 #[derive(InitSpace)]
+pub struct LendingMarket {
+    pub owner: Pubkey,
+    // Currency prices are quoted in, e.g. "USD" padded with zeroes.
+    pub quote_currency: [u8; 32],
+    // Stops deposits, withdrawals, borrows and liquidations across every bank in the market.
+    pub paused: bool,
+    // Oldest price update, in seconds, the market accepts.
+    pub max_price_age: u64,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
 pub struct User {
     pub owner: Pubkey,
+    pub market: Pubkey,
     pub deposited_sol: u64,
     pub deposited_sol_shares: u64,
     pub borrowed_sol: u64,
@@ -15,30 +30,211 @@ pub struct User {
     pub usdc_address: Pubkey,
     pub last_updated: i64,
     pub last_updated_borrow: i64,
+    // Mint of the isolated collateral the user's SOL-side deposit is in, or the default key when not isolated.
+    pub isolated_mint: Pubkey,
+    // E-mode category the user opted into with `set_emode`, 0 when none.
+    pub emode_category: u8,
+    // Liquidity mining rewards earned on the SOL-side and USDC-side positions.
+    pub sol_rewards: UserRewards,
+    pub usdc_rewards: UserRewards,
+    // Stable-rate debt on the SOL-side and USDC-side banks.
+    pub sol_stable_debt: StableDebt,
+    pub usdc_stable_debt: StableDebt,
+    // When the position was first seen liquidatable, 0 while it is healthy.
+    pub liquidatable_since: i64,
+    // What the user's term loans from the SOL-side and USDC-side banks owe as of their last accrual, mirrored from
+    // `TermLoan::owed` so health checks can count it without the loan accounts.
+    pub sol_term_owed: u64,
+    pub usdc_term_owed: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct StableDebt {
+    pub principal: u64,
+    // Principal plus interest accrued up to `last_updated`.
+    pub owed: u64,
+    // Average rate, weighted by principal, of the stable borrows that make up the debt.
+    pub rate_bps: u64,
+    pub last_updated: i64,
+}
+
+// A user's rewards from the streams of one bank, by stream slot.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct UserRewards {
+    // Mint of the bank the rewards come from, or the default key before the first accrual.
+    pub bank_mint: Pubkey,
+    pub positions: [RewardPosition; MAX_REWARD_STREAMS],
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct RewardPosition {
+    // Stream index the position last caught up with.
+    pub index: u128,
+    // Rewards earned and not yet claimed, in the stream's reward mint.
+    pub earned: u64,
+}
 
 #[account]
-#[derive(InitSpace)]
+#[derive(InitSpace, Default)]
 pub struct Bank {
     pub authority: Pubkey,
+    pub market: Pubkey,
     pub mint_address: Pubkey,
     pub total_deposits: u64,
     pub total_deposit_shares: u64,
     pub total_borrowed: u64,
     pub total_borrowed_shares: u64,
+    // Shares of a deposit's value, in basis points, that count towards the health factor and that can be borrowed
+    // against, see `max_ltv`.
     pub liquidation_threshold: u64,
-    pub liquidation_bonus: u64,
-    pub liquidation_close_factor: u64,
+    // Bonus liquidators get on the collateral they seize, see `liquidation_bonus_bps`.
+    pub liquidation_bonus_min_bps: u64,
+    // Share of the debt a single liquidation may repay.
+    pub liquidation_close_factor_bps: u64,
     pub max_ltv: u64,
     pub last_updated: u64,
     pub interest_rate: u64,
+    // Collateral from an isolated bank can't be mixed with other collateral and only backs `borrowable_in_isolation`
+    // banks, up to `debt_ceiling` tokens of outstanding `isolated_debt`.
+    pub isolated: bool,
+    pub debt_ceiling: u64,
+    pub isolated_debt: u64,
+    pub borrowable_in_isolation: bool,
+    // E-mode category the bank belongs to, 0 when none.
+    pub emode_category: u8,
+    pub reward_streams: [RewardStream; MAX_REWARD_STREAMS],
+    // Utilization curve, see `rates::curve_rate_bps`. Term loans lock in the curve rate plus `term_premium_bps`, and
+    // overdue loans pay `term_penalty_rate_bps` on top.
+    pub base_rate_bps: u64,
+    pub rate_slope_bps: u64,
+    pub term_premium_bps: u64,
+    pub term_penalty_rate_bps: u64,
+    // Principal lent out through term loans, which counts towards utilization.
+    pub total_term_borrowed: u64,
+    // Stable-rate debt principal and the average rate, weighted by principal, it was borrowed at. Stable borrows
+    // lock in the curve rate plus `stable_premium_bps`.
+    pub total_stable_borrowed: u64,
+    pub average_stable_rate_bps: u64,
+    pub stable_premium_bps: u64,
+    // A stable rate can be rebalanced up to the current one at or above this utilization, and down once it exceeds
+    // the current one by more than `stable_rebalance_down_delta_bps`.
+    pub stable_rebalance_utilization_bps: u64,
+    pub stable_rebalance_down_delta_bps: u64,
+    // The liquidation bonus rises from `liquidation_bonus_min_bps` at a health factor of 1 to
+    // `liquidation_bonus_max_bps` at `liquidation_bonus_max_health_bps`, and reaches the maximum anyway once a
+    // position has been liquidatable for `liquidation_bonus_ramp_seconds`, 0 for no time ramp.
+    pub liquidation_bonus_max_bps: u64,
+    pub liquidation_bonus_max_health_bps: u64,
+    pub liquidation_bonus_ramp_seconds: i64,
+    // Collateral from this bank is sold by `start_auction` auctions instead of seized by `liquidate`. Auctions open
+    // `auction_start_premium_bps` above the oracle price and fall to `auction_end_discount_bps` below it over
+    // `auction_duration` seconds.
+    pub auction_liquidation: bool,
+    pub auction_duration: i64,
+    pub auction_start_premium_bps: u64,
+    pub auction_end_discount_bps: u64,
+    // Share of interest payments kept back from depositors for the insurance fund, and what has been kept so far
+    // that still sits in the treasury waiting to be swept into the fund's vault.
+    pub reserve_factor_bps: u64,
+    pub insurance_fees_pending: u64,
+    // Shares of the insurance fund held by stakers, and how long they wait between `request_unstake` and `unstake`.
+    pub insurance_shares: u64,
+    pub insurance_cooldown: i64,
+    // Withdrawals and borrows fail once net outflows over the last `outflow_window` seconds exceed `outflow_limit`,
+    // in tokens or, with `outflow_limit_in_usd`, in USD with `USD_DECIMALS` decimals at the oracle price. A window of
+    // 0 turns the limit off. Flows are tracked per window from `outflow_window_start`, see `outflow`.
+    pub outflow_window: i64,
+    pub outflow_limit: u64,
+    pub outflow_limit_in_usd: bool,
+    pub outflow_window_start: i64,
+    pub outflow_previous: i64,
+    pub outflow_current: i64,
+    // Seconds after its cooldown during which a requested unstake can go through, after which it has to be
+    // requested again.
+    pub insurance_unstake_window: i64,
 }
 
+// A reward token emitted to the bank's depositors, or borrowers, at `emission_rate` tokens per second between
+// `start` and `end`. Slots with the default mint are unused.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct RewardStream {
+    pub mint: Pubkey,
+    pub borrow_side: bool,
+    pub emission_rate: u64,
+    pub start: i64,
+    pub end: i64,
+    // Rewards emitted per share so far, scaled by `rewards::INDEX_SCALE`.
+    pub index: u128,
+    pub last_updated: i64,
+}
 
+// Lets `delegate` borrow from `bank` against `owner`'s collateral, with the debt landing on the owner's `User`.
+#[account]
+#[derive(InitSpace)]
+pub struct BorrowAllowance {
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+    pub bank: Pubkey,
+    // Tokens the delegate may still borrow; each delegated borrow draws it down.
+    pub amount: u64,
+    // Unix timestamp after which the allowance can no longer be used.
+    pub expires_at: i64,
+}
 
+// A descending-price sale of a liquidatable user's collateral for the debt asset. The collateral stays in the user's
+// deposit shares until it is bid for, so whatever isn't sold remains theirs.
+#[account]
+#[derive(InitSpace)]
+pub struct Auction {
+    pub user: Pubkey,
+    // Paid the rent and gets it back when the auction closes.
+    pub starter: Pubkey,
+    pub collateral_bank: Pubkey,
+    pub borrowed_bank: Pubkey,
+    // Debt the auction may still repay, in borrowed tokens.
+    pub debt: u64,
+    // The collateral's price falls linearly from `start_price_bps` to `end_price_bps` of its oracle value at the
+    // time of each bid over `duration` seconds, and stays there.
+    pub start_price_bps: u64,
+    pub end_price_bps: u64,
+    pub start_time: i64,
+    pub duration: i64,
+}
 
+// A staker's shares of a bank's insurance fund. Shares asked to be unstaked keep bearing the fund's losses until
+// they are unstaked, which can be done from `unstake_at` on until the bank's unstake window has passed.
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceStake {
+    pub owner: Pubkey,
+    pub bank: Pubkey,
+    pub shares: u64,
+    pub unstake_shares: u64,
+    pub unstake_at: i64,
+}
 
+// Correlated banks grouped under higher risk parameters, for users who opt in with `set_emode`.
+#[account]
+#[derive(InitSpace)]
+pub struct EmodeCategory {
+    pub market: Pubkey,
+    pub id: u8,
+    // In basis points like the banks' own, and at least as high as those of every bank in the category.
+    pub max_ltv: u64,
+    pub liquidation_threshold: u64,
+}
 
-
-
+// A fixed-rate loan from `bank` that is due at `maturity`, backed by the owner's collateral like any other borrow.
+#[account]
+#[derive(InitSpace)]
+pub struct TermLoan {
+    pub owner: Pubkey,
+    pub bank: Pubkey,
+    // Principal still outstanding, and what is owed on it including accrued interest.
+    pub principal: u64,
+    pub owed: u64,
+    pub rate_bps: u64,
+    pub penalty_rate_bps: u64,
+    pub maturity: i64,
+    pub last_accrued: i64,
+}
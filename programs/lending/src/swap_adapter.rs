@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, program::invoke};
+
+use crate::error::ErrorCode;
+
+// Instructions that trade through an external swap program don't know its accounts or instruction layout. The
+// caller builds the swap instruction off-chain, and the program forwards its data and the accounts it passed in
+// `remaining_accounts` unchanged. Only the user's own signature is carried into the call, never a program PDA's,
+// so the swap can move nothing the user couldn't move themselves. What the swap delivered is measured from token
+// balances afterwards rather than trusted.
+
+/// Calls `swap_program` with `data` and `accounts`, keeping the signer and writable flags each account arrived with.
+pub fn invoke_swap<'info>(swap_program: &AccountInfo<'info>, accounts: &[AccountInfo<'info>], data: Vec<u8>) -> Result<()> {
+    if !swap_program.executable || swap_program.key() == crate::ID {
+        return Err(ErrorCode::InvalidSwapProgram.into());
+    }
+
+    let instruction: Instruction = Instruction {
+        program_id: swap_program.key(),
+        accounts: accounts
+            .iter()
+            .map(|account| AccountMeta { pubkey: account.key(), is_signer: account.is_signer, is_writable: account.is_writable })
+            .collect(),
+        data,
+    };
+
+    let mut account_infos: Vec<AccountInfo<'info>> = accounts.to_vec();
+    account_infos.push(swap_program.clone());
+    invoke(&instruction, &account_infos)?;
+
+    Ok(())
+}
@@ -0,0 +1,281 @@
+//! Property tests for the share accounting in `lending::accounting`.
+//!
+//! Random sequences of deposits, withdrawals, borrows, repayments and interest accrual are replayed
+//! against a single bank while a model tracks what each user is owed.
+
+use lending::accounting;
+use lending::state::Bank;
+use proptest::prelude::*;
+
+const USERS: usize = 4;
+const MAX_AMOUNT: u64 = 1_000_000_000_000;
+// Sequences use smaller amounts so the floating point model stays exact well below one base unit.
+const MAX_SEQUENCE_AMOUNT: u64 = 1_000_000_000;
+// Slack for the floating point model, far below one base unit of the token.
+const TOLERANCE: f64 = 1e-3;
+
+#[derive(Clone, Debug)]
+enum DepositOp {
+    Deposit { user: usize, amount: u64 },
+    // Withdraws `percent`% of what the user can currently redeem.
+    Withdraw { user: usize, percent: u64 },
+    // Grows the bank's deposits by `bps` basis points, as interest paid by borrowers would.
+    Accrue { bps: u64 },
+}
+
+#[derive(Clone, Debug)]
+enum BorrowOp {
+    Borrow { user: usize, amount: u64 },
+    // Repays `percent`% of what the user currently owes.
+    Repay { user: usize, percent: u64 },
+    Accrue { bps: u64 },
+}
+
+#[derive(Clone, Debug, Default)]
+struct Position {
+    shares: u64,
+    deposited: u64,
+    withdrawn: u64,
+    // Interest plus rounding dust left behind by other users, attributed pro rata to shares.
+    attributed: f64,
+}
+
+fn deposit_op() -> impl Strategy<Value = DepositOp> {
+    prop_oneof![
+        (0..USERS, 1..MAX_SEQUENCE_AMOUNT).prop_map(|(user, amount)| DepositOp::Deposit { user, amount }),
+        (0..USERS, 1..=100u64).prop_map(|(user, percent)| DepositOp::Withdraw { user, percent }),
+        (0..=100u64).prop_map(|bps| DepositOp::Accrue { bps }),
+    ]
+}
+
+fn borrow_op() -> impl Strategy<Value = BorrowOp> {
+    prop_oneof![
+        (0..USERS, 1..MAX_SEQUENCE_AMOUNT).prop_map(|(user, amount)| BorrowOp::Borrow { user, amount }),
+        (0..USERS, 1..=100u64).prop_map(|(user, percent)| BorrowOp::Repay { user, percent }),
+        (0..=100u64).prop_map(|bps| BorrowOp::Accrue { bps }),
+    ]
+}
+
+fn interest(total: u64, bps: u64) -> u64 {
+    (total as u128 * bps as u128 / 10_000) as u64
+}
+
+fn exact_value(bank: &Bank, shares: u64) -> f64 {
+    if bank.total_deposit_shares == 0 {
+        return 0.0;
+    }
+    shares as f64 * bank.total_deposits as f64 / bank.total_deposit_shares as f64
+}
+
+// Shares `amount` among the positions currently holding `total_shares`.
+fn attribute(positions: &mut [Position], amount: f64, total_shares: u64) {
+    if total_shares == 0 {
+        return;
+    }
+    for position in positions.iter_mut() {
+        position.attributed += amount * position.shares as f64 / total_shares as f64;
+    }
+}
+
+proptest! {
+    #[test]
+    fn deposit_shares_round_down(amount in 1..MAX_AMOUNT, total in 1..MAX_AMOUNT, total_shares in 1..MAX_AMOUNT) {
+        let shares: u64 = accounting::amount_to_shares_floor(amount, total, total_shares).unwrap();
+        let value: u64 = accounting::shares_to_amount_ceil(shares, total, total_shares).unwrap();
+        prop_assert!(value <= amount);
+    }
+
+    #[test]
+    fn debt_shares_round_up(amount in 1..MAX_AMOUNT, total in 1..MAX_AMOUNT, total_shares in 1..MAX_AMOUNT) {
+        let shares: u64 = accounting::amount_to_shares_ceil(amount, total, total_shares).unwrap();
+        let value: u64 = accounting::shares_to_amount_floor(shares, total, total_shares).unwrap();
+        prop_assert!(value >= amount);
+    }
+
+    #[test]
+    fn deposit_then_withdraw_never_profits(
+        seed in 1..MAX_AMOUNT,
+        seed_interest in 0..=10_000u64,
+        amount in 1..MAX_AMOUNT,
+    ) {
+        let mut bank: Bank = Bank::default();
+        accounting::deposit(&mut bank, seed).unwrap();
+        bank.total_deposits += interest(bank.total_deposits, seed_interest);
+
+        let shares: u64 = accounting::deposit(&mut bank, amount).unwrap();
+        let redeemable: u64 = accounting::deposit_value(&bank, shares).unwrap();
+        prop_assert!(redeemable <= amount);
+
+        let burned: u64 = accounting::withdraw(&mut bank, redeemable, shares).unwrap();
+        prop_assert!(burned <= shares);
+    }
+
+    #[test]
+    fn withdraw_all_burns_every_share_without_overpaying(
+        seed in 1..MAX_AMOUNT,
+        seed_interest in 0..=10_000u64,
+        amount in 1..MAX_AMOUNT,
+    ) {
+        let mut bank: Bank = Bank::default();
+        let seed_shares: u64 = accounting::deposit(&mut bank, seed).unwrap();
+        bank.total_deposits += interest(bank.total_deposits, seed_interest);
+
+        let shares: u64 = accounting::deposit(&mut bank, amount).unwrap();
+        let withdrawn: u64 = accounting::withdraw_all(&mut bank, shares).unwrap();
+        prop_assert!(withdrawn <= amount);
+        prop_assert_eq!(bank.total_deposit_shares, seed_shares);
+        prop_assert!(accounting::deposit_value(&bank, seed_shares).unwrap() >= seed);
+    }
+
+    #[test]
+    fn borrow_then_repay_never_undercharges(
+        seed in 1..MAX_AMOUNT,
+        seed_interest in 0..=10_000u64,
+        amount in 1..MAX_AMOUNT,
+    ) {
+        let mut bank: Bank = Bank::default();
+        accounting::borrow(&mut bank, seed).unwrap();
+        bank.total_borrowed += interest(bank.total_borrowed, seed_interest);
+
+        let shares: u64 = accounting::borrow(&mut bank, amount).unwrap();
+        let owed: u64 = accounting::borrowed_value(&bank, shares).unwrap();
+        prop_assert!(owed >= amount);
+
+        let burned: u64 = accounting::repay(&mut bank, owed, shares).unwrap();
+        prop_assert_eq!(burned, shares);
+    }
+
+    #[test]
+    fn credit_interest_splits_interest_between_depositors_and_reserve(
+        deposits in 0..MAX_AMOUNT,
+        amount in 0..MAX_AMOUNT,
+        reserve_factor_bps in 0..=10_000u64,
+    ) {
+        let mut bank: Bank = Bank { total_deposits: deposits, reserve_factor_bps, ..Bank::default() };
+        accounting::credit_interest(&mut bank, amount).unwrap();
+        prop_assert_eq!(bank.total_deposits - deposits + bank.insurance_fees_pending, amount);
+        prop_assert!(bank.insurance_fees_pending <= amount * reserve_factor_bps / 10_000);
+    }
+
+    #[test]
+    fn write_off_moves_the_debt_onto_depositors(
+        deposits in 1..MAX_AMOUNT,
+        seed in 1..MAX_AMOUNT,
+        amount in 1..MAX_AMOUNT,
+    ) {
+        let mut bank: Bank = Bank::default();
+        accounting::deposit(&mut bank, deposits).unwrap();
+        let seed_shares: u64 = accounting::borrow(&mut bank, seed).unwrap();
+        let shares: u64 = accounting::borrow(&mut bank, amount).unwrap();
+        let owed: u64 = accounting::borrowed_value(&bank, shares).unwrap();
+
+        prop_assert_eq!(accounting::write_off(&mut bank, shares).unwrap(), owed);
+        prop_assert_eq!(bank.total_borrowed_shares, seed_shares);
+        prop_assert_eq!(bank.total_deposits, deposits.saturating_sub(owed));
+        prop_assert!(accounting::borrowed_value(&bank, seed_shares).unwrap() >= seed);
+    }
+
+    #[test]
+    fn deposit_into_wiped_out_bank_fails(
+        deposits in 1..MAX_AMOUNT,
+        amount in 1..MAX_AMOUNT,
+    ) {
+        let mut bank: Bank = Bank::default();
+        accounting::deposit(&mut bank, deposits).unwrap();
+        let shares: u64 = accounting::borrow(&mut bank, deposits).unwrap();
+        accounting::write_off(&mut bank, shares).unwrap();
+
+        prop_assert!(accounting::deposit(&mut bank, amount).is_err());
+    }
+
+    #[test]
+    fn deposit_sequences_keep_the_bank_solvent(ops in prop::collection::vec(deposit_op(), 1..64)) {
+        let mut bank: Bank = Bank::default();
+        let mut positions: Vec<Position> = vec![Position::default(); USERS];
+
+        for op in ops {
+            match op {
+                DepositOp::Deposit { user, amount } => {
+                    // Anything left in the bank once every share is gone goes to the next depositor.
+                    let orphaned: f64 = if bank.total_deposit_shares == 0 { bank.total_deposits as f64 } else { 0.0 };
+                    let shares: u64 = accounting::deposit(&mut bank, amount).unwrap();
+                    let retained: f64 = amount as f64 + orphaned - exact_value(&bank, shares);
+                    prop_assert!(retained >= -TOLERANCE);
+                    attribute(&mut positions, retained, bank.total_deposit_shares - shares);
+                    positions[user].shares += shares;
+                    positions[user].deposited += amount;
+                    positions[user].attributed += orphaned;
+                }
+                DepositOp::Withdraw { user, percent } => {
+                    let redeemable: u64 = accounting::deposit_value(&bank, positions[user].shares).unwrap();
+                    let amount: u64 = redeemable * percent / 100;
+                    if amount == 0 {
+                        continue;
+                    }
+                    let before: Bank = bank.clone();
+                    let burned: u64 = accounting::withdraw(&mut bank, amount, positions[user].shares).unwrap();
+                    let retained: f64 = exact_value(&before, burned) - amount as f64;
+                    prop_assert!(retained >= -TOLERANCE);
+                    positions[user].shares -= burned;
+                    positions[user].withdrawn += amount;
+                    attribute(&mut positions, retained, bank.total_deposit_shares);
+                }
+                DepositOp::Accrue { bps } => {
+                    let accrued: u64 = interest(bank.total_deposits, bps);
+                    bank.total_deposits += accrued;
+                    attribute(&mut positions, accrued as f64, bank.total_deposit_shares);
+                }
+            }
+
+            let total_shares: u64 = positions.iter().map(|position| position.shares).sum();
+            prop_assert_eq!(total_shares, bank.total_deposit_shares);
+
+            let mut redeemable: u64 = 0;
+            for position in positions.iter() {
+                let value: u64 = accounting::deposit_value(&bank, position.shares).unwrap();
+                // A user can never get out more than they put in plus what was attributed to their shares.
+                let extracted: f64 = position.withdrawn as f64 + value as f64;
+                prop_assert!(extracted <= position.deposited as f64 + position.attributed + TOLERANCE);
+                redeemable += value;
+            }
+            prop_assert!(redeemable <= bank.total_deposits);
+        }
+    }
+
+    #[test]
+    fn borrow_sequences_never_lose_debt(ops in prop::collection::vec(borrow_op(), 1..64)) {
+        let mut bank: Bank = Bank::default();
+        let mut shares: Vec<u64> = vec![0; USERS];
+        let mut borrowed: u64 = 0;
+        let mut repaid: u64 = 0;
+
+        for op in ops {
+            match op {
+                BorrowOp::Borrow { user, amount } => {
+                    shares[user] += accounting::borrow(&mut bank, amount).unwrap();
+                    borrowed += amount;
+                }
+                BorrowOp::Repay { user, percent } => {
+                    let owed: u64 = accounting::borrowed_value(&bank, shares[user]).unwrap();
+                    let amount: u64 = owed * percent / 100;
+                    if amount == 0 {
+                        continue;
+                    }
+                    shares[user] -= accounting::repay(&mut bank, amount, shares[user]).unwrap();
+                    repaid += amount;
+                }
+                BorrowOp::Accrue { bps } => {
+                    bank.total_borrowed += interest(bank.total_borrowed, bps);
+                }
+            }
+
+            let total_shares: u64 = shares.iter().sum();
+            prop_assert_eq!(total_shares, bank.total_borrowed_shares);
+
+            // Rounding may move dust between borrowers, but never lets debt disappear from the bank.
+            let owed: u64 = shares.iter().map(|user_shares| accounting::borrowed_value(&bank, *user_shares).unwrap()).sum();
+            prop_assert!(owed >= bank.total_borrowed);
+            prop_assert!(owed + repaid >= borrowed);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+use lending::error::ErrorCode;
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+#[tokio::test]
+async fn init_bank_sets_risk_parameters() {
+    let mut env: TestEnv = TestEnv::new().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.init_bank(&mint, 80, 70).await.unwrap();
+
+    let bank = env.bank(&mint).await;
+    assert_eq!(bank.authority, env.ctx.payer.pubkey());
+    assert_eq!(bank.mint_address, mint);
+    assert_eq!(bank.liquidation_threshold, 80);
+    assert_eq!(bank.max_ltv, 70);
+    assert_eq!(bank.total_deposits, 0);
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 0);
+}
+
+#[tokio::test]
+async fn init_bank_twice_fails() {
+    let mut env: TestEnv = TestEnv::new().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.init_bank(&mint, 80, 70).await.unwrap();
+    assert!(env.init_bank(&mint, 90, 80).await.is_err());
+}
+
+#[tokio::test]
+async fn init_user_records_owner_and_usdc_mint() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+
+    let account = env.user(&user.key()).await;
+    assert_eq!(account.owner, user.key());
+    assert_eq!(account.usdc_address, env.usdc_mint.pubkey());
+    assert_eq!(account.deposited_sol, 0);
+    assert_eq!(account.borrowed_usdc, 0);
+
+    assert!(env.init_user(&user.keypair).await.is_err());
+}
+
+#[tokio::test]
+async fn close_user_refunds_rent_to_owner() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let address: Pubkey = user_address(&env.market.pubkey(), &user.key());
+
+    env.deposit(&user, &sol_mint, 100).await.unwrap();
+    env.withdraw(&user, &sol_mint, 100).await.unwrap();
+
+    let rent: u64 = env.lamports(&address).await;
+    let lamports_before: u64 = env.lamports(&user.key()).await;
+    env.close_user(&user.keypair).await.unwrap();
+
+    assert!(env.ctx.banks_client.get_account(address).await.unwrap().is_none());
+    assert_eq!(env.lamports(&user.key()).await, lamports_before + rent);
+}
+
+#[tokio::test]
+async fn close_user_with_open_position_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&user, &usdc_mint, 100).await.unwrap();
+
+    assert_error(env.close_user(&user.keypair).await, ErrorCode::OpenPosition);
+}
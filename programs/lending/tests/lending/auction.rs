@@ -0,0 +1,200 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{SOL_USB_FEED_ID, USDC_USD_FEED_ID}, error::ErrorCode, state::Auction};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+// 1_000 SOL against 100_000 USDC, with SOL auctioned from 10% above its oracle price down to 20% below over 1_000
+// seconds.
+async fn setup() -> (TestEnv, TestUser, TestUser, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_auction(&sol_mint, true, 1_000, 1_000, 2_000).await.unwrap();
+
+    let borrower: TestUser = env.create_borrower(500_000, 1_000).await;
+    let keeper: TestUser = env.create_user().await;
+    let bidder: TestUser = env.create_user().await;
+    env.borrow(&borrower, &usdc_mint, 100_000).await.unwrap();
+
+    (env, borrower, keeper, bidder)
+}
+
+#[tokio::test]
+async fn liquidate_fails_when_collateral_is_auctioned() {
+    let (mut env, borrower, keeper, _) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+
+    assert_error(env.liquidate(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::LiquidatedByAuction);
+}
+
+#[tokio::test]
+async fn start_auction_requires_auction_bank() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let keeper: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+
+    assert_error(env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::AuctionLiquidationDisabled);
+}
+
+#[tokio::test]
+async fn start_auction_on_healthy_position_fails() {
+    let (mut env, borrower, keeper, _) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::NotUnderCollaterized);
+}
+
+#[tokio::test]
+async fn bid_price_falls_over_time() {
+    let (mut env, borrower, keeper, bidder) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    // Halfway through, SOL is offered at 95% of 90.
+    env.warp_seconds(500).await;
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+    env.bid(&bidder, &keeper.key(), &borrower.key(), &sol_mint, &usdc_mint, 100).await.unwrap();
+
+    assert_eq!(env.token_balance(&bidder.sol_token_account).await, STARTING_BALANCE + 100);
+    assert_eq!(env.token_balance(&bidder.usdc_token_account).await, STARTING_BALANCE - 8_550);
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol, 900);
+    assert_eq!(user.borrowed_usdc, 100_000 - 8_550);
+
+    let auction_address: Pubkey = auction_address(&bank_address(&env.market.pubkey(), &sol_mint), &user_address(&env.market.pubkey(), &borrower.key()));
+    assert_eq!(env.account::<Auction>(&auction_address).await.debt, 100_000 - 8_550);
+}
+
+#[tokio::test]
+async fn bid_repays_debt_and_leaves_surplus_collateral() {
+    let (mut env, borrower, keeper, bidder) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 99).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    let auction_address: Pubkey = auction_address(&bank_address(&env.market.pubkey(), &sol_mint), &user_address(&env.market.pubkey(), &borrower.key()));
+    let rent: u64 = env.lamports(&auction_address).await;
+    let keeper_lamports: u64 = env.lamports(&keeper.key()).await;
+
+    // At 110% of 99, the whole debt buys 919 SOL; the other 81 stay deposited.
+    env.bid(&bidder, &keeper.key(), &borrower.key(), &sol_mint, &usdc_mint, u64::MAX).await.unwrap();
+
+    assert_eq!(env.token_balance(&bidder.sol_token_account).await, STARTING_BALANCE + 919);
+    assert_eq!(env.token_balance(&bidder.usdc_token_account).await, STARTING_BALANCE - 100_000);
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol, 81);
+    assert_eq!(user.borrowed_usdc_shares, 0);
+
+    assert!(env.ctx.banks_client.get_account(auction_address).await.unwrap().is_none());
+    assert_eq!(env.lamports(&keeper.key()).await, keeper_lamports + rent);
+}
+
+#[tokio::test]
+async fn bid_that_restores_health_closes_auction() {
+    let (mut env, borrower, keeper, bidder) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 99).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    // Each SOL sold at 110% of 99 repays more than it was worth, so 102 SOL leave 88_902 backing 88_892.
+    env.bid(&bidder, &keeper.key(), &borrower.key(), &sol_mint, &usdc_mint, 102).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol, 898);
+    assert_eq!(user.borrowed_usdc, 100_000 - 11_108);
+
+    let auction_address: Pubkey = auction_address(&bank_address(&env.market.pubkey(), &sol_mint), &user_address(&env.market.pubkey(), &borrower.key()));
+    assert!(env.ctx.banks_client.get_account(auction_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn bid_on_healthy_position_closes_auction() {
+    let (mut env, borrower, keeper, bidder) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+
+    env.bid(&bidder, &keeper.key(), &borrower.key(), &sol_mint, &usdc_mint, 100).await.unwrap();
+
+    assert_eq!(env.token_balance(&bidder.sol_token_account).await, STARTING_BALANCE);
+    assert_eq!(env.user(&borrower.key()).await.deposited_sol, 1_000);
+    let auction_address: Pubkey = auction_address(&bank_address(&env.market.pubkey(), &sol_mint), &user_address(&env.market.pubkey(), &borrower.key()));
+    assert!(env.ctx.banks_client.get_account(auction_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn stale_auction_starts_over_at_the_premium() {
+    let (mut env, borrower, keeper, bidder) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // The auction runs down to its deepest discount while the position recovers.
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    env.warp_seconds(1_000).await;
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+    env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+    env.bid(&bidder, &keeper.key(), &borrower.key(), &sol_mint, &usdc_mint, 100).await.unwrap();
+
+    // Once it is liquidatable again, SOL is offered at 110% of 90 rather than 80%.
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    env.bid(&bidder, &keeper.key(), &borrower.key(), &sol_mint, &usdc_mint, 100).await.unwrap();
+
+    assert_eq!(env.token_balance(&bidder.sol_token_account).await, STARTING_BALANCE + 100);
+    assert_eq!(env.token_balance(&bidder.usdc_token_account).await, STARTING_BALANCE - 9_900);
+}
+
+#[tokio::test]
+async fn cancel_auction_on_healthy_position_refunds_starter() {
+    let (mut env, borrower, keeper, _) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    let auction_address: Pubkey = auction_address(&bank_address(&env.market.pubkey(), &sol_mint), &user_address(&env.market.pubkey(), &borrower.key()));
+    let rent: u64 = env.lamports(&auction_address).await;
+    let keeper_lamports: u64 = env.lamports(&keeper.key()).await;
+
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+    env.cancel_auction(&keeper.key(), &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert!(env.ctx.banks_client.get_account(auction_address).await.unwrap().is_none());
+    assert_eq!(env.lamports(&keeper.key()).await, keeper_lamports + rent);
+}
+
+#[tokio::test]
+async fn cancel_auction_on_liquidatable_position_fails() {
+    let (mut env, borrower, keeper, _) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.start_auction(&keeper, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert_error(env.cancel_auction(&keeper.key(), &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::AuctionStillLiquidatable);
+}
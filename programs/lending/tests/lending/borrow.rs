@@ -0,0 +1,91 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{BASIS_POINTS, SOL_USB_FEED_ID, USDC_USD_FEED_ID}, error::ErrorCode};
+use pyth_solana_receiver_sdk::error::GetPriceError;
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+const LIQUIDITY: u64 = 100_000;
+const COLLATERAL: u64 = 10;
+
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let borrower: TestUser = env.create_borrower(LIQUIDITY, COLLATERAL).await;
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn borrow_against_collateral() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    assert_eq!(env.token_balance(&borrower.usdc_token_account).await, STARTING_BALANCE + 1_000);
+    assert_eq!(env.token_balance(&env.treasury_address(&usdc_mint)).await, LIQUIDITY - 1_000);
+
+    let account = env.user(&borrower.key()).await;
+    assert_eq!(account.borrowed_usdc, 1_000);
+    assert_eq!(account.last_updated_borrow, env.clock().await.unix_timestamp);
+}
+
+#[tokio::test]
+async fn borrow_over_collateral_value_fails() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // The debt is valued at its own price, so at 3 USD a USDC a third as much can be borrowed.
+    let usdc_price: i64 = 3;
+    env.set_price(USDC_USD_FEED_ID, usdc_price).await;
+    let borrowable: u64 = COLLATERAL * SOL_PRICE as u64 * MAX_LTV / BASIS_POINTS / usdc_price as u64;
+
+    assert_error(env.borrow(&borrower, &usdc_mint, borrowable + 1).await, ErrorCode::OverBorrowableAmount);
+    env.borrow(&borrower, &usdc_mint, borrowable).await.unwrap();
+}
+
+#[tokio::test]
+async fn borrow_follows_collateral_price() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE / 2).await;
+
+    let borrowable: u64 = COLLATERAL * (SOL_PRICE / 2) as u64 * MAX_LTV / BASIS_POINTS;
+    assert_error(env.borrow(&borrower, &usdc_mint, borrowable + 1).await, ErrorCode::OverBorrowableAmount);
+}
+
+#[tokio::test]
+async fn borrow_values_each_side_in_usd() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let borrower: TestUser = env.create_borrower(200_000, 1_000_000).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // Both feeds with 8 price decimals: 1_000_000 raw units of 9-decimal SOL at 150 are worth $0.15, which is
+    // 150_000 raw units of 6-decimal USDC at 1.
+    env.set_price_with_exponent(SOL_USB_FEED_ID, SOL_PRICE * 100_000_000, -8).await;
+    env.set_price_with_exponent(USDC_USD_FEED_ID, USDC_PRICE * 100_000_000, -8).await;
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 150_001).await, ErrorCode::OverBorrowableAmount);
+    env.borrow(&borrower, &usdc_mint, 150_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn borrow_at_non_positive_price_fails() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, -SOL_PRICE).await;
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 1).await, ErrorCode::InvalidPrice);
+}
+
+#[tokio::test]
+async fn borrow_with_stale_price_fails() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.warp_seconds(lending::constants::MAX_AGE as i64 + 1).await;
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 1).await, GetPriceError::PriceTooOld);
+}
@@ -0,0 +1,1513 @@
+use anchor_lang::{prelude::*, AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas};
+use anchor_lang::solana_program::{entrypoint::ProgramResult, instruction::Instruction, system_instruction, sysvar::clock::Clock};
+use anchor_spl::{associated_token::{self, get_associated_token_address_with_program_id, spl_associated_token_account}, token::spl_token};
+use anchor_spl::token_2022::spl_token_2022::{self, extension::{interest_bearing_mint, transfer_fee, ExtensionType, StateWithExtensions}};
+use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceFeedMessage, PriceUpdateV2, VerificationLevel};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{account::Account as SolanaAccount, instruction::InstructionError, signature::Keypair, signer::Signer, transaction::{Transaction, TransactionError}};
+
+use lending::{constants::{SOL_USB_FEED_ID, USDC_USD_FEED_ID}, state::{Bank, TermLoan, User}};
+
+pub const SOL_DECIMALS: u8 = 9;
+pub const USDC_DECIMALS: u8 = 6;
+
+// Prices are written with exponents that cancel out the mints' decimals, so a raw unit of SOL is worth `SOL_PRICE` raw
+// units of USDC and amounts in tests stay readable.
+pub const SOL_PRICE: i64 = 150;
+pub const USDC_PRICE: i64 = 1;
+
+// Collateral backs its full value, so the health factor is just collateral value over debt.
+pub const LIQUIDATION_THRESHOLD: u64 = 10_000;
+pub const MAX_LTV: u64 = 10_000;
+
+pub const STARTING_BALANCE: u64 = 1_000_000;
+
+pub const QUOTE_CURRENCY: [u8; 32] = {
+    let mut currency: [u8; 32] = [0; 32];
+    currency[0] = b'U';
+    currency[1] = b'S';
+    currency[2] = b'D';
+    currency
+};
+
+// anchor's entrypoint wants the accounts slice to live as long as the account infos inside it.
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    lending::entry(program_id, accounts, data)
+}
+
+fn process_mock_amm_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    mock_amm::entry(program_id, accounts, data)
+}
+
+/// Token-2022 mint extensions a test can ask for.
+#[derive(Clone, Copy)]
+pub enum MintExtension {
+    TransferFee { basis_points: u16, maximum_fee: u64 },
+    InterestBearing { rate: i16 },
+    PermanentDelegate,
+    NonTransferable,
+}
+
+impl MintExtension {
+    fn extension_type(self) -> ExtensionType {
+        match self {
+            MintExtension::TransferFee { .. } => ExtensionType::TransferFeeConfig,
+            MintExtension::InterestBearing { .. } => ExtensionType::InterestBearingConfig,
+            MintExtension::PermanentDelegate => ExtensionType::PermanentDelegate,
+            MintExtension::NonTransferable => ExtensionType::NonTransferable,
+        }
+    }
+
+    fn initialize(self, mint: &Pubkey, authority: &Pubkey) -> Instruction {
+        match self {
+            MintExtension::TransferFee { basis_points, maximum_fee } => transfer_fee::instruction::initialize_transfer_fee_config(
+                &spl_token_2022::ID, mint, Some(authority), Some(authority), basis_points, maximum_fee,
+            ).unwrap(),
+            MintExtension::InterestBearing { rate } => interest_bearing_mint::instruction::initialize(&spl_token_2022::ID, mint, Some(*authority), rate).unwrap(),
+            MintExtension::PermanentDelegate => spl_token_2022::instruction::initialize_permanent_delegate(&spl_token_2022::ID, mint, authority).unwrap(),
+            MintExtension::NonTransferable => spl_token_2022::instruction::initialize_non_transferable_mint(&spl_token_2022::ID, mint).unwrap(),
+        }
+    }
+}
+
+pub struct TestEnv {
+    pub ctx: ProgramTestContext,
+    pub token_program: Pubkey,
+    pub market: Keypair,
+    pub sol_mint: Keypair,
+    pub usdc_mint: Keypair,
+    pub sol_price_update: Pubkey,
+    pub usdc_price_update: Pubkey,
+}
+
+pub struct TestUser {
+    pub keypair: Keypair,
+    pub sol_token_account: Pubkey,
+    pub usdc_token_account: Pubkey,
+}
+
+impl TestUser {
+    pub fn key(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+}
+
+pub fn bank_address(market: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"bank", market.as_ref(), mint.as_ref()], &lending::ID).0
+}
+
+pub fn treasury_address(market: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury", market.as_ref(), mint.as_ref()], &lending::ID).0
+}
+
+pub fn user_address(market: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"user", market.as_ref(), owner.as_ref()], &lending::ID).0
+}
+
+/// Temporary wSOL account the native deposit and withdraw instructions wrap lamports in.
+pub fn wrapped_sol_address(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"wrap", owner.as_ref()], &lending::ID).0
+}
+
+pub fn allowance_address(owner: &Pubkey, delegate: &Pubkey, bank: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"allowance", owner.as_ref(), delegate.as_ref(), bank.as_ref()], &lending::ID).0
+}
+
+pub fn emode_address(market: &Pubkey, id: u8) -> Pubkey {
+    Pubkey::find_program_address(&[b"emode", market.as_ref(), &[id]], &lending::ID).0
+}
+
+pub fn reward_vault_address(bank: &Pubkey, reward_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"reward_vault", bank.as_ref(), reward_mint.as_ref()], &lending::ID).0
+}
+
+pub fn term_loan_address(bank: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"term_loan", bank.as_ref(), owner.as_ref()], &lending::ID).0
+}
+
+pub fn auction_address(collateral_bank: &Pubkey, user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"auction", collateral_bank.as_ref(), user.as_ref()], &lending::ID).0
+}
+
+pub fn insurance_vault_address(bank: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"insurance", bank.as_ref()], &lending::ID).0
+}
+
+pub fn insurance_stake_address(bank: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"insurance_stake", bank.as_ref(), owner.as_ref()], &lending::ID).0
+}
+
+/// Address banks had before markets existed.
+pub fn legacy_bank_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[mint.as_ref()], &lending::ID).0
+}
+
+/// Address treasuries had before markets existed.
+pub fn legacy_treasury_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury", mint.as_ref()], &lending::ID).0
+}
+
+/// Address users had before markets existed.
+pub fn legacy_user_address(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref()], &lending::ID).0
+}
+
+pub fn token_account_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+/// Asserts that a transaction failed with the given custom program error.
+pub fn assert_error(result: std::result::Result<(), BanksClientError>, expected: impl Into<u32>) {
+    let expected: u32 = expected.into();
+    match result.expect_err("transaction should have failed").unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => assert_eq!(code, expected),
+        err => panic!("expected custom error {expected}, got {err:?}"),
+    }
+}
+
+impl TestEnv {
+    /// Starts the program in-process with a market, a SOL-like and a USDC-like mint and fresh price updates for both.
+    pub async fn new() -> Self {
+        TestEnv::with_token_program(spl_token::ID, &[]).await
+    }
+
+    /// Like `new`, but both mints belong to `token_program` and the SOL-like mint carries `sol_extensions`.
+    pub async fn with_token_program(token_program: Pubkey, sol_extensions: &[MintExtension]) -> Self {
+        let mut program_test: ProgramTest = ProgramTest::new("lending", lending::ID, processor!(process_instruction));
+        program_test.add_program("mock_amm", mock_amm::ID, processor!(process_mock_amm_instruction));
+        let ctx: ProgramTestContext = program_test.start_with_context().await;
+
+        let mut env = TestEnv {
+            ctx,
+            token_program,
+            market: Keypair::new(),
+            sol_mint: Keypair::new(),
+            usdc_mint: Keypair::new(),
+            sol_price_update: Pubkey::new_unique(),
+            usdc_price_update: Pubkey::new_unique(),
+        };
+
+        let sol_mint: Keypair = env.sol_mint.insecure_clone();
+        let usdc_mint: Keypair = env.usdc_mint.insecure_clone();
+        env.create_mint(&sol_mint, SOL_DECIMALS, sol_extensions).await;
+        env.create_mint(&usdc_mint, USDC_DECIMALS, &[]).await;
+        env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+        env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+
+        let market: Keypair = env.market.insecure_clone();
+        env.init_market(&market).await.unwrap();
+
+        env
+    }
+
+    /// Starts the environment and creates both banks.
+    pub async fn with_banks() -> Self {
+        TestEnv::with_risk_parameters(LIQUIDATION_THRESHOLD, MAX_LTV).await
+    }
+
+    /// Like `with_banks`, with both banks at `liquidation_threshold` and `max_ltv`.
+    pub async fn with_risk_parameters(liquidation_threshold: u64, max_ltv: u64) -> Self {
+        let mut env: TestEnv = TestEnv::new().await;
+        let sol_mint: Pubkey = env.sol_mint.pubkey();
+        let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+        env.init_bank(&sol_mint, liquidation_threshold, max_ltv).await.unwrap();
+        env.init_bank(&usdc_mint, liquidation_threshold, max_ltv).await.unwrap();
+        env
+    }
+
+    pub fn treasury_address(&self, mint: &Pubkey) -> Pubkey {
+        treasury_address(&self.market.pubkey(), mint)
+    }
+
+    pub fn token_account_address(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        token_account_address(owner, mint, &self.token_program)
+    }
+
+    pub fn payer(&self) -> Keypair {
+        self.ctx.payer.insecure_clone()
+    }
+
+    pub async fn process(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> std::result::Result<(), BanksClientError> {
+        let payer: Keypair = self.payer();
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers: Vec<&Keypair> = vec![&payer];
+        all_signers.extend_from_slice(signers);
+        let transaction: Transaction = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &all_signers, blockhash);
+        self.ctx.banks_client.process_transaction(transaction).await
+    }
+
+    pub async fn create_mint(&mut self, mint: &Keypair, decimals: u8, extensions: &[MintExtension]) {
+        let payer: Pubkey = self.ctx.payer.pubkey();
+        let rent: Rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let extension_types: Vec<ExtensionType> = extensions.iter().map(|extension| extension.extension_type()).collect();
+        let space: usize = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&extension_types).unwrap();
+
+        let mut instructions: Vec<Instruction> = vec![
+            system_instruction::create_account(&payer, &mint.pubkey(), rent.minimum_balance(space), space as u64, &self.token_program),
+        ];
+        instructions.extend(extensions.iter().map(|extension| extension.initialize(&mint.pubkey(), &payer)));
+        instructions.push(spl_token_2022::instruction::initialize_mint2(&self.token_program, &mint.pubkey(), &payer, None, decimals).unwrap());
+        self.process(&instructions, &[mint]).await.unwrap();
+    }
+
+    /// Funds a new wallet, gives it token accounts for both mints and creates its `User` account.
+    pub async fn create_user(&mut self) -> TestUser {
+        let keypair: Keypair = Keypair::new();
+        let payer: Pubkey = self.ctx.payer.pubkey();
+        let sol_mint: Pubkey = self.sol_mint.pubkey();
+        let usdc_mint: Pubkey = self.usdc_mint.pubkey();
+
+        let user = TestUser {
+            sol_token_account: self.token_account_address(&keypair.pubkey(), &sol_mint),
+            usdc_token_account: self.token_account_address(&keypair.pubkey(), &usdc_mint),
+            keypair,
+        };
+
+        let instructions: Vec<Instruction> = vec![
+            system_instruction::transfer(&payer, &user.key(), 10_000_000_000),
+            spl_associated_token_account::instruction::create_associated_token_account(&payer, &user.key(), &sol_mint, &self.token_program),
+            spl_associated_token_account::instruction::create_associated_token_account(&payer, &user.key(), &usdc_mint, &self.token_program),
+            spl_token_2022::instruction::mint_to(&self.token_program, &sol_mint, &user.sol_token_account, &payer, &[], STARTING_BALANCE).unwrap(),
+            spl_token_2022::instruction::mint_to(&self.token_program, &usdc_mint, &user.usdc_token_account, &payer, &[], STARTING_BALANCE).unwrap(),
+        ];
+        self.process(&instructions, &[]).await.unwrap();
+        self.init_user(&user.keypair).await.unwrap();
+
+        user
+    }
+
+    /// Has a new lender deposit `liquidity` USDC and a new borrower deposit `collateral` SOL, and returns the
+    /// borrower.
+    pub async fn create_borrower(&mut self, liquidity: u64, collateral: u64) -> TestUser {
+        let lender: TestUser = self.create_user().await;
+        let borrower: TestUser = self.create_user().await;
+        let sol_mint: Pubkey = self.sol_mint.pubkey();
+        let usdc_mint: Pubkey = self.usdc_mint.pubkey();
+
+        self.deposit(&lender, &usdc_mint, liquidity).await.unwrap();
+        self.deposit(&borrower, &sol_mint, collateral).await.unwrap();
+
+        borrower
+    }
+
+    /// Creates `owner`'s token account for `mint` and mints `amount` into it.
+    pub async fn fund(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        let payer: Pubkey = self.ctx.payer.pubkey();
+        let instructions: [Instruction; 2] = [
+            spl_associated_token_account::instruction::create_associated_token_account(&payer, owner, mint, &self.token_program),
+            spl_token_2022::instruction::mint_to(&self.token_program, mint, &self.token_account_address(owner, mint), &payer, &[], amount).unwrap(),
+        ];
+        self.process(&instructions, &[]).await.unwrap();
+    }
+
+    /// Writes a fully verified `PriceUpdateV2` for `feed` that was published at the current clock time, with the
+    /// exponent that cancels out the decimals of its mint.
+    pub async fn set_price(&mut self, feed: &str, price: i64) {
+        let exponent: i32 = if feed == SOL_USB_FEED_ID { (SOL_DECIMALS - USDC_DECIMALS) as i32 } else { 0 };
+        self.set_price_with_exponent(feed, price, exponent).await;
+    }
+
+    /// Like `set_price`, for a price of `price * 10^exponent`.
+    pub async fn set_price_with_exponent(&mut self, feed: &str, price: i64, exponent: i32) {
+        let address: Pubkey = if feed == SOL_USB_FEED_ID { self.sol_price_update } else { self.usdc_price_update };
+        let publish_time: i64 = self.clock().await.unix_timestamp;
+
+        let price_update = PriceUpdateV2 {
+            write_authority: Pubkey::default(),
+            verification_level: VerificationLevel::Full,
+            price_message: PriceFeedMessage {
+                feed_id: get_feed_id_from_hex(feed).unwrap(),
+                price,
+                conf: 0,
+                exponent,
+                publish_time,
+                prev_publish_time: publish_time,
+                ema_price: price,
+                ema_conf: 0,
+            },
+            posted_slot: 0,
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        price_update.try_serialize(&mut data).unwrap();
+
+        let account = SolanaAccount {
+            lamports: 1_000_000_000,
+            data,
+            owner: pyth_solana_receiver_sdk::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.ctx.set_account(&address, &account.into());
+    }
+
+    pub async fn clock(&mut self) -> Clock {
+        self.ctx.banks_client.get_sysvar::<Clock>().await.unwrap()
+    }
+
+    /// Moves the clock's unix timestamp forward without touching the slot.
+    pub async fn warp_seconds(&mut self, seconds: i64) {
+        let mut clock: Clock = self.clock().await;
+        clock.unix_timestamp += seconds;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    pub async fn token_balance(&mut self, address: &Pubkey) -> u64 {
+        let account: SolanaAccount = self.ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data).unwrap().base.amount
+    }
+
+    pub async fn lamports(&mut self, address: &Pubkey) -> u64 {
+        self.ctx.banks_client.get_balance(*address).await.unwrap()
+    }
+
+    pub async fn account<T: AccountDeserialize>(&mut self, address: &Pubkey) -> T {
+        let account: SolanaAccount = self.ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn bank(&mut self, mint: &Pubkey) -> Bank {
+        self.account(&bank_address(&self.market.pubkey(), mint)).await
+    }
+
+    pub async fn user(&mut self, owner: &Pubkey) -> User {
+        self.account(&user_address(&self.market.pubkey(), owner)).await
+    }
+
+    /// Bank of the isolated collateral `owner` borrows against, if any.
+    pub async fn isolated_bank_for(&mut self, owner: &Pubkey) -> Option<Pubkey> {
+        let isolated_mint: Pubkey = self.user(owner).await.isolated_mint;
+        (isolated_mint != Pubkey::default()).then(|| bank_address(&self.market.pubkey(), &isolated_mint))
+    }
+
+    pub async fn emode_for(&mut self, owner: &Pubkey) -> Option<Pubkey> {
+        let category: u8 = self.user(owner).await.emode_category;
+        (category != 0).then(|| emode_address(&self.market.pubkey(), category))
+    }
+
+    /// Price update holding the feed the program reads for `mint`, the SOL feed for everything but USDC.
+    pub fn price_update_for(&self, mint: &Pubkey) -> Pubkey {
+        if *mint == self.usdc_mint.pubkey() { self.usdc_price_update } else { self.sol_price_update }
+    }
+
+    /// Mint of `owner`'s other side from `mint`: USDC, or the bank the SOL side is in.
+    pub async fn other_side_mint(&mut self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        if *mint != self.usdc_mint.pubkey() {
+            return self.usdc_mint.pubkey();
+        }
+        let sol_side_mint: Pubkey = self.user(owner).await.sol_rewards.bank_mint;
+        if sol_side_mint == Pubkey::default() { self.sol_mint.pubkey() } else { sol_side_mint }
+    }
+
+    pub async fn init_market(&mut self, market: &Keypair) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::InitMarket {
+                signer: self.ctx.payer.pubkey(),
+                market: market.pubkey(),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::InitMarket { quote_currency: QUOTE_CURRENCY }.data(),
+        };
+        self.process(&[instruction], &[market]).await
+    }
+
+    pub async fn update_market(&mut self, signer: &Keypair, paused: bool, max_price_age: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateMarket {
+                signer: signer.pubkey(),
+                market: self.market.pubkey(),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateMarket { paused, max_price_age }.data(),
+        };
+        self.process(&[instruction], &[signer]).await
+    }
+
+    pub async fn init_bank(&mut self, mint: &Pubkey, liquidation_threshold: u64, max_ltv: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::InitBank {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::InitBank { liquidation_threshold, max_ltv }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn update_bank_isolation(&mut self, mint: &Pubkey, isolated: bool, debt_ceiling: u64, borrowable_in_isolation: bool) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankIsolation {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankIsolation { isolated, debt_ceiling, borrowable_in_isolation }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn init_emode_category(&mut self, id: u8, max_ltv: u64, liquidation_threshold: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::InitEmodeCategory {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                emode_category: emode_address(&self.market.pubkey(), id),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::InitEmodeCategory { id, max_ltv, liquidation_threshold }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn update_bank_emode(&mut self, mint: &Pubkey, category: u8) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankEmode {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                emode_category: (category != 0).then(|| emode_address(&self.market.pubkey(), category)),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankEmode { category }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    /// Opts `user` into `category`, passing the banks of `position_mints` for validation.
+    pub async fn set_emode(&mut self, user: &TestUser, category: u8, position_mints: &[Pubkey]) -> std::result::Result<(), BanksClientError> {
+        let sol_mint: Pubkey = self.sol_mint.pubkey();
+        let usdc_mint: Pubkey = self.usdc_mint.pubkey();
+        let mut accounts: Vec<AccountMeta> = lending::accounts::SetEmode {
+            signer: user.key(),
+            market: self.market.pubkey(),
+            user_account: user_address(&self.market.pubkey(), &user.key()),
+            emode_category: (category != 0).then(|| emode_address(&self.market.pubkey(), category)),
+            sol_mint,
+            sol_bank: bank_address(&self.market.pubkey(), &sol_mint),
+            usdc_mint,
+            usdc_bank: bank_address(&self.market.pubkey(), &usdc_mint),
+            sol_price_update: Some(self.sol_price_update),
+            usdc_price_update: Some(self.usdc_price_update),
+        }.to_account_metas(None);
+        accounts.extend(position_mints.iter().map(|mint| AccountMeta::new_readonly(bank_address(&self.market.pubkey(), mint), false)));
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts,
+            data: lending::instruction::SetEmode { category }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn init_user(&mut self, owner: &Keypair) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::InitUser {
+                signer: owner.pubkey(),
+                market: self.market.pubkey(),
+                user_account: user_address(&self.market.pubkey(), &owner.pubkey()),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::InitUser { usdc_address: self.usdc_mint.pubkey() }.data(),
+        };
+        self.process(&[instruction], &[owner]).await
+    }
+
+    pub async fn close_user(&mut self, owner: &Keypair) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::CloseUser {
+                signer: owner.pubkey(),
+                market: self.market.pubkey(),
+                user_account: user_address(&self.market.pubkey(), &owner.pubkey()),
+            }.to_account_metas(None),
+            data: lending::instruction::CloseUser {}.data(),
+        };
+        self.process(&[instruction], &[owner]).await
+    }
+
+    pub async fn deposit(&mut self, user: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::Deposit {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                user_token_account: self.token_account_address(&user.key(), mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::Deposit { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    /// Deposits `payer`'s tokens into `beneficiary`'s position.
+    pub async fn deposit_for(&mut self, payer: &TestUser, beneficiary: &Pubkey, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::DepositFor {
+                signer: payer.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), beneficiary),
+                signer_token_account: self.token_account_address(&payer.key(), mint),
+                token_program: self.token_program,
+            }.to_account_metas(None),
+            data: lending::instruction::DepositFor { beneficiary: *beneficiary, amount }.data(),
+        };
+        self.process(&[instruction], &[&payer.keypair]).await
+    }
+
+    pub async fn withdraw(&mut self, user: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let other_mint: Pubkey = self.other_side_mint(&user.key(), mint).await;
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::Withdraw {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                user_token_account: self.token_account_address(&user.key(), mint),
+                other_mint,
+                other_bank: bank_address(&self.market.pubkey(), &other_mint),
+                emode_category,
+                price_update: Some(self.price_update_for(mint)),
+                other_price_update: Some(self.price_update_for(&other_mint)),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::Withdraw { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn deposit_native(&mut self, user: &TestUser, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::DepositNative {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: spl_token::native_mint::ID,
+                bank: bank_address(&self.market.pubkey(), &spl_token::native_mint::ID),
+                bank_token_account: treasury_address(&self.market.pubkey(), &spl_token::native_mint::ID),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                wrapped_sol_account: wrapped_sol_address(&user.key()),
+                token_program: spl_token::ID,
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::DepositNative { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn withdraw_native(&mut self, user: &TestUser, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let usdc_mint: Pubkey = self.usdc_mint.pubkey();
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::WithdrawNative {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: spl_token::native_mint::ID,
+                bank: bank_address(&self.market.pubkey(), &spl_token::native_mint::ID),
+                bank_token_account: treasury_address(&self.market.pubkey(), &spl_token::native_mint::ID),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                wrapped_sol_account: wrapped_sol_address(&user.key()),
+                usdc_mint,
+                usdc_bank: bank_address(&self.market.pubkey(), &usdc_mint),
+                emode_category,
+                price_update: Some(self.sol_price_update),
+                usdc_price_update: Some(self.usdc_price_update),
+                token_program: spl_token::ID,
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::WithdrawNative { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    /// Borrows `mint` against the other asset, so the price update is the collateral's.
+    pub async fn borrow(&mut self, user: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let collateral_mint: Pubkey = if *mint == self.usdc_mint.pubkey() { self.sol_mint.pubkey() } else { self.usdc_mint.pubkey() };
+        let collateral_price_update: Pubkey = self.price_update_for(&collateral_mint);
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::Borrow {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                collateral_mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                collateral_bank: bank_address(&self.market.pubkey(), &collateral_mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                emode_category,
+                user_token_account: self.token_account_address(&user.key(), mint),
+                price_update: collateral_price_update,
+                borrowed_price_update: self.price_update_for(mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::Borrow { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn approve_borrow_delegation(&mut self, owner: &TestUser, delegate: &Pubkey, mint: &Pubkey, amount: u64, expires_at: i64) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::ApproveBorrowDelegation {
+                signer: owner.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                delegate: *delegate,
+                borrow_allowance: allowance_address(&owner.key(), delegate, &bank),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::ApproveBorrowDelegation { amount, expires_at }.data(),
+        };
+        self.process(&[instruction], &[&owner.keypair]).await
+    }
+
+    /// Borrows `mint` onto `owner`'s account with `delegate` receiving the tokens.
+    pub async fn borrow_delegated(&mut self, delegate: &TestUser, owner: &Pubkey, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let collateral_mint: Pubkey = if *mint == self.usdc_mint.pubkey() { self.sol_mint.pubkey() } else { self.usdc_mint.pubkey() };
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let emode_category: Option<Pubkey> = self.emode_for(owner).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::BorrowDelegated {
+                signer: delegate.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                collateral_mint,
+                bank,
+                collateral_bank: bank_address(&self.market.pubkey(), &collateral_mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), owner),
+                emode_category,
+                borrow_allowance: allowance_address(owner, &delegate.key(), &bank),
+                signer_token_account: self.token_account_address(&delegate.key(), mint),
+                price_update: self.price_update_for(&collateral_mint),
+                borrowed_price_update: self.price_update_for(mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::BorrowDelegated { amount }.data(),
+        };
+        self.process(&[instruction], &[&delegate.keypair]).await
+    }
+
+    pub async fn repay(&mut self, user: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let isolated_bank: Option<Pubkey> = self.isolated_bank_for(&user.key()).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::Repay {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                isolated_bank,
+                user_token_account: self.token_account_address(&user.key(), mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::Repay { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    /// Repays `borrower`'s debt out of `payer`'s own token account.
+    pub async fn repay_for(&mut self, payer: &TestUser, borrower: &Pubkey, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let isolated_bank: Option<Pubkey> = self.isolated_bank_for(borrower).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::RepayFor {
+                signer: payer.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), borrower),
+                isolated_bank,
+                signer_token_account: self.token_account_address(&payer.key(), mint),
+                token_program: self.token_program,
+            }.to_account_metas(None),
+            data: lending::instruction::RepayFor { amount }.data(),
+        };
+        self.process(&[instruction], &[&payer.keypair]).await
+    }
+
+    pub async fn update_bank_liquidation(
+        &mut self,
+        mint: &Pubkey,
+        close_factor_bps: u64,
+        bonus_min_bps: u64,
+        bonus_max_bps: u64,
+        bonus_max_health_bps: u64,
+        bonus_ramp_seconds: i64,
+    ) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankLiquidation {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankLiquidation { close_factor_bps, bonus_min_bps, bonus_max_bps, bonus_max_health_bps, bonus_ramp_seconds }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn flag_liquidatable(&mut self, borrower: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let emode_category: Option<Pubkey> = self.emode_for(borrower).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::FlagLiquidatable {
+                market: self.market.pubkey(),
+                collateral_price_update: self.price_update_for(collateral_mint),
+                borrowed_price_update: self.price_update_for(borrowed_mint),
+                collateral_mint: *collateral_mint,
+                borrowed_mint: *borrowed_mint,
+                collateral_bank: bank_address(&self.market.pubkey(), collateral_mint),
+                borrowed_bank: bank_address(&self.market.pubkey(), borrowed_mint),
+                user_account: user_address(&self.market.pubkey(), borrower),
+                emode_category,
+            }.to_account_metas(None),
+            data: lending::instruction::FlagLiquidatable {}.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn liquidate(&mut self, liquidator: &TestUser, borrower: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        self.liquidate_loan(liquidator, borrower, collateral_mint, borrowed_mint, None).await
+    }
+
+    /// Liquidates `borrower`'s term loan from the `borrowed_mint` bank.
+    pub async fn liquidate_term_loan(&mut self, liquidator: &TestUser, borrower: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let term_loan: Pubkey = term_loan_address(&bank_address(&self.market.pubkey(), borrowed_mint), borrower);
+        self.liquidate_loan(liquidator, borrower, collateral_mint, borrowed_mint, Some(term_loan)).await
+    }
+
+    async fn liquidate_loan(&mut self, liquidator: &TestUser, borrower: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey, term_loan: Option<Pubkey>) -> std::result::Result<(), BanksClientError> {
+        let emode_category: Option<Pubkey> = self.emode_for(borrower).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::Liquidate {
+                liquidator: liquidator.key(),
+                market: self.market.pubkey(),
+                collateral_price_update: self.price_update_for(collateral_mint),
+                borrowed_price_update: self.price_update_for(borrowed_mint),
+                collateral_mint: *collateral_mint,
+                borrowed_mint: *borrowed_mint,
+                collateral_bank: bank_address(&self.market.pubkey(), collateral_mint),
+                borrowed_bank: bank_address(&self.market.pubkey(), borrowed_mint),
+                collateral_bank_token_account: treasury_address(&self.market.pubkey(), collateral_mint),
+                borrowed_bank_token_account: treasury_address(&self.market.pubkey(), borrowed_mint),
+                user_account: user_address(&self.market.pubkey(), borrower),
+                emode_category,
+                term_loan,
+                liquidator_collateral_token_account: self.token_account_address(&liquidator.key(), collateral_mint),
+                liquidator_borrowed_token_account: self.token_account_address(&liquidator.key(), borrowed_mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::Liquidate {}.data(),
+        };
+        self.process(&[instruction], &[&liquidator.keypair]).await
+    }
+
+    pub async fn update_bank_auction(&mut self, mint: &Pubkey, enabled: bool, duration: i64, start_premium_bps: u64, end_discount_bps: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankAuction {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankAuction { enabled, duration, start_premium_bps, end_discount_bps }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn start_auction(&mut self, starter: &TestUser, borrower: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let emode_category: Option<Pubkey> = self.emode_for(borrower).await;
+        let user_account: Pubkey = user_address(&self.market.pubkey(), borrower);
+        let collateral_bank: Pubkey = bank_address(&self.market.pubkey(), collateral_mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::StartAuction {
+                starter: starter.key(),
+                market: self.market.pubkey(),
+                collateral_price_update: self.price_update_for(collateral_mint),
+                borrowed_price_update: self.price_update_for(borrowed_mint),
+                collateral_mint: *collateral_mint,
+                borrowed_mint: *borrowed_mint,
+                collateral_bank,
+                borrowed_bank: bank_address(&self.market.pubkey(), borrowed_mint),
+                user_account,
+                emode_category,
+                auction: auction_address(&collateral_bank, &user_account),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::StartAuction {}.data(),
+        };
+        self.process(&[instruction], &[&starter.keypair]).await
+    }
+
+    pub async fn bid(&mut self, bidder: &TestUser, starter: &Pubkey, borrower: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let emode_category: Option<Pubkey> = self.emode_for(borrower).await;
+        let user_account: Pubkey = user_address(&self.market.pubkey(), borrower);
+        let collateral_bank: Pubkey = bank_address(&self.market.pubkey(), collateral_mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::Bid {
+                bidder: bidder.key(),
+                market: self.market.pubkey(),
+                collateral_mint: *collateral_mint,
+                borrowed_mint: *borrowed_mint,
+                collateral_price_update: self.price_update_for(collateral_mint),
+                borrowed_price_update: self.price_update_for(borrowed_mint),
+                collateral_bank,
+                borrowed_bank: bank_address(&self.market.pubkey(), borrowed_mint),
+                collateral_bank_token_account: treasury_address(&self.market.pubkey(), collateral_mint),
+                borrowed_bank_token_account: treasury_address(&self.market.pubkey(), borrowed_mint),
+                user_account,
+                emode_category,
+                auction: auction_address(&collateral_bank, &user_account),
+                starter: *starter,
+                bidder_collateral_token_account: self.token_account_address(&bidder.key(), collateral_mint),
+                bidder_borrowed_token_account: self.token_account_address(&bidder.key(), borrowed_mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::Bid { amount }.data(),
+        };
+        self.process(&[instruction], &[&bidder.keypair]).await
+    }
+
+    pub async fn cancel_auction(&mut self, starter: &Pubkey, borrower: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let emode_category: Option<Pubkey> = self.emode_for(borrower).await;
+        let user_account: Pubkey = user_address(&self.market.pubkey(), borrower);
+        let collateral_bank: Pubkey = bank_address(&self.market.pubkey(), collateral_mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::CancelAuction {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                collateral_price_update: self.price_update_for(collateral_mint),
+                borrowed_price_update: self.price_update_for(borrowed_mint),
+                collateral_mint: *collateral_mint,
+                borrowed_mint: *borrowed_mint,
+                collateral_bank,
+                borrowed_bank: bank_address(&self.market.pubkey(), borrowed_mint),
+                user_account,
+                emode_category,
+                auction: auction_address(&collateral_bank, &user_account),
+                starter: *starter,
+            }.to_account_metas(None),
+            data: lending::instruction::CancelAuction {}.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn update_bank_rates(&mut self, mint: &Pubkey, base_rate_bps: u64, rate_slope_bps: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankRates {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankRates { base_rate_bps, rate_slope_bps }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn update_bank_term_rates(&mut self, mint: &Pubkey, premium_bps: u64, penalty_rate_bps: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankTermRates {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankTermRates { premium_bps, penalty_rate_bps }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn borrow_fixed(&mut self, user: &TestUser, mint: &Pubkey, amount: u64, duration: i64) -> std::result::Result<(), BanksClientError> {
+        let collateral_mint: Pubkey = if *mint == self.usdc_mint.pubkey() { self.sol_mint.pubkey() } else { self.usdc_mint.pubkey() };
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::BorrowFixed {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                collateral_mint,
+                bank,
+                collateral_bank: bank_address(&self.market.pubkey(), &collateral_mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                emode_category,
+                term_loan: term_loan_address(&bank, &user.key()),
+                user_token_account: self.token_account_address(&user.key(), mint),
+                price_update: self.price_update_for(&collateral_mint),
+                borrowed_price_update: self.price_update_for(mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::BorrowFixed { amount, duration }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn repay_fixed(&mut self, user: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::RepayFixed {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                term_loan: term_loan_address(&bank, &user.key()),
+                user_token_account: self.token_account_address(&user.key(), mint),
+                token_program: self.token_program,
+            }.to_account_metas(None),
+            data: lending::instruction::RepayFixed { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn update_bank_stable_rates(&mut self, mint: &Pubkey, premium_bps: u64, rebalance_utilization_bps: u64, rebalance_down_delta_bps: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankStableRates {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankStableRates { premium_bps, rebalance_utilization_bps, rebalance_down_delta_bps }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn borrow_stable(&mut self, user: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let collateral_mint: Pubkey = if *mint == self.usdc_mint.pubkey() { self.sol_mint.pubkey() } else { self.usdc_mint.pubkey() };
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::BorrowStable {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                collateral_mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                collateral_bank: bank_address(&self.market.pubkey(), &collateral_mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                emode_category,
+                user_token_account: self.token_account_address(&user.key(), mint),
+                price_update: self.price_update_for(&collateral_mint),
+                borrowed_price_update: self.price_update_for(mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::BorrowStable { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn repay_stable(&mut self, user: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::RepayStable {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                user_token_account: self.token_account_address(&user.key(), mint),
+                token_program: self.token_program,
+            }.to_account_metas(None),
+            data: lending::instruction::RepayStable { amount }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn rebalance_stable_rate(&mut self, signer: &TestUser, owner: &Pubkey, mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::RebalanceStableRate {
+                signer: signer.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                user_account: user_address(&self.market.pubkey(), owner),
+            }.to_account_metas(None),
+            data: lending::instruction::RebalanceStableRate {}.data(),
+        };
+        self.process(&[instruction], &[&signer.keypair]).await
+    }
+
+    pub async fn update_bank_outflow_limit(&mut self, mint: &Pubkey, window: i64, limit: u64, limit_in_usd: bool) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankOutflowLimit {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankOutflowLimit { window, limit, limit_in_usd }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn update_bank_insurance(&mut self, mint: &Pubkey, reserve_factor_bps: u64, cooldown: i64, unstake_window: i64) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::UpdateBankInsurance {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                insurance_vault: insurance_vault_address(&bank),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::UpdateBankInsurance { reserve_factor_bps, cooldown, unstake_window }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn stake_insurance(&mut self, staker: &TestUser, mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::StakeInsurance {
+                staker: staker.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                insurance_vault: insurance_vault_address(&bank),
+                stake: insurance_stake_address(&bank, &staker.key()),
+                staker_token_account: self.token_account_address(&staker.key(), mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::StakeInsurance { amount }.data(),
+        };
+        self.process(&[instruction], &[&staker.keypair]).await
+    }
+
+    pub async fn request_unstake(&mut self, staker: &TestUser, mint: &Pubkey, shares: u64) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::RequestUnstake {
+                staker: staker.key(),
+                bank,
+                stake: insurance_stake_address(&bank, &staker.key()),
+            }.to_account_metas(None),
+            data: lending::instruction::RequestUnstake { shares }.data(),
+        };
+        self.process(&[instruction], &[&staker.keypair]).await
+    }
+
+    pub async fn unstake(&mut self, staker: &TestUser, mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::Unstake {
+                staker: staker.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                insurance_vault: insurance_vault_address(&bank),
+                stake: insurance_stake_address(&bank, &staker.key()),
+                staker_token_account: self.token_account_address(&staker.key(), mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::Unstake {}.data(),
+        };
+        self.process(&[instruction], &[&staker.keypair]).await
+    }
+
+    pub async fn cover_bad_debt(&mut self, borrower: &Pubkey, mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let term_loan: Pubkey = term_loan_address(&bank_address(&self.market.pubkey(), mint), borrower);
+        let term_loan: Option<Pubkey> = self.ctx.banks_client.get_account(term_loan).await.unwrap().is_some().then_some(term_loan);
+        self.cover_debt(borrower, mint, term_loan).await
+    }
+
+    /// Like `cover_bad_debt`, leaving out the borrower's term loan even when there is one.
+    pub async fn cover_bad_debt_without_term_loan(&mut self, borrower: &Pubkey, mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        self.cover_debt(borrower, mint, None).await
+    }
+
+    async fn cover_debt(&mut self, borrower: &Pubkey, mint: &Pubkey, term_loan: Option<Pubkey>) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let isolated_bank: Option<Pubkey> = self.isolated_bank_for(borrower).await;
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::CoverBadDebt {
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                bank_token_account: treasury_address(&self.market.pubkey(), mint),
+                insurance_vault: insurance_vault_address(&bank),
+                user_account: user_address(&self.market.pubkey(), borrower),
+                isolated_bank,
+                term_loan,
+                token_program: self.token_program,
+            }.to_account_metas(None),
+            data: lending::instruction::CoverBadDebt {}.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    /// Opens a mock AMM pool pricing one raw SOL unit at `rate_numerator / rate_denominator` raw USDC units, with
+    /// `liquidity` of each mint in its vaults.
+    pub async fn init_pool(&mut self, rate_numerator: u64, rate_denominator: u64, liquidity: u64) -> std::result::Result<(), BanksClientError> {
+        let sol_mint: Pubkey = self.sol_mint.pubkey();
+        let usdc_mint: Pubkey = self.usdc_mint.pubkey();
+        let payer: Pubkey = self.ctx.payer.pubkey();
+        let pool: Pubkey = mock_amm::pool_address(&sol_mint, &usdc_mint);
+        let instructions: [Instruction; 3] = [
+            Instruction {
+                program_id: mock_amm::ID,
+                accounts: mock_amm::accounts::InitPool {
+                    signer: payer,
+                    mint_a: sol_mint,
+                    mint_b: usdc_mint,
+                    pool,
+                    vault_a: mock_amm::vault_address(&pool, &sol_mint),
+                    vault_b: mock_amm::vault_address(&pool, &usdc_mint),
+                    token_program: self.token_program,
+                    system_program: anchor_lang::system_program::ID,
+                }.to_account_metas(None),
+                data: mock_amm::instruction::InitPool { rate_numerator, rate_denominator }.data(),
+            },
+            spl_token_2022::instruction::mint_to(&self.token_program, &sol_mint, &mock_amm::vault_address(&pool, &sol_mint), &payer, &[], liquidity).unwrap(),
+            spl_token_2022::instruction::mint_to(&self.token_program, &usdc_mint, &mock_amm::vault_address(&pool, &usdc_mint), &payer, &[], liquidity).unwrap(),
+        ];
+        self.process(&instructions, &[]).await
+    }
+
+    pub async fn update_pool_rate(&mut self, rate_numerator: u64, rate_denominator: u64) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: mock_amm::ID,
+            accounts: mock_amm::accounts::UpdateRate {
+                signer: self.ctx.payer.pubkey(),
+                pool: mock_amm::pool_address(&self.sol_mint.pubkey(), &self.usdc_mint.pubkey()),
+            }.to_account_metas(None),
+            data: mock_amm::instruction::UpdateRate { rate_numerator, rate_denominator }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    /// Mock AMM swap of `owner`'s `mint_in` for `mint_out`, for the lending program to forward to the pool.
+    pub fn swap_instruction(&self, owner: &Pubkey, mint_in: &Pubkey, mint_out: &Pubkey, amount_in: u64, min_amount_out: u64) -> Instruction {
+        let pool: Pubkey = mock_amm::pool_address(&self.sol_mint.pubkey(), &self.usdc_mint.pubkey());
+        Instruction {
+            program_id: mock_amm::ID,
+            accounts: mock_amm::accounts::Swap {
+                user: *owner,
+                pool,
+                mint_in: *mint_in,
+                mint_out: *mint_out,
+                vault_in: mock_amm::vault_address(&pool, mint_in),
+                vault_out: mock_amm::vault_address(&pool, mint_out),
+                user_in: self.token_account_address(owner, mint_in),
+                user_out: self.token_account_address(owner, mint_out),
+                token_program: self.token_program,
+            }.to_account_metas(None),
+            data: mock_amm::instruction::Swap { amount_in, min_amount_out }.data(),
+        }
+    }
+
+    pub async fn leverage(
+        &mut self,
+        user: &TestUser,
+        mint: &Pubkey,
+        amount: u64,
+        min_health_factor_bps: u64,
+        swap: Instruction,
+    ) -> std::result::Result<(), BanksClientError> {
+        let collateral_mint: Pubkey = if *mint == self.usdc_mint.pubkey() { self.sol_mint.pubkey() } else { self.usdc_mint.pubkey() };
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let mut accounts: Vec<AccountMeta> = lending::accounts::Leverage {
+            signer: user.key(),
+            market: self.market.pubkey(),
+            mint: *mint,
+            collateral_mint,
+            bank: bank_address(&self.market.pubkey(), mint),
+            collateral_bank: bank_address(&self.market.pubkey(), &collateral_mint),
+            bank_token_account: treasury_address(&self.market.pubkey(), mint),
+            collateral_bank_token_account: treasury_address(&self.market.pubkey(), &collateral_mint),
+            user_account: user_address(&self.market.pubkey(), &user.key()),
+            emode_category,
+            user_token_account: self.token_account_address(&user.key(), mint),
+            user_collateral_token_account: self.token_account_address(&user.key(), &collateral_mint),
+            price_update: self.price_update_for(mint),
+            collateral_price_update: self.price_update_for(&collateral_mint),
+            swap_program: swap.program_id,
+            token_program: self.token_program,
+            system_program: anchor_lang::system_program::ID,
+            associated_token_program: associated_token::ID,
+        }.to_account_metas(None);
+        accounts.extend(swap.accounts);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts,
+            data: lending::instruction::Leverage { amount, min_health_factor_bps, swap_data: swap.data }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn swap_collateral(&mut self, user: &TestUser, from_mint: &Pubkey, amount: u64, swap: Instruction) -> std::result::Result<(), BanksClientError> {
+        let to_mint: Pubkey = if *from_mint == self.usdc_mint.pubkey() { self.sol_mint.pubkey() } else { self.usdc_mint.pubkey() };
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let mut accounts: Vec<AccountMeta> = lending::accounts::SwapCollateral {
+            signer: user.key(),
+            market: self.market.pubkey(),
+            from_mint: *from_mint,
+            to_mint,
+            from_bank: bank_address(&self.market.pubkey(), from_mint),
+            to_bank: bank_address(&self.market.pubkey(), &to_mint),
+            from_bank_token_account: treasury_address(&self.market.pubkey(), from_mint),
+            to_bank_token_account: treasury_address(&self.market.pubkey(), &to_mint),
+            user_account: user_address(&self.market.pubkey(), &user.key()),
+            emode_category,
+            user_from_token_account: self.token_account_address(&user.key(), from_mint),
+            user_to_token_account: self.token_account_address(&user.key(), &to_mint),
+            from_price_update: self.price_update_for(from_mint),
+            to_price_update: self.price_update_for(&to_mint),
+            swap_program: swap.program_id,
+            token_program: self.token_program,
+            system_program: anchor_lang::system_program::ID,
+            associated_token_program: associated_token::ID,
+        }.to_account_metas(None);
+        accounts.extend(swap.accounts);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts,
+            data: lending::instruction::SwapCollateral { amount, swap_data: swap.data }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn deleverage(&mut self, user: &TestUser, mint: &Pubkey, amount: u64, swap: Instruction) -> std::result::Result<(), BanksClientError> {
+        let collateral_mint: Pubkey = if *mint == self.usdc_mint.pubkey() { self.sol_mint.pubkey() } else { self.usdc_mint.pubkey() };
+        let emode_category: Option<Pubkey> = self.emode_for(&user.key()).await;
+        let mut accounts: Vec<AccountMeta> = lending::accounts::Deleverage {
+            signer: user.key(),
+            market: self.market.pubkey(),
+            mint: *mint,
+            collateral_mint,
+            bank: bank_address(&self.market.pubkey(), mint),
+            collateral_bank: bank_address(&self.market.pubkey(), &collateral_mint),
+            bank_token_account: treasury_address(&self.market.pubkey(), mint),
+            collateral_bank_token_account: treasury_address(&self.market.pubkey(), &collateral_mint),
+            user_account: user_address(&self.market.pubkey(), &user.key()),
+            emode_category,
+            user_token_account: self.token_account_address(&user.key(), mint),
+            user_collateral_token_account: self.token_account_address(&user.key(), &collateral_mint),
+            price_update: self.price_update_for(mint),
+            collateral_price_update: self.price_update_for(&collateral_mint),
+            swap_program: swap.program_id,
+            token_program: self.token_program,
+            system_program: anchor_lang::system_program::ID,
+            associated_token_program: associated_token::ID,
+        }.to_account_metas(None);
+        accounts.extend(swap.accounts);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts,
+            data: lending::instruction::Deleverage { amount, swap_data: swap.data }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn term_loan(&mut self, owner: &Pubkey, mint: &Pubkey) -> TermLoan {
+        self.account(&term_loan_address(&bank_address(&self.market.pubkey(), mint), owner)).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn init_reward_stream(&mut self, mint: &Pubkey, reward_mint: &Pubkey, stream: u8, borrow_side: bool, emission_rate: u64, start: i64, end: i64) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::InitRewardStream {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                reward_mint: *reward_mint,
+                reward_vault: reward_vault_address(&bank, reward_mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::InitRewardStream { stream, borrow_side, emission_rate, start, end }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    /// Moves `amount` of `reward_mint` from the market owner's token account into the bank's reward vault.
+    pub async fn fund_rewards(&mut self, mint: &Pubkey, reward_mint: &Pubkey, amount: u64) -> std::result::Result<(), BanksClientError> {
+        let owner: Pubkey = self.ctx.payer.pubkey();
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::FundRewards {
+                signer: owner,
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                reward_mint: *reward_mint,
+                reward_vault: reward_vault_address(&bank, reward_mint),
+                signer_token_account: self.token_account_address(&owner, reward_mint),
+                token_program: self.token_program,
+            }.to_account_metas(None),
+            data: lending::instruction::FundRewards { amount }.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn claim_rewards(&mut self, user: &TestUser, mint: &Pubkey, reward_mint: &Pubkey, stream: u8) -> std::result::Result<(), BanksClientError> {
+        let bank: Pubkey = bank_address(&self.market.pubkey(), mint);
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::ClaimRewards {
+                signer: user.key(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank,
+                user_account: user_address(&self.market.pubkey(), &user.key()),
+                reward_mint: *reward_mint,
+                reward_vault: reward_vault_address(&bank, reward_mint),
+                user_reward_token_account: self.token_account_address(&user.key(), reward_mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: associated_token::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::ClaimRewards { stream }.data(),
+        };
+        self.process(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn migrate_bank(&mut self, mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::MigrateBank {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                legacy_bank: legacy_bank_address(mint),
+                legacy_bank_token_account: legacy_treasury_address(mint),
+                bank: bank_address(&self.market.pubkey(), mint),
+                bank_token_account: self.treasury_address(mint),
+                token_program: self.token_program,
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::MigrateBank {}.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn migrate_user(&mut self, owner: &Keypair) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::MigrateUser {
+                signer: owner.pubkey(),
+                market: self.market.pubkey(),
+                legacy_user_account: legacy_user_address(&owner.pubkey()),
+                user_account: user_address(&self.market.pubkey(), &owner.pubkey()),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::MigrateUser {}.data(),
+        };
+        self.process(&[instruction], &[owner]).await
+    }
+
+    pub async fn realloc_bank(&mut self, mint: &Pubkey) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::ReallocBank {
+                signer: self.ctx.payer.pubkey(),
+                market: self.market.pubkey(),
+                mint: *mint,
+                bank: bank_address(&self.market.pubkey(), mint),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::ReallocBank {}.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn realloc_user(&mut self, owner: &Keypair) -> std::result::Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: lending::ID,
+            accounts: lending::accounts::ReallocUser {
+                signer: owner.pubkey(),
+                market: self.market.pubkey(),
+                user_account: user_address(&self.market.pubkey(), &owner.pubkey()),
+                system_program: anchor_lang::system_program::ID,
+            }.to_account_metas(None),
+            data: lending::instruction::ReallocUser {}.data(),
+        };
+        self.process(&[instruction], &[owner]).await
+    }
+}
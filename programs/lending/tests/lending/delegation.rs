@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{SOL_USB_FEED_ID, USDC_USD_FEED_ID}, error::ErrorCode, state::BorrowAllowance};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+const ALLOWANCE: u64 = 1_000;
+const DURATION: i64 = 3_600;
+
+async fn setup() -> (TestEnv, TestUser, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let owner: TestUser = env.create_borrower(100_000, 10).await;
+    let delegate: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let expires_at: i64 = env.clock().await.unix_timestamp + DURATION;
+    env.approve_borrow_delegation(&owner, &delegate.key(), &usdc_mint, ALLOWANCE, expires_at).await.unwrap();
+
+    (env, owner, delegate)
+}
+
+#[tokio::test]
+async fn delegate_borrows_onto_owners_account() {
+    let (mut env, owner, delegate) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_delegated(&delegate, &owner.key(), &usdc_mint, 600).await.unwrap();
+
+    assert_eq!(env.token_balance(&delegate.usdc_token_account).await, STARTING_BALANCE + 600);
+    assert_eq!(env.token_balance(&owner.usdc_token_account).await, STARTING_BALANCE);
+    assert_eq!(env.user(&owner.key()).await.borrowed_usdc, 600);
+    assert_eq!(env.user(&delegate.key()).await.borrowed_usdc, 0);
+
+    let bank: Pubkey = bank_address(&env.market.pubkey(), &usdc_mint);
+    let allowance: BorrowAllowance = env.account(&allowance_address(&owner.key(), &delegate.key(), &bank)).await;
+    assert_eq!(allowance.amount, ALLOWANCE - 600);
+}
+
+#[tokio::test]
+async fn delegate_borrow_over_allowance_fails() {
+    let (mut env, owner, delegate) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_delegated(&delegate, &owner.key(), &usdc_mint, 600).await.unwrap();
+
+    assert_error(env.borrow_delegated(&delegate, &owner.key(), &usdc_mint, 401).await, ErrorCode::AllowanceExceeded);
+}
+
+#[tokio::test]
+async fn delegate_borrow_after_expiry_fails() {
+    let (mut env, owner, delegate) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.warp_seconds(DURATION + 1).await;
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+    env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+
+    assert_error(env.borrow_delegated(&delegate, &owner.key(), &usdc_mint, 100).await, ErrorCode::AllowanceExpired);
+}
+
+#[tokio::test]
+async fn delegate_borrow_still_needs_owners_collateral() {
+    let (mut env, owner, delegate) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // The allowance is generous, but 10 SOL at 50 only backs 500.
+    let expires_at: i64 = env.clock().await.unix_timestamp + DURATION;
+    env.approve_borrow_delegation(&owner, &delegate.key(), &usdc_mint, 10_000, expires_at).await.unwrap();
+    env.set_price(SOL_USB_FEED_ID, 50).await;
+
+    assert_error(env.borrow_delegated(&delegate, &owner.key(), &usdc_mint, 600).await, ErrorCode::OverBorrowableAmount);
+}
+
+#[tokio::test]
+async fn borrow_without_allowance_fails() {
+    let (mut env, owner, _) = setup().await;
+    let stranger: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(
+        env.borrow_delegated(&stranger, &owner.key(), &usdc_mint, 100).await,
+        anchor_lang::error::ErrorCode::AccountNotInitialized,
+    );
+}
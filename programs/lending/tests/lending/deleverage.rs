@@ -0,0 +1,67 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use lending::{constants::SOL_USB_FEED_ID, error::ErrorCode};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.init_pool(SOL_PRICE as u64, USDC_PRICE as u64, 100_000).await.unwrap();
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn deleverage_repays_debt_without_bonus() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let usdc_before: u64 = env.token_balance(&borrower.usdc_token_account).await;
+
+    // 4 SOL are sold for 600 USDC, and exactly 4 SOL leave the bank.
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &sol_mint, &usdc_mint, 4, 600);
+    env.deleverage(&borrower, &usdc_mint, 4, swap).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol, 6);
+    assert_eq!(user.borrowed_usdc, 400);
+    assert_eq!(env.bank(&sol_mint).await.total_deposits, 6);
+    assert_eq!(env.token_balance(&borrower.usdc_token_account).await, usdc_before);
+}
+
+#[tokio::test]
+async fn deleverage_near_threshold_passes_through_unhealthy_state() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // At 110 the 10 SOL barely cover the 1_000 borrowed, and the 6 left after the withdrawal don't.
+    env.set_price(SOL_USB_FEED_ID, 110).await;
+    env.update_pool_rate(110, 1).await.unwrap();
+
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &sol_mint, &usdc_mint, 4, 440);
+    env.deleverage(&borrower, &usdc_mint, 4, swap).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol, 6);
+    assert_eq!(user.borrowed_usdc, 560);
+}
+
+#[tokio::test]
+async fn deleverage_that_lowers_health_fails() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // Selling 4 SOL at 50 USDC only repays 200, leaving the position worse off than before.
+    env.update_pool_rate(50, 1).await.unwrap();
+
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &sol_mint, &usdc_mint, 4, 0);
+    assert_error(env.deleverage(&borrower, &usdc_mint, 4, swap).await, ErrorCode::HealthFactorTooLow);
+}
@@ -0,0 +1,95 @@
+use anchor_lang::prelude::*;
+use lending::error::ErrorCode;
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+#[tokio::test]
+async fn deposit_moves_tokens_into_treasury() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&user, &mint, 1_000).await.unwrap();
+
+    assert_eq!(env.token_balance(&user.sol_token_account).await, STARTING_BALANCE - 1_000);
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 1_000);
+
+    let account = env.user(&user.key()).await;
+    assert_eq!(account.deposited_sol, 1_000);
+    assert_eq!(account.deposited_sol_shares, 1_000);
+    assert_eq!(account.deposited_usdc, 0);
+    assert_eq!(account.last_updated, env.clock().await.unix_timestamp);
+}
+
+#[tokio::test]
+async fn deposit_usdc_is_tracked_separately() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&user, &mint, 2_500).await.unwrap();
+
+    let account = env.user(&user.key()).await;
+    assert_eq!(account.deposited_usdc, 2_500);
+    assert_eq!(account.deposited_sol, 0);
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 2_500);
+}
+
+#[tokio::test]
+async fn deposit_more_than_balance_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    assert!(env.deposit(&user, &mint, STARTING_BALANCE + 1).await.is_err());
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 0);
+}
+
+#[tokio::test]
+async fn deposit_for_credits_the_beneficiary() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let payer: TestUser = env.create_user().await;
+    let beneficiary: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit_for(&payer, &beneficiary.key(), &mint, 1_000).await.unwrap();
+
+    assert_eq!(env.token_balance(&payer.sol_token_account).await, STARTING_BALANCE - 1_000);
+    assert_eq!(env.token_balance(&beneficiary.sol_token_account).await, STARTING_BALANCE);
+    assert_eq!(env.user(&payer.key()).await.deposited_sol_shares, 0);
+    assert_eq!(env.user(&beneficiary.key()).await.deposited_sol_shares, 1_000);
+
+    // The beneficiary owns the deposit and can withdraw it.
+    env.withdraw(&beneficiary, &mint, 1_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn deposit_for_into_isolation_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let payer: TestUser = env.create_user().await;
+    let beneficiary: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.update_bank_isolation(&mint, true, 1_000, false).await.unwrap();
+
+    assert_error(env.deposit_for(&payer, &beneficiary.key(), &mint, 1_000).await, ErrorCode::DepositForChangesMode);
+    env.deposit(&beneficiary, &mint, 1_000).await.unwrap();
+    env.deposit_for(&payer, &beneficiary.key(), &mint, 1_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn deposit_for_outside_emode_category_fails() {
+    let mut env: TestEnv = TestEnv::with_risk_parameters(5_000, 5_000).await;
+    let payer: TestUser = env.create_user().await;
+    let beneficiary: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.init_emode_category(1, 9_000, 9_000).await.unwrap();
+    env.update_bank_emode(&usdc_mint, 1).await.unwrap();
+    env.set_emode(&beneficiary, 1, &[]).await.unwrap();
+
+    assert_error(env.deposit_for(&payer, &beneficiary.key(), &sol_mint, 1_000).await, ErrorCode::DepositForChangesMode);
+    env.deposit_for(&payer, &beneficiary.key(), &usdc_mint, 1_000).await.unwrap();
+}
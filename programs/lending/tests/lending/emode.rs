@@ -0,0 +1,167 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{BASIS_POINTS, SOL_USB_FEED_ID}, error::ErrorCode};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::common::*;
+
+const CATEGORY: u8 = 1;
+const BANK_RISK_PARAMETER: u64 = 5_000;
+const EMODE_RISK_PARAMETER: u64 = 9_000;
+
+// Both banks back half their collateral's value and are in the category, which raises what 10 SOL at 150 can back
+// from 750 to 1_350 USDC.
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_risk_parameters(BANK_RISK_PARAMETER, BANK_RISK_PARAMETER).await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.init_emode_category(CATEGORY, EMODE_RISK_PARAMETER, EMODE_RISK_PARAMETER).await.unwrap();
+    env.update_bank_emode(&sol_mint, CATEGORY).await.unwrap();
+    env.update_bank_emode(&usdc_mint, CATEGORY).await.unwrap();
+
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn init_emode_category_zero_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+
+    assert_error(env.init_emode_category(0, EMODE_RISK_PARAMETER, EMODE_RISK_PARAMETER).await, ErrorCode::InvalidEmodeCategory);
+}
+
+#[tokio::test]
+async fn init_emode_category_with_invalid_parameters_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+
+    assert_error(env.init_emode_category(CATEGORY, EMODE_RISK_PARAMETER, EMODE_RISK_PARAMETER - 1).await, ErrorCode::InvalidRiskParameters);
+    assert_error(env.init_emode_category(CATEGORY, EMODE_RISK_PARAMETER, BASIS_POINTS).await, ErrorCode::InvalidRiskParameters);
+}
+
+#[tokio::test]
+async fn bank_with_higher_parameters_than_category_cannot_join() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    env.init_emode_category(CATEGORY, EMODE_RISK_PARAMETER, EMODE_RISK_PARAMETER).await.unwrap();
+
+    assert_error(env.update_bank_emode(&sol_mint, CATEGORY).await, ErrorCode::EmodeBelowBankParameters);
+}
+
+#[tokio::test]
+async fn emode_raises_borrowable_amount() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 1_000).await, ErrorCode::OverBorrowableAmount);
+
+    env.set_emode(&borrower, CATEGORY, &[sol_mint]).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.emode_category, CATEGORY);
+
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn set_emode_with_position_outside_category_fails() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    // The SOL deposit has to be vouched for by its bank.
+    assert_error(env.set_emode(&borrower, CATEGORY, &[]).await, ErrorCode::EmodeCategoryMismatch);
+
+    env.update_bank_emode(&sol_mint, 0).await.unwrap();
+
+    assert_error(env.set_emode(&borrower, CATEGORY, &[sol_mint]).await, ErrorCode::EmodeCategoryMismatch);
+}
+
+#[tokio::test]
+async fn borrow_outside_category_fails() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_emode(&borrower, CATEGORY, &[sol_mint]).await.unwrap();
+    env.update_bank_emode(&usdc_mint, 0).await.unwrap();
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 100).await, ErrorCode::EmodeCategoryMismatch);
+}
+
+#[tokio::test]
+async fn liquidation_uses_category_threshold() {
+    let (mut env, borrower) = setup().await;
+    let liquidator: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_emode(&borrower, CATEGORY, &[sol_mint]).await.unwrap();
+    env.borrow(&borrower, &usdc_mint, 1_300).await.unwrap();
+
+    // 10 SOL at 145 back 1_305 at the category threshold, though only 725 at the banks' own.
+    env.set_price(SOL_USB_FEED_ID, 145).await;
+    assert_error(env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::NotUnderCollaterized);
+
+    env.set_price(SOL_USB_FEED_ID, 140).await;
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+}
+
+#[tokio::test]
+async fn withdraw_uses_category_threshold() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_emode(&borrower, CATEGORY, &[sol_mint]).await.unwrap();
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    // 8 SOL at 150 back 1_080 at the category threshold, 7 only 945.
+    env.withdraw(&borrower, &sol_mint, 2).await.unwrap();
+    assert_error(env.withdraw(&borrower, &sol_mint, 1).await, ErrorCode::HealthFactorTooLow);
+}
+
+#[tokio::test]
+async fn borrow_is_capped_at_category_max_ltv() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let category: u8 = CATEGORY + 1;
+    env.init_emode_category(category, 6_000, EMODE_RISK_PARAMETER).await.unwrap();
+    env.update_bank_emode(&sol_mint, category).await.unwrap();
+    env.update_bank_emode(&usdc_mint, category).await.unwrap();
+    env.set_emode(&borrower, category, &[sol_mint]).await.unwrap();
+
+    // The category's 60% loan-to-value caps 10 SOL at 150 at 900 USDC, below its threshold.
+    assert_error(env.borrow(&borrower, &usdc_mint, 901).await, ErrorCode::OverBorrowableAmount);
+    env.borrow(&borrower, &usdc_mint, 900).await.unwrap();
+}
+
+#[tokio::test]
+async fn set_emode_vouched_for_by_another_sol_bank_fails() {
+    let (mut env, borrower) = setup().await;
+
+    let other_mint: Keypair = Keypair::new();
+    env.create_mint(&other_mint, SOL_DECIMALS, &[]).await;
+    env.init_bank(&other_mint.pubkey(), BANK_RISK_PARAMETER, BANK_RISK_PARAMETER).await.unwrap();
+    env.update_bank_emode(&other_mint.pubkey(), CATEGORY).await.unwrap();
+
+    assert_error(env.set_emode(&borrower, CATEGORY, &[other_mint.pubkey()]).await, ErrorCode::EmodeCategoryMismatch);
+}
+
+#[tokio::test]
+async fn leaving_emode_with_debt_the_banks_wont_back_fails() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_emode(&borrower, CATEGORY, &[sol_mint]).await.unwrap();
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    // Outside the category 10 SOL at 150 only back 750 USDC.
+    assert_error(env.set_emode(&borrower, 0, &[]).await, ErrorCode::HealthFactorTooLow);
+
+    env.repay(&borrower, &usdc_mint, 300).await.unwrap();
+    env.set_emode(&borrower, 0, &[]).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.emode_category, 0);
+}
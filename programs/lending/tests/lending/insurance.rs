@@ -0,0 +1,304 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{SECONDS_PER_YEAR, SOL_USB_FEED_ID}, error::ErrorCode, state::{InsuranceStake, TermLoan}};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::common::*;
+
+// A USDC insurance fund keeping back 20% of interest, with a 100 second unstake cooldown and 50 seconds after it to
+// unstake in.
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let staker: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_insurance(&usdc_mint, 2_000, 100, 50).await.unwrap();
+
+    (env, staker)
+}
+
+#[derive(Clone, Copy)]
+enum Debt {
+    Variable,
+    Stable,
+    Term,
+}
+
+// 10 SOL against 1_000 USDC of `debt`. Liquidations repay half the debt for no bonus, so a fall of SOL to 50 leaves
+// 500 USDC of debt and no collateral behind.
+async fn setup_bad_debt(env: &mut TestEnv, debt: Debt) -> TestUser {
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    for mint in [sol_mint, usdc_mint] {
+        env.update_bank_liquidation(&mint, 5_000, 0, 0, 0, 0).await.unwrap();
+    }
+
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    let liquidator: TestUser = env.create_user().await;
+    match debt {
+        Debt::Variable => env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap(),
+        Debt::Stable => env.borrow_stable(&borrower, &usdc_mint, 1_000).await.unwrap(),
+        Debt::Term => env.borrow_fixed(&borrower, &usdc_mint, 1_000, SECONDS_PER_YEAR as i64).await.unwrap(),
+    }
+
+    env.set_price(SOL_USB_FEED_ID, 50).await;
+    match debt {
+        Debt::Term => env.liquidate_term_loan(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap(),
+        _ => env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap(),
+    }
+
+    borrower
+}
+
+#[tokio::test]
+async fn stake_insurance_funds_the_vault() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.stake_insurance(&staker, &usdc_mint, 1_000).await.unwrap();
+
+    let bank: Pubkey = bank_address(&env.market.pubkey(), &usdc_mint);
+    assert_eq!(env.token_balance(&insurance_vault_address(&bank)).await, 1_000);
+    assert_eq!(env.token_balance(&staker.usdc_token_account).await, STARTING_BALANCE - 1_000);
+    assert_eq!(env.bank(&usdc_mint).await.insurance_shares, 1_000);
+    assert_eq!(env.account::<InsuranceStake>(&insurance_stake_address(&bank, &staker.key())).await.shares, 1_000);
+}
+
+#[tokio::test]
+async fn unstake_waits_for_the_cooldown() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.stake_insurance(&staker, &usdc_mint, 1_000).await.unwrap();
+    assert_error(env.unstake(&staker, &usdc_mint).await, ErrorCode::UnstakeCooldown);
+
+    env.request_unstake(&staker, &usdc_mint, 400).await.unwrap();
+    assert_error(env.unstake(&staker, &usdc_mint).await, ErrorCode::UnstakeCooldown);
+
+    env.warp_seconds(100).await;
+    env.unstake(&staker, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&staker.usdc_token_account).await, STARTING_BALANCE - 600);
+    assert_eq!(env.bank(&usdc_mint).await.insurance_shares, 600);
+}
+
+#[tokio::test]
+async fn unstake_after_the_window_has_to_be_requested_again() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.stake_insurance(&staker, &usdc_mint, 1_000).await.unwrap();
+    env.request_unstake(&staker, &usdc_mint, 400).await.unwrap();
+
+    env.warp_seconds(151).await;
+    assert_error(env.unstake(&staker, &usdc_mint).await, ErrorCode::UnstakeWindowExpired);
+
+    env.request_unstake(&staker, &usdc_mint, 400).await.unwrap();
+    env.warp_seconds(100).await;
+    env.unstake(&staker, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&staker.usdc_token_account).await, STARTING_BALANCE - 600);
+}
+
+#[tokio::test]
+async fn interest_reserve_is_swept_to_stakers() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_rates(&usdc_mint, 100, 1_000).await.unwrap();
+    env.update_bank_term_rates(&usdc_mint, 200, 500).await.unwrap();
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    env.stake_insurance(&staker, &usdc_mint, 1_000).await.unwrap();
+
+    env.borrow_fixed(&borrower, &usdc_mint, 1_000, SECONDS_PER_YEAR as i64).await.unwrap();
+    let deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+    env.warp_seconds(2 * SECONDS_PER_YEAR as i64).await;
+
+    // Of the 110 interest, 22 is kept back for the fund.
+    env.repay_fixed(&borrower, &usdc_mint, u64::MAX).await.unwrap();
+    assert_eq!(env.bank(&usdc_mint).await.total_deposits, deposits_before + 88);
+    assert_eq!(env.bank(&usdc_mint).await.insurance_fees_pending, 22);
+
+    env.request_unstake(&staker, &usdc_mint, 1_000).await.unwrap();
+    env.warp_seconds(100).await;
+    env.unstake(&staker, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&staker.usdc_token_account).await, STARTING_BALANCE + 22);
+    assert_eq!(env.bank(&usdc_mint).await.insurance_fees_pending, 0);
+}
+
+#[tokio::test]
+async fn cover_bad_debt_socializes_what_the_fund_cannot_cover() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = setup_bad_debt(&mut env, Debt::Variable).await;
+    env.stake_insurance(&staker, &usdc_mint, 300).await.unwrap();
+    let deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+
+    env.cover_bad_debt(&borrower.key(), &usdc_mint).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.borrowed_usdc_shares, 0);
+    assert_eq!(user.borrowed_usdc, 0);
+    let bank = env.bank(&usdc_mint).await;
+    assert_eq!(bank.total_deposits, deposits_before - 200);
+    assert_eq!(bank.total_borrowed, 0);
+    assert_eq!(env.token_balance(&insurance_vault_address(&bank_address(&env.market.pubkey(), &usdc_mint))).await, 0);
+}
+
+#[tokio::test]
+async fn cover_bad_debt_from_the_fund_spares_depositors() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = setup_bad_debt(&mut env, Debt::Variable).await;
+    env.stake_insurance(&staker, &usdc_mint, 2_000).await.unwrap();
+    let deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+
+    env.cover_bad_debt(&borrower.key(), &usdc_mint).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.borrowed_usdc_shares, 0);
+    assert_eq!(env.bank(&usdc_mint).await.total_deposits, deposits_before);
+    assert_eq!(env.token_balance(&insurance_vault_address(&bank_address(&env.market.pubkey(), &usdc_mint))).await, 1_500);
+}
+
+#[tokio::test]
+async fn cover_bad_debt_settles_borrow_rewards_first() {
+    let (mut env, _) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let owner: Pubkey = env.ctx.payer.pubkey();
+
+    // Borrowers of USDC earn 10 reward tokens a second.
+    let reward_mint: Keypair = Keypair::new();
+    env.create_mint(&reward_mint, USDC_DECIMALS, &[]).await;
+    env.fund(&owner, &reward_mint.pubkey(), 100_000).await;
+    let start: i64 = env.clock().await.unix_timestamp;
+    env.init_reward_stream(&usdc_mint, &reward_mint.pubkey(), 0, true, 10, start, start + 1_000).await.unwrap();
+    env.fund_rewards(&usdc_mint, &reward_mint.pubkey(), 100_000).await.unwrap();
+
+    let borrower: TestUser = setup_bad_debt(&mut env, Debt::Variable).await;
+    env.warp_seconds(100).await;
+    env.cover_bad_debt(&borrower.key(), &usdc_mint).await.unwrap();
+
+    // Nothing is earned once the debt is gone.
+    env.warp_seconds(100).await;
+    env.claim_rewards(&borrower, &usdc_mint, &reward_mint.pubkey(), 0).await.unwrap();
+
+    assert_eq!(env.token_balance(&env.token_account_address(&borrower.key(), &reward_mint.pubkey())).await, 1_000);
+}
+
+#[tokio::test]
+async fn cover_bad_debt_with_collateral_left_fails() {
+    let (mut env, _) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    assert_error(env.cover_bad_debt(&borrower.key(), &usdc_mint).await, ErrorCode::NoBadDebt);
+}
+
+#[tokio::test]
+async fn cover_bad_debt_writes_off_stable_debt() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = setup_bad_debt(&mut env, Debt::Stable).await;
+    env.stake_insurance(&staker, &usdc_mint, 300).await.unwrap();
+    let deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+
+    env.cover_bad_debt(&borrower.key(), &usdc_mint).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.usdc_stable_debt.owed, 0);
+    let bank = env.bank(&usdc_mint).await;
+    assert_eq!(bank.total_deposits, deposits_before - 200);
+    assert_eq!(bank.total_stable_borrowed, 0);
+}
+
+#[tokio::test]
+async fn cover_bad_debt_writes_off_term_debt() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = setup_bad_debt(&mut env, Debt::Term).await;
+    env.stake_insurance(&staker, &usdc_mint, 300).await.unwrap();
+    let deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+
+    env.cover_bad_debt(&borrower.key(), &usdc_mint).await.unwrap();
+
+    let bank: Pubkey = bank_address(&env.market.pubkey(), &usdc_mint);
+    assert_eq!(env.account::<TermLoan>(&term_loan_address(&bank, &borrower.key())).await.owed, 0);
+    assert_eq!(env.user(&borrower.key()).await.usdc_term_owed, 0);
+    let bank = env.bank(&usdc_mint).await;
+    assert_eq!(bank.total_deposits, deposits_before - 200);
+    assert_eq!(bank.total_term_borrowed, 0);
+}
+
+#[tokio::test]
+async fn cover_bad_debt_without_the_term_loan_fails() {
+    let (mut env, _) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = setup_bad_debt(&mut env, Debt::Term).await;
+
+    assert_error(env.cover_bad_debt_without_term_loan(&borrower.key(), &usdc_mint).await, ErrorCode::TermLoanRequired);
+    assert_eq!(env.user(&borrower.key()).await.usdc_term_owed, 500);
+}
+
+#[tokio::test]
+async fn deposit_after_deposits_are_written_off_fails() {
+    let (mut env, _) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let lender: TestUser = env.create_user().await;
+    let borrower: TestUser = env.create_user().await;
+    let liquidator: TestUser = env.create_user().await;
+    let depositor: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    // The lender takes out what the liquidation repaid and the rest of the pool is lost, leaving shares worth nothing.
+    env.update_bank_liquidation(&usdc_mint, 5_000, 0, 0, 0, 0).await.unwrap();
+    env.deposit(&lender, &usdc_mint, 1_000).await.unwrap();
+    env.deposit(&borrower, &sol_mint, 10).await.unwrap();
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+    env.set_price(SOL_USB_FEED_ID, 50).await;
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    env.withdraw(&lender, &usdc_mint, 500).await.unwrap();
+    env.cover_bad_debt(&borrower.key(), &usdc_mint).await.unwrap();
+    assert_eq!(env.bank(&usdc_mint).await.total_deposits, 0);
+
+    assert_error(env.deposit(&depositor, &usdc_mint, 1_000).await, ErrorCode::SharesWithoutAssets);
+}
+
+#[tokio::test]
+async fn first_stake_does_not_take_swept_reserve() {
+    let (mut env, staker) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_rates(&usdc_mint, 100, 1_000).await.unwrap();
+    env.update_bank_term_rates(&usdc_mint, 200, 500).await.unwrap();
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    env.borrow_fixed(&borrower, &usdc_mint, 1_000, SECONDS_PER_YEAR as i64).await.unwrap();
+    env.warp_seconds(2 * SECONDS_PER_YEAR as i64).await;
+    env.repay_fixed(&borrower, &usdc_mint, u64::MAX).await.unwrap();
+
+    // The 22 reserve is swept in ahead of the stake and stays behind when it is withdrawn.
+    env.stake_insurance(&staker, &usdc_mint, 1_000).await.unwrap();
+    assert_eq!(env.bank(&usdc_mint).await.insurance_shares, 1_022);
+
+    env.request_unstake(&staker, &usdc_mint, 1_000).await.unwrap();
+    env.warp_seconds(100).await;
+    env.unstake(&staker, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&staker.usdc_token_account).await, STARTING_BALANCE);
+    assert_eq!(env.token_balance(&insurance_vault_address(&bank_address(&env.market.pubkey(), &usdc_mint))).await, 22);
+}
+
+#[tokio::test]
+async fn close_user_with_stable_debt_fails() {
+    let (mut env, _) = setup().await;
+
+    let borrower: TestUser = setup_bad_debt(&mut env, Debt::Stable).await;
+
+    assert_error(env.close_user(&borrower.keypair).await, ErrorCode::OpenPosition);
+}
@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+use lending::error::ErrorCode;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::common::*;
+
+const DEBT_CEILING: u64 = 1_000;
+
+// The SOL bank is isolated and only the USDC bank can be borrowed against it. 10 SOL at 150 backs 1_500 USDC,
+// more than the ceiling allows.
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_isolation(&sol_mint, true, DEBT_CEILING, false).await.unwrap();
+    env.update_bank_isolation(&usdc_mint, false, 0, true).await.unwrap();
+
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn deposit_into_isolated_bank_isolates_the_user() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    assert_eq!(env.user(&borrower.key()).await.isolated_mint, sol_mint);
+
+    env.withdraw(&borrower, &sol_mint, u64::MAX).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.isolated_mint, Pubkey::default());
+}
+
+#[tokio::test]
+async fn borrow_against_isolated_collateral_is_capped_by_debt_ceiling() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow(&borrower, &usdc_mint, 600).await.unwrap();
+    assert_eq!(env.bank(&sol_mint).await.isolated_debt, 600);
+
+    assert_error(env.borrow(&borrower, &usdc_mint, DEBT_CEILING - 600 + 1).await, ErrorCode::DebtCeilingExceeded);
+}
+
+#[tokio::test]
+async fn repay_frees_up_debt_ceiling() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow(&borrower, &usdc_mint, 600).await.unwrap();
+    env.repay(&borrower, &usdc_mint, 600).await.unwrap();
+    assert_eq!(env.bank(&sol_mint).await.isolated_debt, 0);
+
+    env.borrow(&borrower, &usdc_mint, DEBT_CEILING).await.unwrap();
+}
+
+#[tokio::test]
+async fn borrow_of_non_allowlisted_asset_against_isolated_collateral_fails() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_isolation(&usdc_mint, false, 0, false).await.unwrap();
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 100).await, ErrorCode::NotBorrowableInIsolation);
+}
+
+#[tokio::test]
+async fn isolated_collateral_cannot_be_mixed() {
+    let (mut env, borrower) = setup().await;
+
+    let other_mint: Keypair = Keypair::new();
+    env.create_mint(&other_mint, SOL_DECIMALS, &[]).await;
+    env.init_bank(&other_mint.pubkey(), LIQUIDATION_THRESHOLD, MAX_LTV).await.unwrap();
+    env.fund(&borrower.key(), &other_mint.pubkey(), 1_000).await;
+
+    assert_error(env.deposit(&borrower, &other_mint.pubkey(), 1_000).await, ErrorCode::IsolatedCollateralMixed);
+}
@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use lending::error::ErrorCode;
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+
+    env.init_pool(SOL_PRICE as u64, USDC_PRICE as u64, 100_000).await.unwrap();
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn leverage_deposits_swapped_collateral() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let sol_before: u64 = env.token_balance(&borrower.sol_token_account).await;
+    let usdc_before: u64 = env.token_balance(&borrower.usdc_token_account).await;
+
+    // 900 USDC buys 6 SOL, leaving 16 SOL worth 2_400 against 900 of debt.
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &usdc_mint, &sol_mint, 900, 6);
+    env.leverage(&borrower, &usdc_mint, 900, 26_000, swap).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol, 16);
+    assert_eq!(user.borrowed_usdc, 900);
+    assert_eq!(env.token_balance(&borrower.sol_token_account).await, sol_before);
+    assert_eq!(env.token_balance(&borrower.usdc_token_account).await, usdc_before);
+}
+
+#[tokio::test]
+async fn leverage_below_min_health_factor_fails() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &usdc_mint, &sol_mint, 900, 6);
+    assert_error(env.leverage(&borrower, &usdc_mint, 900, 27_000, swap).await, ErrorCode::HealthFactorTooLow);
+}
+
+#[tokio::test]
+async fn leverage_rejects_lending_program_as_swap() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let swap: Instruction = Instruction { program_id: lending::ID, accounts: Vec::new(), data: Vec::new() };
+    assert_error(env.leverage(&borrower, &usdc_mint, 900, 0, swap).await, ErrorCode::InvalidSwapProgram);
+}
+
+#[tokio::test]
+async fn leverage_reverts_when_swap_slips() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // SOL doubles on the pool after the swap was quoted, so 900 USDC only buys 3.
+    env.update_pool_rate(2 * SOL_PRICE as u64, USDC_PRICE as u64).await.unwrap();
+
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &usdc_mint, &sol_mint, 900, 6);
+    assert_error(env.leverage(&borrower, &usdc_mint, 900, 0, swap).await, mock_amm::AmmError::SlippageExceeded);
+    assert_eq!(env.user(&borrower.key()).await.borrowed_usdc, 0);
+}
+
+#[tokio::test]
+async fn leverage_that_swaps_nothing_cannot_leave_position_unhealthy() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // The swap sells nothing, so the 1_600 USDC stay in the wallet and only 10 SOL worth 1_500 back them.
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &usdc_mint, &sol_mint, 0, 0);
+    assert_error(env.leverage(&borrower, &usdc_mint, 1_600, 0, swap).await, ErrorCode::HealthFactorTooLow);
+}
@@ -0,0 +1,223 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{BASIS_POINTS, SOL_USB_FEED_ID, USDC_USD_FEED_ID}, error::ErrorCode};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+async fn setup() -> (TestEnv, TestUser, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    let liquidator: TestUser = env.create_user().await;
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    (env, borrower, liquidator)
+}
+
+// A position large enough for the bonus to show in whole tokens: 1_000 SOL against 100_000 USDC. Liquidations
+// repay half the debt for a bonus of 5% at a health factor of 1, rising to 20% at 0.8 or after 1_000 seconds.
+async fn setup_with_bonus() -> (TestEnv, TestUser, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    for mint in [sol_mint, usdc_mint] {
+        env.update_bank_liquidation(&mint, 5_000, 500, 2_000, 8_000, 1_000).await.unwrap();
+    }
+
+    let borrower: TestUser = env.create_borrower(500_000, 1_000).await;
+    let liquidator: TestUser = env.create_user().await;
+    env.borrow(&borrower, &usdc_mint, 100_000).await.unwrap();
+
+    (env, borrower, liquidator)
+}
+
+#[tokio::test]
+async fn liquidate_healthy_position_fails() {
+    let (mut env, borrower, liquidator) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::NotUnderCollaterized);
+}
+
+#[tokio::test]
+async fn liquidate_without_debt_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let borrower: TestUser = env.create_user().await;
+    let liquidator: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&borrower, &sol_mint, 10).await.unwrap();
+
+    assert_error(env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::NotUnderCollaterized);
+}
+
+#[tokio::test]
+async fn liquidate_after_collateral_price_drop() {
+    let (mut env, borrower, liquidator) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // 10 SOL at 50 is worth less than the 1_000 USDC borrowed.
+    env.set_price(SOL_USB_FEED_ID, 50).await;
+
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+}
+
+#[tokio::test]
+async fn liquidate_empty_account_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let borrower: TestUser = env.create_user().await;
+    let liquidator: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::NotUnderCollaterized);
+}
+
+#[tokio::test]
+async fn liquidate_with_same_mint_fails() {
+    let (mut env, borrower, liquidator) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.liquidate(&liquidator, &borrower.key(), &usdc_mint, &usdc_mint).await, ErrorCode::InvalidLiquidationMints);
+}
+
+#[tokio::test]
+async fn liquidation_bonus_is_near_minimum_just_below_threshold() {
+    let (mut env, borrower, liquidator) = setup_with_bonus().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // Health factor 0.99: 50_000 USDC buys 505 SOL, plus a 5.75% bonus.
+    env.set_price(SOL_USB_FEED_ID, 99).await;
+    let usdc_before: u64 = env.token_balance(&liquidator.usdc_token_account).await;
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&liquidator.sol_token_account).await, STARTING_BALANCE + 534);
+    assert_eq!(usdc_before - env.token_balance(&liquidator.usdc_token_account).await, 50_000);
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol, 1_000 - 534);
+    assert_eq!(user.borrowed_usdc, 50_000);
+}
+
+#[tokio::test]
+async fn liquidation_bonus_grows_as_health_drops() {
+    let (mut env, borrower, liquidator) = setup_with_bonus().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // Health factor 0.9, halfway to 0.8: 50_000 USDC buys 555 SOL, plus a 12.5% bonus.
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&liquidator.sol_token_account).await, STARTING_BALANCE + 624);
+}
+
+#[tokio::test]
+async fn liquidation_bonus_grows_while_position_stays_liquidatable() {
+    let (mut env, borrower, liquidator) = setup_with_bonus().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 99).await;
+    env.flag_liquidatable(&borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    env.warp_seconds(500).await;
+    env.set_price(SOL_USB_FEED_ID, 99).await;
+    env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+
+    // Half the ramp on top of the health factor's 5%: 505 SOL plus a 13.25% bonus.
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&liquidator.sol_token_account).await, STARTING_BALANCE + 571);
+}
+
+#[tokio::test]
+async fn liquidation_short_of_collateral_repays_what_the_collateral_covers() {
+    let (mut env, borrower, liquidator) = setup_with_bonus().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // At 40, 50_000 USDC buys 1_250 SOL plus a 20% bonus, but only 1_000 SOL are left. They cover 33_333 USDC at
+    // the bonus, which is all the liquidator pays.
+    env.set_price(SOL_USB_FEED_ID, 40).await;
+    let usdc_before: u64 = env.token_balance(&liquidator.usdc_token_account).await;
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&liquidator.sol_token_account).await, STARTING_BALANCE + 1_000);
+    assert_eq!(usdc_before - env.token_balance(&liquidator.usdc_token_account).await, 33_333);
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol_shares, 0);
+    assert_eq!(user.borrowed_usdc, 100_000 - 33_333);
+}
+
+#[tokio::test]
+async fn flag_liquidatable_tracks_when_position_became_liquidatable() {
+    let (mut env, borrower, _) = setup_with_bonus().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.flag_liquidatable(&borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.liquidatable_since, 0);
+
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    let flagged_at: i64 = env.clock().await.unix_timestamp;
+    env.flag_liquidatable(&borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.liquidatable_since, flagged_at);
+
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+    env.flag_liquidatable(&borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.liquidatable_since, 0);
+}
+
+#[tokio::test]
+async fn repay_clears_liquidatable_flag() {
+    let (mut env, borrower, _) = setup_with_bonus().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.set_price(SOL_USB_FEED_ID, 99).await;
+    env.flag_liquidatable(&borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    assert_ne!(env.user(&borrower.key()).await.liquidatable_since, 0);
+
+    // Paying down 2_000 brings 1_000 SOL at 99 back over the debt, and the bonus clock needs a fresh flag.
+    env.repay(&borrower, &usdc_mint, 2_000).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.liquidatable_since, 0);
+}
+
+#[tokio::test]
+async fn liquidation_that_restores_health_clears_liquidatable_flag() {
+    let (mut env, borrower, liquidator) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    for mint in [sol_mint, usdc_mint] {
+        env.update_bank_liquidation(&mint, 10_000, 0, 0, 0, 0).await.unwrap();
+    }
+
+    env.set_price(SOL_USB_FEED_ID, 99).await;
+    env.flag_liquidatable(&borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.liquidatable_since, 0);
+}
+
+#[tokio::test]
+async fn update_bank_liquidation_rejects_min_bonus_above_max() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    assert_error(env.update_bank_liquidation(&sol_mint, 5_000, 2_000, 500, 8_000, 0).await, ErrorCode::InvalidLiquidationConfig);
+}
+
+#[tokio::test]
+async fn update_bank_liquidation_rejects_max_bonus_of_the_whole_collateral() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    assert_error(env.update_bank_liquidation(&sol_mint, 5_000, 0, BASIS_POINTS, 8_000, 0).await, ErrorCode::InvalidLiquidationConfig);
+    env.update_bank_liquidation(&sol_mint, 5_000, 0, BASIS_POINTS - 1, 8_000, 0).await.unwrap();
+}
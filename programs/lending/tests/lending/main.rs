@@ -0,0 +1,29 @@
+//! In-process integration tests for the lending program.
+//!
+//! Each module drives one instruction through `solana-program-test`, using the helpers in `common`
+//! to create mints, mock Pyth `PriceUpdateV2` accounts and warp the clock.
+
+mod common;
+
+mod admin;
+mod auction;
+mod borrow;
+mod delegation;
+mod deleverage;
+mod deposit;
+mod emode;
+mod insurance;
+mod isolation;
+mod leverage;
+mod liquidate;
+mod market;
+mod migrate;
+mod native;
+mod outflow_limit;
+mod repay;
+mod rewards;
+mod stable_rate;
+mod swap_collateral;
+mod term_loan;
+mod token_2022;
+mod withdraw;
@@ -0,0 +1,82 @@
+use anchor_lang::prelude::*;
+use lending::{constants::MAX_AGE, error::ErrorCode, state::LendingMarket};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::common::*;
+
+#[tokio::test]
+async fn init_market_records_owner_and_settings() {
+    let mut env: TestEnv = TestEnv::new().await;
+
+    let market: LendingMarket = env.account(&env.market.pubkey()).await;
+    assert_eq!(market.owner, env.ctx.payer.pubkey());
+    assert_eq!(market.quote_currency, QUOTE_CURRENCY);
+    assert!(!market.paused);
+    assert_eq!(market.max_price_age, MAX_AGE);
+}
+
+#[tokio::test]
+async fn update_market_changes_settings() {
+    let mut env: TestEnv = TestEnv::new().await;
+    let owner: Keypair = env.payer();
+
+    env.update_market(&owner, true, 30).await.unwrap();
+
+    let market: LendingMarket = env.account(&env.market.pubkey()).await;
+    assert!(market.paused);
+    assert_eq!(market.max_price_age, 30);
+}
+
+#[tokio::test]
+async fn update_market_by_non_owner_fails() {
+    let mut env: TestEnv = TestEnv::new().await;
+    let stranger: TestUser = env.create_user().await;
+
+    assert_error(env.update_market(&stranger.keypair, true, MAX_AGE).await, ErrorCode::Unauthorized);
+}
+
+#[tokio::test]
+async fn paused_market_blocks_deposits_but_not_repayments() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let lender: TestUser = env.create_user().await;
+    let borrower: TestUser = env.create_user().await;
+    let owner: Keypair = env.payer();
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&lender, &usdc_mint, 100_000).await.unwrap();
+    env.deposit(&borrower, &sol_mint, 10).await.unwrap();
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    env.update_market(&owner, true, MAX_AGE).await.unwrap();
+
+    assert_error(env.deposit(&lender, &usdc_mint, 1_000).await, ErrorCode::MarketPaused);
+    assert_error(env.withdraw(&lender, &usdc_mint, 1_000).await, ErrorCode::MarketPaused);
+    assert_error(env.borrow(&borrower, &usdc_mint, 1_000).await, ErrorCode::MarketPaused);
+    env.repay(&borrower, &usdc_mint, 1_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn markets_keep_separate_banks_for_the_same_mint() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&user, &mint, 1_000).await.unwrap();
+    let first_treasury: Pubkey = env.treasury_address(&mint);
+
+    // Point the environment at a second market and give the same wallet an account there.
+    let second: Keypair = Keypair::new();
+    env.init_market(&second).await.unwrap();
+    env.market = second;
+    env.init_bank(&mint, LIQUIDATION_THRESHOLD, MAX_LTV).await.unwrap();
+    env.init_user(&user.keypair).await.unwrap();
+
+    env.deposit(&user, &mint, 250).await.unwrap();
+
+    assert_ne!(env.treasury_address(&mint), first_treasury);
+    assert_eq!(env.token_balance(&first_treasury).await, 1_000);
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 250);
+    assert_eq!(env.bank(&mint).await.total_deposits, 250);
+    assert_eq!(env.user(&user.key()).await.deposited_sol, 250);
+}
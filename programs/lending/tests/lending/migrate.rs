@@ -0,0 +1,187 @@
+use anchor_lang::{prelude::*, Discriminator};
+use anchor_lang::solana_program::{program_pack::Pack, system_instruction};
+use anchor_spl::token::spl_token;
+use lending::{error::ErrorCode, state::{Bank, User}};
+use solana_sdk::{account::Account as SolanaAccount, signature::Keypair, signer::Signer};
+
+use crate::common::*;
+
+// `Bank` and `User` as they were laid out before markets existed.
+#[derive(AnchorSerialize, Default)]
+struct LegacyBank {
+    authority: Pubkey,
+    mint_address: Pubkey,
+    total_deposits: u64,
+    total_deposit_shares: u64,
+    total_borrowed: u64,
+    total_borrowed_shares: u64,
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+    liquidation_close_factor: u64,
+    max_ltv: u64,
+    last_updated: u64,
+    interest_rate: u64,
+}
+
+#[derive(AnchorSerialize, Default)]
+struct LegacyUser {
+    owner: Pubkey,
+    deposited_sol: u64,
+    deposited_sol_shares: u64,
+    borrowed_sol: u64,
+    borrowed_sol_shares: u64,
+    deposited_usdc: u64,
+    deposited_usdc_shares: u64,
+    borrowed_usdc: u64,
+    borrowed_usdc_shares: u64,
+    usdc_address: Pubkey,
+    last_updated: i64,
+    last_updated_borrow: i64,
+}
+
+// Size of banks and users created when markets were added, before any of the later fields.
+const MARKET_ACCOUNT_LEN: usize = 8 + 32 * 3 + 8 * 10;
+
+fn write_account(env: &mut TestEnv, address: &Pubkey, owner: Pubkey, data: Vec<u8>) {
+    let account = SolanaAccount {
+        lamports: 1_000_000_000,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.ctx.set_account(address, &account.into());
+}
+
+// Writes `value` at `address` as if the program had created it there before markets existed.
+fn write_legacy_account<T: AnchorSerialize>(env: &mut TestEnv, address: &Pubkey, discriminator: [u8; 8], value: &T) {
+    let mut data: Vec<u8> = discriminator.to_vec();
+    value.serialize(&mut data).unwrap();
+    write_account(env, address, lending::ID, data);
+}
+
+// Writes a treasury at the legacy address holding `amount` of `mint`.
+fn write_legacy_treasury(env: &mut TestEnv, mint: &Pubkey, amount: u64) {
+    let address: Pubkey = legacy_treasury_address(mint);
+    let token_account = spl_token::state::Account {
+        mint: *mint,
+        owner: address,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    };
+    let mut data: Vec<u8> = vec![0; spl_token::state::Account::LEN];
+    token_account.pack_into_slice(&mut data);
+    write_account(env, &address, spl_token::ID, data);
+}
+
+// Cuts the account at `address` back to the size it had before the latest fields were added.
+async fn truncate_account(env: &mut TestEnv, address: &Pubkey) {
+    let mut account: SolanaAccount = env.ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+    account.data.truncate(MARKET_ACCOUNT_LEN);
+    env.ctx.set_account(address, &account.into());
+}
+
+#[tokio::test]
+async fn migrate_bank_moves_state_and_tokens_into_market() {
+    let mut env: TestEnv = TestEnv::new().await;
+    let market: Pubkey = env.market.pubkey();
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    let legacy_bank = LegacyBank {
+        authority: env.ctx.payer.pubkey(),
+        mint_address: mint,
+        total_deposits: 500,
+        total_deposit_shares: 500,
+        // Legacy banks weighted collateral by plain multipliers.
+        liquidation_threshold: 1,
+        max_ltv: 1,
+        ..LegacyBank::default()
+    };
+    write_legacy_account(&mut env, &legacy_bank_address(&mint), Bank::DISCRIMINATOR, &legacy_bank);
+    write_legacy_treasury(&mut env, &mint, 500);
+
+    env.migrate_bank(&mint).await.unwrap();
+
+    let bank: Bank = env.bank(&mint).await;
+    assert_eq!(bank.market, market);
+    assert_eq!(bank.mint_address, mint);
+    assert_eq!(bank.total_deposits, 500);
+    assert_eq!(bank.total_deposit_shares, 500);
+    assert_eq!(bank.max_ltv, MAX_LTV);
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 500);
+    assert!(env.ctx.banks_client.get_account(legacy_bank_address(&mint)).await.unwrap().is_none());
+    assert!(env.ctx.banks_client.get_account(legacy_treasury_address(&mint)).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn migrate_bank_by_non_owner_fails() {
+    let mut env: TestEnv = TestEnv::new().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+    write_legacy_account(&mut env, &legacy_bank_address(&mint), Bank::DISCRIMINATOR, &LegacyBank { mint_address: mint, ..LegacyBank::default() });
+    write_legacy_treasury(&mut env, &mint, 0);
+
+    let stranger: TestUser = env.create_user().await;
+    env.ctx.payer = stranger.keypair.insecure_clone();
+
+    assert_error(env.migrate_bank(&mint).await, ErrorCode::Unauthorized);
+}
+
+#[tokio::test]
+async fn migrate_bank_from_user_account_fails() {
+    let mut env: TestEnv = TestEnv::new().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+    write_legacy_account(&mut env, &legacy_bank_address(&mint), User::DISCRIMINATOR, &LegacyUser::default());
+    write_legacy_treasury(&mut env, &mint, 0);
+
+    assert_error(env.migrate_bank(&mint).await, ErrorCode::InvalidLegacyAccount);
+}
+
+#[tokio::test]
+async fn migrate_user_moves_state_into_market() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let market: Pubkey = env.market.pubkey();
+    let owner: Keypair = Keypair::new();
+    let legacy_address: Pubkey = legacy_user_address(&owner.pubkey());
+
+    let payer: Pubkey = env.ctx.payer.pubkey();
+    env.process(&[system_instruction::transfer(&payer, &owner.pubkey(), 1_000_000_000)], &[]).await.unwrap();
+
+    let legacy_user = LegacyUser {
+        owner: owner.pubkey(),
+        deposited_sol: 42,
+        deposited_sol_shares: 42,
+        usdc_address: env.usdc_mint.pubkey(),
+        ..LegacyUser::default()
+    };
+    write_legacy_account(&mut env, &legacy_address, User::DISCRIMINATOR, &legacy_user);
+
+    env.migrate_user(&owner).await.unwrap();
+
+    let user: User = env.user(&owner.pubkey()).await;
+    assert_eq!(user.owner, owner.pubkey());
+    assert_eq!(user.market, market);
+    assert_eq!(user.deposited_sol, 42);
+    assert_eq!(user.deposited_sol_shares, 42);
+    assert_eq!(user.usdc_address, env.usdc_mint.pubkey());
+    assert!(env.ctx.banks_client.get_account(legacy_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn realloc_grows_accounts_created_before_the_latest_fields() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let market: Pubkey = env.market.pubkey();
+
+    env.deposit(&user, &sol_mint, 100).await.unwrap();
+    truncate_account(&mut env, &bank_address(&market, &sol_mint)).await;
+    truncate_account(&mut env, &user_address(&market, &user.key())).await;
+
+    env.realloc_bank(&sol_mint).await.unwrap();
+    env.realloc_user(&user.keypair).await.unwrap();
+
+    assert_eq!(env.bank(&sol_mint).await.total_deposits, 100);
+    assert_eq!(env.user(&user.key()).await.deposited_sol, 100);
+    env.withdraw(&user, &sol_mint, 100).await.unwrap();
+}
@@ -0,0 +1,54 @@
+use anchor_spl::token::spl_token::native_mint;
+use lending::{error::ErrorCode, state::{Bank, User}};
+
+use crate::common::*;
+
+const ONE_SOL: u64 = 1_000_000_000;
+
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    env.init_bank(&native_mint::ID, LIQUIDATION_THRESHOLD, MAX_LTV).await.unwrap();
+    let user: TestUser = env.create_user().await;
+    (env, user)
+}
+
+#[tokio::test]
+async fn deposit_native_wraps_lamports_into_the_sol_bank() {
+    let (mut env, user) = setup().await;
+
+    let lamports_before: u64 = env.lamports(&user.key()).await;
+    env.deposit_native(&user, ONE_SOL).await.unwrap();
+
+    // The payer covers fees and the wrapper's rent is refunded, so only the deposit leaves the wallet.
+    assert_eq!(env.lamports(&user.key()).await, lamports_before - ONE_SOL);
+    assert_eq!(env.token_balance(&env.treasury_address(&native_mint::ID)).await, ONE_SOL);
+    assert!(env.ctx.banks_client.get_account(wrapped_sol_address(&user.key())).await.unwrap().is_none());
+
+    let bank: Bank = env.bank(&native_mint::ID).await;
+    let account: User = env.user(&user.key()).await;
+    assert_eq!(bank.total_deposits, ONE_SOL);
+    assert_eq!(account.deposited_sol, ONE_SOL);
+    assert_eq!(account.deposited_sol_shares, ONE_SOL);
+}
+
+#[tokio::test]
+async fn withdraw_native_unwraps_to_plain_sol() {
+    let (mut env, user) = setup().await;
+    env.deposit_native(&user, ONE_SOL).await.unwrap();
+
+    let lamports_before: u64 = env.lamports(&user.key()).await;
+    env.withdraw_native(&user, ONE_SOL / 4).await.unwrap();
+
+    assert_eq!(env.lamports(&user.key()).await, lamports_before + ONE_SOL / 4);
+    assert_eq!(env.token_balance(&env.treasury_address(&native_mint::ID)).await, ONE_SOL - ONE_SOL / 4);
+    assert!(env.ctx.banks_client.get_account(wrapped_sol_address(&user.key())).await.unwrap().is_none());
+    assert_eq!(env.user(&user.key()).await.deposited_sol, ONE_SOL - ONE_SOL / 4);
+}
+
+#[tokio::test]
+async fn withdraw_native_more_than_deposited_fails() {
+    let (mut env, user) = setup().await;
+    env.deposit_native(&user, ONE_SOL).await.unwrap();
+
+    assert_error(env.withdraw_native(&user, 2 * ONE_SOL).await, ErrorCode::InsufficientFunds);
+}
@@ -0,0 +1,117 @@
+use anchor_lang::prelude::*;
+use lending::{constants::SOL_USB_FEED_ID, error::ErrorCode};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+// A USDC bank holding 100_000 that lets at most 1_000 out over 100 seconds.
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let lender: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&lender, &usdc_mint, 100_000).await.unwrap();
+    env.update_bank_outflow_limit(&usdc_mint, 100, 1_000, false).await.unwrap();
+
+    (env, lender)
+}
+
+#[tokio::test]
+async fn withdraw_over_limit_fails() {
+    let (mut env, lender) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.withdraw(&lender, &usdc_mint, 600).await.unwrap();
+
+    assert_error(env.withdraw(&lender, &usdc_mint, 401).await, ErrorCode::OutflowRateLimited);
+    env.withdraw(&lender, &usdc_mint, 400).await.unwrap();
+}
+
+#[tokio::test]
+async fn deposits_offset_outflows() {
+    let (mut env, lender) = setup().await;
+    let depositor: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.withdraw(&lender, &usdc_mint, 600).await.unwrap();
+    env.deposit(&depositor, &usdc_mint, 500).await.unwrap();
+
+    assert_error(env.withdraw(&lender, &usdc_mint, 901).await, ErrorCode::OutflowRateLimited);
+    env.withdraw(&lender, &usdc_mint, 900).await.unwrap();
+}
+
+#[tokio::test]
+async fn outflows_leave_the_rolling_window_gradually() {
+    let (mut env, lender) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.withdraw(&lender, &usdc_mint, 1_000).await.unwrap();
+
+    // Halfway through the next window, half of the last one still counts.
+    env.warp_seconds(150).await;
+    assert_error(env.withdraw(&lender, &usdc_mint, 501).await, ErrorCode::OutflowRateLimited);
+    env.withdraw(&lender, &usdc_mint, 500).await.unwrap();
+
+    env.warp_seconds(150).await;
+    env.withdraw(&lender, &usdc_mint, 1_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn borrow_over_usd_limit_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let borrower: TestUser = env.create_borrower(100_000, 100).await;
+
+    // USD amounts have as many decimals as USDC, so at a price of 1 the limit is in raw USDC.
+    env.update_bank_outflow_limit(&usdc_mint, 100, 1_500, true).await.unwrap();
+
+    env.borrow(&borrower, &usdc_mint, 1_500).await.unwrap();
+    assert_error(env.borrow(&borrower, &usdc_mint, 1).await, ErrorCode::OutflowRateLimited);
+
+    env.repay(&borrower, &usdc_mint, 400).await.unwrap();
+    env.borrow(&borrower, &usdc_mint, 400).await.unwrap();
+}
+
+#[tokio::test]
+async fn usd_limit_accounts_for_exponent_and_decimals() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let lender: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&lender, &sol_mint, 100_000).await.unwrap();
+
+    // SOL at 150 with 8 price decimals, so 10_000 raw units of 9-decimal SOL are worth $0.0015.
+    env.set_price_with_exponent(SOL_USB_FEED_ID, 15_000_000_000, -8).await;
+    env.update_bank_outflow_limit(&sol_mint, 100, 1_500, true).await.unwrap();
+
+    env.withdraw(&lender, &sol_mint, 10_000).await.unwrap();
+    assert_error(env.withdraw(&lender, &sol_mint, 7).await, ErrorCode::OutflowRateLimited);
+}
+
+#[tokio::test]
+async fn every_borrow_counts_towards_the_limit() {
+    let (mut env, lender) = setup().await;
+    let borrower: TestUser = env.create_user().await;
+    let delegate: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&borrower, &sol_mint, 100).await.unwrap();
+    env.approve_borrow_delegation(&borrower, &delegate.key(), &usdc_mint, 1_000, i64::MAX).await.unwrap();
+
+    env.borrow_fixed(&borrower, &usdc_mint, 300, 1_000).await.unwrap();
+    env.borrow_stable(&borrower, &usdc_mint, 300).await.unwrap();
+    env.borrow_delegated(&delegate, &borrower.key(), &usdc_mint, 300).await.unwrap();
+
+    assert_error(env.withdraw(&lender, &usdc_mint, 101).await, ErrorCode::OutflowRateLimited);
+    env.withdraw(&lender, &usdc_mint, 100).await.unwrap();
+}
+
+#[tokio::test]
+async fn update_outflow_limit_with_negative_window_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.update_bank_outflow_limit(&usdc_mint, -1, 1_000, false).await, ErrorCode::InvalidOutflowConfig);
+}
@@ -0,0 +1,103 @@
+use anchor_lang::prelude::*;
+use lending::{constants::SECONDS_PER_YEAR, error::ErrorCode};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+async fn setup(borrowed: u64) -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow(&borrower, &usdc_mint, borrowed).await.unwrap();
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn repay_clears_debt() {
+    let (mut env, borrower) = setup(1_000).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let treasury_before: u64 = env.token_balance(&env.treasury_address(&usdc_mint)).await;
+
+    env.repay(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    assert_eq!(env.token_balance(&borrower.usdc_token_account).await, STARTING_BALANCE);
+    assert_eq!(env.token_balance(&env.treasury_address(&usdc_mint)).await, treasury_before + 1_000);
+    assert_eq!(env.user(&borrower.key()).await.borrowed_usdc, 0);
+}
+
+#[tokio::test]
+async fn partial_repay_reduces_debt() {
+    let (mut env, borrower) = setup(1_000).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.repay(&borrower, &usdc_mint, 400).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.borrowed_usdc, 600);
+}
+
+#[tokio::test]
+async fn repay_more_than_borrowed_fails() {
+    let (mut env, borrower) = setup(1_000).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.repay(&borrower, &usdc_mint, 1_001).await, ErrorCode::OverRepay);
+    assert_eq!(env.user(&borrower.key()).await.borrowed_usdc, 1_000);
+}
+
+#[tokio::test]
+async fn repay_max_clears_the_whole_debt() {
+    let (mut env, borrower) = setup(1_000).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.repay(&borrower, &usdc_mint, u64::MAX).await.unwrap();
+
+    let account = env.user(&borrower.key()).await;
+    assert_eq!(account.borrowed_usdc, 0);
+    assert_eq!(account.borrowed_usdc_shares, 0);
+    assert_eq!(env.bank(&usdc_mint).await.total_borrowed_shares, 0);
+    assert_eq!(env.token_balance(&borrower.usdc_token_account).await, STARTING_BALANCE);
+}
+
+#[tokio::test]
+async fn repay_for_pays_down_another_users_debt() {
+    let (mut env, borrower) = setup(1_000).await;
+    let helper: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.repay_for(&helper, &borrower.key(), &usdc_mint, 400).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.borrowed_usdc, 600);
+    assert_eq!(env.token_balance(&helper.usdc_token_account).await, STARTING_BALANCE - 400);
+    assert_eq!(env.token_balance(&borrower.usdc_token_account).await, STARTING_BALANCE + 1_000);
+}
+
+#[tokio::test]
+async fn repay_for_more_than_owed_fails() {
+    let (mut env, borrower) = setup(1_000).await;
+    let helper: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.repay_for(&helper, &borrower.key(), &usdc_mint, 1_001).await, ErrorCode::OverRepay);
+}
+
+#[tokio::test]
+async fn repays_accrue_bank_interest_once() {
+    let (mut env, borrower) = setup(1_000).await;
+    let helper: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // Variable debt pays 10% a year.
+    env.update_bank_rates(&usdc_mint, 1_000, 0).await.unwrap();
+    env.warp_seconds(SECONDS_PER_YEAR as i64).await;
+
+    env.repay(&borrower, &usdc_mint, 100).await.unwrap();
+    assert_eq!(env.bank(&usdc_mint).await.total_borrowed, 1_000);
+
+    // A second repayment in the same second doesn't charge the year again.
+    env.repay_for(&helper, &borrower.key(), &usdc_mint, 100).await.unwrap();
+    let bank = env.bank(&usdc_mint).await;
+    assert_eq!(bank.total_borrowed, 900);
+    assert_eq!(bank.last_updated, env.clock().await.unix_timestamp as u64);
+}
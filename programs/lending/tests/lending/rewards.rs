@@ -0,0 +1,113 @@
+use anchor_lang::prelude::*;
+use lending::error::ErrorCode;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::common::*;
+
+const EMISSION_RATE: u64 = 10;
+const DURATION: i64 = 1_000;
+const VAULT_BALANCE: u64 = 100_000;
+
+// Stream 0 of the USDC bank pays `EMISSION_RATE` reward tokens a second to depositors for `DURATION` seconds.
+async fn setup() -> (TestEnv, Pubkey) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let owner: Pubkey = env.ctx.payer.pubkey();
+
+    let reward_mint: Keypair = Keypair::new();
+    env.create_mint(&reward_mint, USDC_DECIMALS, &[]).await;
+    env.fund(&owner, &reward_mint.pubkey(), VAULT_BALANCE).await;
+
+    let start: i64 = env.clock().await.unix_timestamp;
+    env.init_reward_stream(&usdc_mint, &reward_mint.pubkey(), 0, false, EMISSION_RATE, start, start + DURATION).await.unwrap();
+    env.fund_rewards(&usdc_mint, &reward_mint.pubkey(), VAULT_BALANCE).await.unwrap();
+
+    (env, reward_mint.pubkey())
+}
+
+#[tokio::test]
+async fn depositors_earn_rewards_pro_rata() {
+    let (mut env, reward_mint) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let small: TestUser = env.create_user().await;
+    let large: TestUser = env.create_user().await;
+
+    env.deposit(&small, &usdc_mint, 1_000).await.unwrap();
+    env.deposit(&large, &usdc_mint, 3_000).await.unwrap();
+    env.warp_seconds(100).await;
+
+    env.claim_rewards(&small, &usdc_mint, &reward_mint, 0).await.unwrap();
+    env.claim_rewards(&large, &usdc_mint, &reward_mint, 0).await.unwrap();
+
+    assert_eq!(env.token_balance(&env.token_account_address(&small.key(), &reward_mint)).await, 250);
+    assert_eq!(env.token_balance(&env.token_account_address(&large.key(), &reward_mint)).await, 750);
+}
+
+#[tokio::test]
+async fn rewards_stop_at_stream_end() {
+    let (mut env, reward_mint) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let user: TestUser = env.create_user().await;
+
+    env.deposit(&user, &usdc_mint, 1_000).await.unwrap();
+    env.warp_seconds(DURATION * 2).await;
+
+    env.claim_rewards(&user, &usdc_mint, &reward_mint, 0).await.unwrap();
+
+    assert_eq!(env.token_balance(&env.token_account_address(&user.key(), &reward_mint)).await, EMISSION_RATE * DURATION as u64);
+    assert_eq!(env.user(&user.key()).await.usdc_rewards.positions[0].earned, 0);
+}
+
+#[tokio::test]
+async fn rewards_accrue_before_shares_change() {
+    let (mut env, reward_mint) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let user: TestUser = env.create_user().await;
+
+    env.deposit(&user, &usdc_mint, 1_000).await.unwrap();
+    env.warp_seconds(100).await;
+    env.withdraw(&user, &usdc_mint, u64::MAX).await.unwrap();
+
+    // Nothing is earned once the deposit is gone.
+    env.warp_seconds(100).await;
+    env.claim_rewards(&user, &usdc_mint, &reward_mint, 0).await.unwrap();
+
+    assert_eq!(env.token_balance(&env.token_account_address(&user.key(), &reward_mint)).await, EMISSION_RATE * 100);
+}
+
+#[tokio::test]
+async fn claim_from_unused_stream_fails() {
+    let (mut env, reward_mint) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let user: TestUser = env.create_user().await;
+
+    assert_error(env.claim_rewards(&user, &usdc_mint, &reward_mint, 1).await, ErrorCode::InvalidRewardStream);
+}
+
+#[tokio::test]
+async fn init_reward_stream_in_used_slot_fails() {
+    let (mut env, reward_mint) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let start: i64 = env.clock().await.unix_timestamp;
+
+    assert_error(
+        env.init_reward_stream(&usdc_mint, &reward_mint, 0, true, EMISSION_RATE, start, start + DURATION).await,
+        ErrorCode::RewardStreamInUse,
+    );
+}
+
+#[tokio::test]
+async fn close_user_with_unclaimed_rewards_fails() {
+    let (mut env, reward_mint) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    let user: TestUser = env.create_user().await;
+
+    env.deposit(&user, &usdc_mint, 1_000).await.unwrap();
+    env.warp_seconds(100).await;
+    env.withdraw(&user, &usdc_mint, u64::MAX).await.unwrap();
+
+    assert_error(env.close_user(&user.keypair).await, ErrorCode::UnclaimedRewards);
+
+    env.claim_rewards(&user, &usdc_mint, &reward_mint, 0).await.unwrap();
+    env.close_user(&user.keypair).await.unwrap();
+}
@@ -0,0 +1,144 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{SECONDS_PER_YEAR, SOL_USB_FEED_ID}, error::ErrorCode};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+const BASE_RATE_BPS: u64 = 100;
+const RATE_SLOPE_BPS: u64 = 1_000;
+const STABLE_PREMIUM_BPS: u64 = 300;
+const YEAR: i64 = SECONDS_PER_YEAR as i64;
+
+async fn setup(rebalance_utilization_bps: u64, rebalance_down_delta_bps: u64) -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_rates(&usdc_mint, BASE_RATE_BPS, RATE_SLOPE_BPS).await.unwrap();
+    env.update_bank_stable_rates(&usdc_mint, STABLE_PREMIUM_BPS, rebalance_utilization_bps, rebalance_down_delta_bps).await.unwrap();
+    let borrower: TestUser = env.create_borrower(100_000, 100).await;
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn borrow_stable_averages_rate_across_borrows() {
+    let (mut env, borrower) = setup(5_000, 100).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // The first borrow is taken at 0% utilization, the second at 1%.
+    env.borrow_stable(&borrower, &usdc_mint, 1_000).await.unwrap();
+    env.borrow_stable(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.usdc_stable_debt.principal, 2_000);
+    assert_eq!(user.usdc_stable_debt.rate_bps, 405);
+
+    let bank = env.bank(&usdc_mint).await;
+    assert_eq!(bank.total_stable_borrowed, 2_000);
+    assert_eq!(bank.average_stable_rate_bps, 405);
+}
+
+#[tokio::test]
+async fn rebalance_up_once_utilization_crosses_threshold() {
+    let (mut env, borrower) = setup(5_000, 100).await;
+    let keeper: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_stable(&borrower, &usdc_mint, 1_000).await.unwrap();
+    assert_error(env.rebalance_stable_rate(&keeper, &borrower.key(), &usdc_mint).await, ErrorCode::RebalanceNotAllowed);
+
+    // A variable-rate borrow of 60_000 puts the bank at 61% utilization.
+    let other: TestUser = env.create_user().await;
+    env.deposit(&other, &sol_mint, 1_000).await.unwrap();
+    env.borrow(&other, &usdc_mint, 60_000).await.unwrap();
+
+    env.rebalance_stable_rate(&keeper, &borrower.key(), &usdc_mint).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.usdc_stable_debt.rate_bps, 1_010);
+    assert_eq!(env.bank(&usdc_mint).await.average_stable_rate_bps, 1_010);
+}
+
+#[tokio::test]
+async fn rebalance_down_when_rate_exceeds_current_by_delta() {
+    let (mut env, borrower) = setup(5_000, 100).await;
+    let keeper: TestUser = env.create_user().await;
+    let other: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&other, &sol_mint, 1_000).await.unwrap();
+    env.borrow(&other, &usdc_mint, 60_000).await.unwrap();
+    env.borrow_stable(&borrower, &usdc_mint, 1_000).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.usdc_stable_debt.rate_bps, 1_000);
+
+    env.repay(&other, &usdc_mint, u64::MAX).await.unwrap();
+    env.rebalance_stable_rate(&keeper, &borrower.key(), &usdc_mint).await.unwrap();
+
+    assert_eq!(env.user(&borrower.key()).await.usdc_stable_debt.rate_bps, 410);
+}
+
+#[tokio::test]
+async fn repay_stable_pays_interest_to_depositors() {
+    let (mut env, borrower) = setup(5_000, 100).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_stable(&borrower, &usdc_mint, 1_000).await.unwrap();
+    let deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+    env.warp_seconds(YEAR).await;
+
+    let balance_before: u64 = env.token_balance(&borrower.usdc_token_account).await;
+    env.repay_stable(&borrower, &usdc_mint, u64::MAX).await.unwrap();
+
+    assert_eq!(balance_before - env.token_balance(&borrower.usdc_token_account).await, 1_040);
+
+    let bank = env.bank(&usdc_mint).await;
+    assert_eq!(bank.total_deposits, deposits_before + 40);
+    assert_eq!(bank.total_stable_borrowed, 0);
+    assert_eq!(env.user(&borrower.key()).await.usdc_stable_debt.owed, 0);
+}
+
+#[tokio::test]
+async fn stable_debt_uses_up_borrow_capacity() {
+    let (mut env, borrower) = setup(5_000, 100).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // 100 SOL at 150 back 15_000 USDC, 10_000 of which the stable borrow takes.
+    env.borrow_stable(&borrower, &usdc_mint, 10_000).await.unwrap();
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 5_001).await, ErrorCode::OverBorrowableAmount);
+    env.borrow(&borrower, &usdc_mint, 5_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn withdraw_leaving_stable_debt_unbacked_fails() {
+    let (mut env, borrower) = setup(5_000, 100).await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_stable(&borrower, &usdc_mint, 10_000).await.unwrap();
+
+    assert_error(env.withdraw(&borrower, &sol_mint, 34).await, ErrorCode::HealthFactorTooLow);
+    env.withdraw(&borrower, &sol_mint, 33).await.unwrap();
+}
+
+#[tokio::test]
+async fn stable_debt_is_liquidatable() {
+    let (mut env, borrower) = setup(5_000, 100).await;
+    let liquidator: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_liquidation(&usdc_mint, 5_000, 0, 0, 0, 0).await.unwrap();
+    env.borrow_stable(&borrower, &usdc_mint, 10_000).await.unwrap();
+
+    // At 90, 100 SOL no longer cover the 10_000 USDC.
+    env.set_price(SOL_USB_FEED_ID, 90).await;
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.usdc_stable_debt.owed, 5_000);
+    assert_eq!(user.usdc_stable_debt.principal, 5_000);
+    assert_eq!(env.bank(&usdc_mint).await.total_stable_borrowed, 5_000);
+    assert_eq!(env.token_balance(&env.token_account_address(&liquidator.key(), &sol_mint)).await, STARTING_BALANCE + 55);
+}
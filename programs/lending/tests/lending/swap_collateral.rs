@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use lending::error::ErrorCode;
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.init_pool(SOL_PRICE as u64, USDC_PRICE as u64, 100_000).await.unwrap();
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    env.borrow(&borrower, &usdc_mint, 500).await.unwrap();
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn swap_collateral_moves_deposit_to_other_bank() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    let usdc_deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+
+    // All 10 SOL are sold for 1_500 USDC, which still covers the 500 borrowed.
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &sol_mint, &usdc_mint, 10, 1_500);
+    env.swap_collateral(&borrower, &sol_mint, u64::MAX, swap).await.unwrap();
+
+    let user = env.user(&borrower.key()).await;
+    assert_eq!(user.deposited_sol_shares, 0);
+    assert_eq!(user.deposited_usdc, 1_500);
+    assert_eq!(user.borrowed_usdc, 500);
+    assert_eq!(env.bank(&sol_mint).await.total_deposits, 0);
+    assert_eq!(env.bank(&usdc_mint).await.total_deposits, usdc_deposits_before + 1_500);
+}
+
+#[tokio::test]
+async fn swap_collateral_into_unhealthy_position_fails() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // The pool prices SOL at 30 USDC, so the 10 SOL only fetch 300 against 500 of debt.
+    env.update_pool_rate(30, 1).await.unwrap();
+
+    let swap: Instruction = env.swap_instruction(&borrower.key(), &sol_mint, &usdc_mint, 10, 0);
+    assert_error(env.swap_collateral(&borrower, &sol_mint, u64::MAX, swap).await, ErrorCode::HealthFactorTooLow);
+}
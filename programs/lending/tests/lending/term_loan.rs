@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+use lending::{constants::{SECONDS_PER_YEAR, SOL_USB_FEED_ID, USDC_USD_FEED_ID}, error::ErrorCode};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+const BASE_RATE_BPS: u64 = 100;
+const RATE_SLOPE_BPS: u64 = 1_000;
+const PREMIUM_BPS: u64 = 200;
+const PENALTY_RATE_BPS: u64 = 500;
+const YEAR: i64 = SECONDS_PER_YEAR as i64;
+
+async fn setup() -> (TestEnv, TestUser) {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_rates(&usdc_mint, BASE_RATE_BPS, RATE_SLOPE_BPS).await.unwrap();
+    env.update_bank_term_rates(&usdc_mint, PREMIUM_BPS, PENALTY_RATE_BPS).await.unwrap();
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+
+    (env, borrower)
+}
+
+#[tokio::test]
+async fn borrow_fixed_locks_rate_from_utilization() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // A variable-rate borrow of 10_000 puts the USDC bank at 10% utilization.
+    let other: TestUser = env.create_user().await;
+    env.deposit(&other, &sol_mint, 100).await.unwrap();
+    env.borrow(&other, &usdc_mint, 10_000).await.unwrap();
+
+    let balance_before: u64 = env.token_balance(&borrower.usdc_token_account).await;
+    env.borrow_fixed(&borrower, &usdc_mint, 1_000, YEAR).await.unwrap();
+
+    let term_loan = env.term_loan(&borrower.key(), &usdc_mint).await;
+    assert_eq!(term_loan.rate_bps, BASE_RATE_BPS + RATE_SLOPE_BPS / 10 + PREMIUM_BPS);
+    assert_eq!(term_loan.principal, 1_000);
+    assert_eq!(env.token_balance(&borrower.usdc_token_account).await, balance_before + 1_000);
+    assert_eq!(env.bank(&usdc_mint).await.total_term_borrowed, 1_000);
+}
+
+#[tokio::test]
+async fn update_bank_term_rates_leaves_the_variable_curve() {
+    let (mut env, _) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.update_bank_term_rates(&usdc_mint, 300, 600).await.unwrap();
+
+    let bank = env.bank(&usdc_mint).await;
+    assert_eq!((bank.base_rate_bps, bank.rate_slope_bps), (BASE_RATE_BPS, RATE_SLOPE_BPS));
+    assert_eq!((bank.term_premium_bps, bank.term_penalty_rate_bps), (300, 600));
+}
+
+#[tokio::test]
+async fn borrow_fixed_without_duration_fails() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    assert_error(env.borrow_fixed(&borrower, &usdc_mint, 1_000, 0).await, ErrorCode::InvalidDuration);
+}
+
+#[tokio::test]
+async fn repay_after_maturity_pays_penalty_rate() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_fixed(&borrower, &usdc_mint, 1_000, YEAR).await.unwrap();
+    let deposits_before: u64 = env.bank(&usdc_mint).await.total_deposits;
+    env.warp_seconds(2 * YEAR).await;
+
+    // A year at 3%, then a year overdue at 3% plus the 5% penalty.
+    let balance_before: u64 = env.token_balance(&borrower.usdc_token_account).await;
+    env.repay_fixed(&borrower, &usdc_mint, u64::MAX).await.unwrap();
+
+    assert_eq!(balance_before - env.token_balance(&borrower.usdc_token_account).await, 1_110);
+    assert_eq!(env.bank(&usdc_mint).await.total_deposits, deposits_before + 110);
+    assert_eq!(env.bank(&usdc_mint).await.total_term_borrowed, 0);
+
+    let term_loan = term_loan_address(&bank_address(&env.market.pubkey(), &usdc_mint), &borrower.key());
+    assert!(env.ctx.banks_client.get_account(term_loan).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn overdue_term_loan_is_liquidatable() {
+    let (mut env, borrower) = setup().await;
+    let liquidator: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_fixed(&borrower, &usdc_mint, 1_000, 100).await.unwrap();
+
+    assert_error(env.liquidate_term_loan(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await, ErrorCode::NotUnderCollaterized);
+
+    env.warp_seconds(101).await;
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+    env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+
+    env.liquidate_term_loan(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+}
+
+#[tokio::test]
+async fn term_loan_uses_up_borrow_capacity() {
+    let (mut env, borrower) = setup().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // 10 SOL at 150 back 1_500 USDC, 1_000 of which the term loan takes.
+    env.borrow_fixed(&borrower, &usdc_mint, 1_000, YEAR).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.usdc_term_owed, 1_000);
+
+    assert_error(env.borrow(&borrower, &usdc_mint, 501).await, ErrorCode::OverBorrowableAmount);
+    env.borrow(&borrower, &usdc_mint, 500).await.unwrap();
+}
+
+#[tokio::test]
+async fn withdraw_leaving_term_loan_unbacked_fails() {
+    let (mut env, borrower) = setup().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow_fixed(&borrower, &usdc_mint, 1_000, YEAR).await.unwrap();
+
+    assert_error(env.withdraw(&borrower, &sol_mint, 4).await, ErrorCode::HealthFactorTooLow);
+    env.withdraw(&borrower, &sol_mint, 3).await.unwrap();
+
+    env.repay_fixed(&borrower, &usdc_mint, u64::MAX).await.unwrap();
+    assert_eq!(env.user(&borrower.key()).await.usdc_term_owed, 0);
+    env.withdraw(&borrower, &sol_mint, 7).await.unwrap();
+}
@@ -0,0 +1,134 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022;
+use lending::{constants::{SOL_USB_FEED_ID, USDC_USD_FEED_ID}, error::ErrorCode, state::{Bank, User}};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+const ONE_YEAR: i64 = 365 * 24 * 60 * 60;
+
+// 1% on every transfer of the SOL-like mint.
+const TRANSFER_FEE: MintExtension = MintExtension::TransferFee { basis_points: 100, maximum_fee: u64::MAX };
+
+async fn setup(sol_extensions: &[MintExtension]) -> TestEnv {
+    let mut env: TestEnv = TestEnv::with_token_program(spl_token_2022::ID, sol_extensions).await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+    env.init_bank(&sol_mint, LIQUIDATION_THRESHOLD, MAX_LTV).await.unwrap();
+    env.init_bank(&usdc_mint, LIQUIDATION_THRESHOLD, MAX_LTV).await.unwrap();
+    env
+}
+
+#[tokio::test]
+async fn deposit_credits_amount_received_after_transfer_fee() {
+    let mut env: TestEnv = setup(&[TRANSFER_FEE]).await;
+    let user: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&user, &sol_mint, 10_000).await.unwrap();
+
+    let bank: Bank = env.bank(&sol_mint).await;
+    let account: User = env.user(&user.key()).await;
+    assert_eq!(env.token_balance(&env.treasury_address(&sol_mint)).await, 9_900);
+    assert_eq!(bank.total_deposits, 9_900);
+    assert_eq!(account.deposited_sol, 9_900);
+    assert_eq!(account.deposited_sol_shares, 9_900);
+}
+
+#[tokio::test]
+async fn repay_credits_amount_received_after_transfer_fee() {
+    let mut env: TestEnv = setup(&[TRANSFER_FEE]).await;
+    let lender: TestUser = env.create_user().await;
+    let borrower: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&lender, &sol_mint, 100_000).await.unwrap();
+    env.deposit(&borrower, &usdc_mint, 200_000).await.unwrap();
+    env.borrow(&borrower, &sol_mint, 1_000).await.unwrap();
+
+    // Only 990 of the 1_000 repaid reaches the treasury.
+    env.repay(&borrower, &sol_mint, 1_000).await.unwrap();
+
+    let account: User = env.user(&borrower.key()).await;
+    assert_eq!(account.borrowed_sol, 10);
+    assert_eq!(env.bank(&sol_mint).await.total_borrowed, 10);
+}
+
+#[tokio::test]
+async fn init_bank_rejects_permanent_delegate_mint() {
+    let mut env: TestEnv = TestEnv::with_token_program(spl_token_2022::ID, &[MintExtension::PermanentDelegate]).await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    assert_error(env.init_bank(&sol_mint, LIQUIDATION_THRESHOLD, MAX_LTV).await, ErrorCode::UnsupportedMintExtension);
+}
+
+#[tokio::test]
+async fn init_bank_rejects_non_transferable_mint() {
+    let mut env: TestEnv = TestEnv::with_token_program(spl_token_2022::ID, &[MintExtension::NonTransferable]).await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+
+    assert_error(env.init_bank(&sol_mint, LIQUIDATION_THRESHOLD, MAX_LTV).await, ErrorCode::UnsupportedMintExtension);
+}
+
+#[tokio::test]
+async fn interest_bearing_collateral_is_valued_with_accrued_interest() {
+    let mut env: TestEnv = setup(&[MintExtension::InterestBearing { rate: 1_000 }]).await;
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // 10 SOL at 150 backs 1_500 USDC until the mint has accrued interest.
+    assert_error(env.borrow(&borrower, &usdc_mint, 1_600).await, ErrorCode::OverBorrowableAmount);
+
+    // A year at 10% grows the 10 SOL to about 11.05.
+    env.warp_seconds(ONE_YEAR).await;
+    env.set_price(SOL_USB_FEED_ID, SOL_PRICE).await;
+    env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+
+    env.borrow(&borrower, &usdc_mint, 1_600).await.unwrap();
+}
+
+#[tokio::test]
+async fn liquidation_seizes_interest_bearing_collateral_at_its_grown_value() {
+    let mut env: TestEnv = setup(&[MintExtension::InterestBearing { rate: 1_000 }]).await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // Liquidations repay half the debt for no bonus.
+    for mint in [sol_mint, usdc_mint] {
+        env.update_bank_liquidation(&mint, 5_000, 0, 0, 0, 0).await.unwrap();
+    }
+
+    let borrower: TestUser = env.create_borrower(100_000, 1_000).await;
+    let liquidator: TestUser = env.create_user().await;
+    env.borrow(&borrower, &usdc_mint, 100_000).await.unwrap();
+
+    // After a year at 10% each raw SOL is worth about 1.105, so the 625 SOL that 50_000 USDC buy at 80 take 565
+    // raw ones.
+    env.warp_seconds(ONE_YEAR).await;
+    env.set_price(SOL_USB_FEED_ID, 80).await;
+    env.set_price(USDC_USD_FEED_ID, USDC_PRICE).await;
+    env.liquidate(&liquidator, &borrower.key(), &sol_mint, &usdc_mint).await.unwrap();
+
+    assert_eq!(env.token_balance(&liquidator.sol_token_account).await, STARTING_BALANCE + 565);
+}
+
+#[tokio::test]
+async fn repay_max_covers_transfer_fee() {
+    let mut env: TestEnv = setup(&[TRANSFER_FEE]).await;
+    let lender: TestUser = env.create_user().await;
+    let borrower: TestUser = env.create_user().await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.deposit(&lender, &sol_mint, 100_000).await.unwrap();
+    env.deposit(&borrower, &usdc_mint, 200_000).await.unwrap();
+    env.borrow(&borrower, &sol_mint, 1_000).await.unwrap();
+
+    env.repay(&borrower, &sol_mint, u64::MAX).await.unwrap();
+
+    let account: User = env.user(&borrower.key()).await;
+    assert_eq!(account.borrowed_sol, 0);
+    assert_eq!(account.borrowed_sol_shares, 0);
+    assert_eq!(env.bank(&sol_mint).await.total_borrowed, 0);
+}
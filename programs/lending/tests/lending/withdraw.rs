@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use lending::{constants::SECONDS_PER_YEAR, error::ErrorCode};
+use solana_sdk::signer::Signer;
+
+use crate::common::*;
+
+#[tokio::test]
+async fn withdraw_returns_deposit() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&user, &mint, 1_000).await.unwrap();
+    env.withdraw(&user, &mint, 1_000).await.unwrap();
+
+    assert_eq!(env.token_balance(&user.sol_token_account).await, STARTING_BALANCE);
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 0);
+    assert_eq!(env.user(&user.key()).await.deposited_sol, 0);
+}
+
+#[tokio::test]
+async fn withdraw_more_than_deposited_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&user, &mint, 1_000).await.unwrap();
+
+    assert_error(env.withdraw(&user, &mint, 1_001).await, ErrorCode::InsufficientFunds);
+    assert_eq!(env.token_balance(&env.treasury_address(&mint)).await, 1_000);
+}
+
+#[tokio::test]
+async fn withdraw_without_deposit_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let lender: TestUser = env.create_user().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&lender, &mint, 1_000).await.unwrap();
+
+    assert_error(env.withdraw(&user, &mint, 1).await, ErrorCode::InsufficientFunds);
+}
+
+#[tokio::test]
+async fn withdraw_max_drains_the_whole_deposit() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let other: TestUser = env.create_user().await;
+    let user: TestUser = env.create_user().await;
+    let mint: Pubkey = env.sol_mint.pubkey();
+
+    env.deposit(&other, &mint, 333).await.unwrap();
+    env.deposit(&user, &mint, 1_000).await.unwrap();
+    env.withdraw(&user, &mint, u64::MAX).await.unwrap();
+
+    let account = env.user(&user.key()).await;
+    assert_eq!(account.deposited_sol, 0);
+    assert_eq!(account.deposited_sol_shares, 0);
+    assert_eq!(env.token_balance(&user.sol_token_account).await, STARTING_BALANCE);
+    assert_eq!(env.bank(&mint).await.total_deposit_shares, env.user(&other.key()).await.deposited_sol_shares);
+}
+
+#[tokio::test]
+async fn withdraw_max_includes_interest_accrued_since_the_last_update() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let borrower: TestUser = env.create_borrower(100_000, 1_000).await;
+    let lender: TestUser = env.create_user().await;
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    // 20_000 of the 200_000 USDC lent pays 10% a year, 1_000 of it for each lender's half.
+    env.deposit(&lender, &usdc_mint, 100_000).await.unwrap();
+    env.borrow(&borrower, &usdc_mint, 20_000).await.unwrap();
+    env.update_bank_rates(&usdc_mint, 1_000, 0).await.unwrap();
+    env.warp_seconds(SECONDS_PER_YEAR as i64).await;
+
+    env.withdraw(&lender, &usdc_mint, u64::MAX).await.unwrap();
+
+    assert_eq!(env.token_balance(&lender.usdc_token_account).await, STARTING_BALANCE + 1_000);
+    assert_eq!(env.user(&lender.key()).await.deposited_usdc_shares, 0);
+}
+
+#[tokio::test]
+async fn withdraw_leaving_debt_unbacked_fails() {
+    let mut env: TestEnv = TestEnv::with_banks().await;
+    let borrower: TestUser = env.create_borrower(100_000, 10).await;
+    let sol_mint: Pubkey = env.sol_mint.pubkey();
+    let usdc_mint: Pubkey = env.usdc_mint.pubkey();
+
+    env.borrow(&borrower, &usdc_mint, 1_000).await.unwrap();
+
+    // 6 SOL at 150 no longer cover 1_000 USDC of debt, 7 still do.
+    assert_error(env.withdraw(&borrower, &sol_mint, 4).await, ErrorCode::HealthFactorTooLow);
+    env.withdraw(&borrower, &sol_mint, 3).await.unwrap();
+}
@@ -0,0 +1,202 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+declare_id!("6CvVZGJgTz8GS9EEsHZm8syhQ43BLTPwkarF94jjPR6t");
+
+// A constant-price pool between two mints, good enough to stand in for a real AMM behind the lending program's
+// swap adapter. One raw unit of `mint_a` is worth `rate_numerator / rate_denominator` raw units of `mint_b`.
+#[program]
+pub mod mock_amm {
+    use super::*;
+
+    pub fn init_pool(ctx: Context<InitPool>, rate_numerator: u64, rate_denominator: u64) -> Result<()> {
+        let pool: &mut Pool = &mut ctx.accounts.pool;
+        pool.mint_a = ctx.accounts.mint_a.key();
+        pool.mint_b = ctx.accounts.mint_b.key();
+        pool.bump = ctx.bumps.pool;
+        set_rate(pool, rate_numerator, rate_denominator)
+    }
+
+    /// Moves the pool's price, so tests can make a swap come out worse than quoted.
+    pub fn update_rate(ctx: Context<UpdateRate>, rate_numerator: u64, rate_denominator: u64) -> Result<()> {
+        set_rate(&mut ctx.accounts.pool, rate_numerator, rate_denominator)
+    }
+
+    /// Swaps `amount_in` of `mint_in` from the user for `mint_out` at the pool's rate, failing below `min_amount_out`.
+    pub fn swap(ctx: Context<Swap>, amount_in: u64, min_amount_out: u64) -> Result<()> {
+        let pool: &Pool = &ctx.accounts.pool;
+        let amount_out: u64 = quote(pool, ctx.accounts.mint_in.key(), amount_in)?;
+        if amount_out < min_amount_out {
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        let cpi_program: AccountInfo = ctx.accounts.token_program.to_account_info();
+
+        let transfer_in: TransferChecked = TransferChecked {
+            from: ctx.accounts.user_in.to_account_info(),
+            to: ctx.accounts.vault_in.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+            mint: ctx.accounts.mint_in.to_account_info(),
+        };
+        token_interface::transfer_checked(CpiContext::new(cpi_program.clone(), transfer_in), amount_in, ctx.accounts.mint_in.decimals)?;
+
+        let transfer_out: TransferChecked = TransferChecked {
+            from: ctx.accounts.vault_out.to_account_info(),
+            to: ctx.accounts.user_out.to_account_info(),
+            authority: ctx.accounts.pool.to_account_info(),
+            mint: ctx.accounts.mint_out.to_account_info(),
+        };
+        let signer_seeds: &[&[&[u8]]] = &[&[b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref(), &[pool.bump]]];
+        let cpi_ctx: CpiContext<TransferChecked> = CpiContext::new(cpi_program, transfer_out).with_signer(signer_seeds);
+        token_interface::transfer_checked(cpi_ctx, amount_out, ctx.accounts.mint_out.decimals)
+    }
+}
+
+fn set_rate(pool: &mut Pool, rate_numerator: u64, rate_denominator: u64) -> Result<()> {
+    if rate_numerator == 0 || rate_denominator == 0 {
+        return Err(AmmError::InvalidRate.into());
+    }
+    pool.rate_numerator = rate_numerator;
+    pool.rate_denominator = rate_denominator;
+    Ok(())
+}
+
+/// What `amount_in` of `mint_in` buys of the pool's other mint, rounded down.
+pub fn quote(pool: &Pool, mint_in: Pubkey, amount_in: u64) -> Result<u64> {
+    let (numerator, denominator): (u64, u64) = match mint_in {
+        key if key == pool.mint_a => (pool.rate_numerator, pool.rate_denominator),
+        key if key == pool.mint_b => (pool.rate_denominator, pool.rate_numerator),
+        _ => return Err(AmmError::InvalidMint.into()),
+    };
+    u64::try_from(amount_in as u128 * numerator as u128 / denominator as u128).map_err(|_| AmmError::MathOverflow.into())
+}
+
+pub fn pool_address(mint_a: &Pubkey, mint_b: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"pool", mint_a.as_ref(), mint_b.as_ref()], &ID).0
+}
+
+pub fn vault_address(pool: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", pool.as_ref(), mint.as_ref()], &ID).0
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Pool {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub rate_numerator: u64,
+    pub rate_denominator: u64,
+    pub bump: u8,
+}
+
+#[derive(Accounts)]
+pub struct InitPool<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Pool::INIT_SPACE,
+        seeds = [b"pool", mint_a.key().as_ref(), mint_b.key().as_ref()],
+        bump,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        init,
+        payer = signer,
+        token::mint = mint_a,
+        token::authority = pool,
+        token::token_program = token_program,
+        seeds = [b"vault", pool.key().as_ref(), mint_a.key().as_ref()],
+        bump,
+    )]
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = signer,
+        token::mint = mint_b,
+        token::authority = pool,
+        token::token_program = token_program,
+        seeds = [b"vault", pool.key().as_ref(), mint_b.key().as_ref()],
+        bump,
+    )]
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRate<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref()],
+        bump = pool.bump,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    pub mint_in: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        constraint = mint_out.key() != mint_in.key() @ AmmError::InvalidMint,
+        constraint = mint_out.key() == pool.mint_a || mint_out.key() == pool.mint_b @ AmmError::InvalidMint,
+    )]
+    pub mint_out: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", pool.key().as_ref(), mint_in.key().as_ref()],
+        bump,
+    )]
+    pub vault_in: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", pool.key().as_ref(), mint_out.key().as_ref()],
+        bump,
+    )]
+    pub vault_out: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint_in,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_in: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint_out,
+        token::token_program = token_program,
+    )]
+    pub user_out: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[error_code]
+pub enum AmmError {
+    #[msg("Swap output is below the minimum")]
+    SlippageExceeded,
+    #[msg("Mint is not part of the pool")]
+    InvalidMint,
+    #[msg("Rate must be non-zero")]
+    InvalidRate,
+    #[msg("Math overflow")]
+    MathOverflow,
+}
@@ -0,0 +1,150 @@
+use anchor_lang::prelude::*;
+use arbitrary::Arbitrary;
+use solana_sdk::signer::Signer;
+
+use lending::{accounting, constants::{SOL_USB_FEED_ID, USDC_USD_FEED_ID}, state::{Bank, User}};
+
+use crate::common::*;
+
+const USERS: usize = 3;
+const MAX_ACTIONS: usize = 32;
+
+#[derive(Arbitrary, Debug, Clone, Copy, PartialEq)]
+pub enum Asset {
+    Sol,
+    Usdc,
+}
+
+// Most of the interesting paths need amounts a user can actually cover, so inputs pick between a
+// bounded amount and a raw one that is free to hit the overflow edges.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum Amount {
+    Bounded(u32),
+    Raw(u64),
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum FuzzAction {
+    Deposit { user: u8, asset: Asset, amount: Amount },
+    Withdraw { user: u8, asset: Asset, amount: Amount },
+    Borrow { user: u8, asset: Asset, amount: Amount },
+    Repay { user: u8, asset: Asset, amount: Amount },
+    Liquidate { liquidator: u8, borrower: u8, collateral: Asset, borrowed: Asset },
+    WarpClock { seconds: u16 },
+    SetPrice { asset: Asset, price: i64 },
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct FuzzData {
+    pub actions: Vec<FuzzAction>,
+}
+
+impl Amount {
+    fn get(self) -> u64 {
+        match self {
+            Amount::Bounded(amount) => amount as u64 % (STARTING_BALANCE + 1),
+            Amount::Raw(amount) => amount,
+        }
+    }
+}
+
+impl FuzzData {
+    /// Replays the actions against a fresh environment, checking the invariants after each one.
+    pub async fn run(self) {
+        let mut env: TestEnv = TestEnv::with_banks().await;
+        // Interest is charged, and part of it kept back for the insurance fund, so the books have fees to account for.
+        for asset in [Asset::Sol, Asset::Usdc] {
+            let mint: Pubkey = mint(&env, asset);
+            env.update_bank_rates(&mint, 500, 2_000).await.unwrap();
+            env.update_bank_insurance(&mint, 1_000, 0, 86_400).await.unwrap();
+        }
+        let mut users: Vec<TestUser> = Vec::with_capacity(USERS);
+        for _ in 0..USERS {
+            users.push(env.create_user().await);
+        }
+
+        for action in self.actions.into_iter().take(MAX_ACTIONS) {
+            // Instructions are allowed to fail; they just must not panic or break the books.
+            let _ = match action {
+                FuzzAction::Deposit { user, asset, amount } => {
+                    let mint: Pubkey = mint(&env, asset);
+                    env.deposit(&users[user as usize % USERS], &mint, amount.get()).await
+                }
+                FuzzAction::Withdraw { user, asset, amount } => {
+                    let mint: Pubkey = mint(&env, asset);
+                    env.withdraw(&users[user as usize % USERS], &mint, amount.get()).await
+                }
+                FuzzAction::Borrow { user, asset, amount } => {
+                    let mint: Pubkey = mint(&env, asset);
+                    env.borrow(&users[user as usize % USERS], &mint, amount.get()).await
+                }
+                FuzzAction::Repay { user, asset, amount } => {
+                    let mint: Pubkey = mint(&env, asset);
+                    env.repay(&users[user as usize % USERS], &mint, amount.get()).await
+                }
+                FuzzAction::Liquidate { liquidator, borrower, collateral, borrowed } => {
+                    let collateral_mint: Pubkey = mint(&env, collateral);
+                    let borrowed_mint: Pubkey = mint(&env, borrowed);
+                    let borrower: Pubkey = users[borrower as usize % USERS].key();
+                    env.liquidate(&users[liquidator as usize % USERS], &borrower, &collateral_mint, &borrowed_mint).await
+                }
+                FuzzAction::WarpClock { seconds } => {
+                    env.warp_seconds(seconds as i64).await;
+                    Ok(())
+                }
+                FuzzAction::SetPrice { asset, price } => {
+                    let feed: &str = if asset == Asset::Sol { SOL_USB_FEED_ID } else { USDC_USD_FEED_ID };
+                    env.set_price(feed, price).await;
+                    Ok(())
+                }
+            };
+
+            check_invariants(&mut env, &users).await;
+        }
+    }
+}
+
+fn mint(env: &TestEnv, asset: Asset) -> Pubkey {
+    match asset {
+        Asset::Sol => env.sol_mint.pubkey(),
+        Asset::Usdc => env.usdc_mint.pubkey(),
+    }
+}
+
+async fn check_invariants(env: &mut TestEnv, users: &[TestUser]) {
+    let mut accounts: Vec<User> = Vec::with_capacity(users.len());
+    for user in users {
+        accounts.push(env.user(&user.key()).await);
+    }
+
+    for asset in [Asset::Sol, Asset::Usdc] {
+        let mint: Pubkey = mint(env, asset);
+        let bank: Bank = env.bank(&mint).await;
+        let treasury: u64 = env.token_balance(&env.treasury_address(&mint)).await;
+
+        // The insurance reserve kept back from interest stays in the treasury until it is swept into the fund.
+        let fees: u64 = bank.insurance_fees_pending;
+        let expected: u64 = bank
+            .total_deposits
+            .checked_sub(bank.total_borrowed)
+            .and_then(|available| available.checked_add(fees))
+            .unwrap_or_else(|| panic!("{asset:?} bank has more borrowed than deposited: {} > {}", bank.total_borrowed, bank.total_deposits));
+        assert_eq!(treasury, expected, "{asset:?} treasury balance does not match the bank's books");
+
+        let (deposit_shares, borrowed_shares): (u64, u64) = accounts.iter().fold((0, 0), |(deposits, borrows), account| match asset {
+            Asset::Sol => (deposits + account.deposited_sol_shares, borrows + account.borrowed_sol_shares),
+            Asset::Usdc => (deposits + account.deposited_usdc_shares, borrows + account.borrowed_usdc_shares),
+        });
+        assert_eq!(deposit_shares, bank.total_deposit_shares, "{asset:?} deposit shares do not add up");
+        assert_eq!(borrowed_shares, bank.total_borrowed_shares, "{asset:?} borrowed shares do not add up");
+
+        let redeemable: u64 = accounts
+            .iter()
+            .map(|account| {
+                let shares: u64 = if asset == Asset::Sol { account.deposited_sol_shares } else { account.deposited_usdc_shares };
+                accounting::deposit_value(&bank, shares).unwrap()
+            })
+            .sum();
+        assert!(redeemable <= bank.total_deposits, "{asset:?} depositors can redeem more than the bank holds");
+    }
+}
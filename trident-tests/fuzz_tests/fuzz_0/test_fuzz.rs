@@ -0,0 +1,29 @@
+//! Coverage-guided fuzz target for the lending program.
+//!
+//! Each input is decoded into a sequence of `deposit`, `withdraw`, `borrow`, `repay` and `liquidate` calls mixed with
+//! clock and price changes, which are replayed against the program running in-process. Invariants are checked after
+//! every step, so a failing assertion or a panic inside the program is reported as a crash.
+//!
+//! Run it from `trident-tests` with `cargo hfuzz run fuzz_0`, and replay a crash with
+//! `cargo hfuzz run-debug fuzz_0 hfuzz_workspace/fuzz_0/*.fuzz`.
+
+use honggfuzz::fuzz;
+use tokio::runtime::Runtime;
+
+use fuzz_instructions::FuzzData;
+
+// The integration tests' environment already knows how to stand the program up, so the fuzzer reuses it.
+#[allow(dead_code)]
+#[path = "../../../programs/lending/tests/lending/common.rs"]
+mod common;
+mod fuzz_instructions;
+
+fn main() {
+    let runtime: Runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+    loop {
+        fuzz!(|data: FuzzData| {
+            runtime.block_on(data.run());
+        });
+    }
+}